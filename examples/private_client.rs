@@ -2,18 +2,15 @@ extern crate env_logger;
 extern crate gdax_client;
 extern crate uuid;
 
-use gdax_client::{NewOrder, PrivateClient, Side, SizeOrFunds};
+use gdax_client::Credentials;
 use uuid::Uuid;
 
-const CB_KEY: &'static str = env!("CB_KEY");
-const CB_SECRET: &'static str = env!("CB_SECRET");
-const CB_PASSPHRASE: &'static str = env!("CB_PASSPHRASE");
-
 
 fn main() {
     env_logger::init().unwrap();
 
-    let mut private_client = PrivateClient::new(CB_KEY, CB_SECRET, CB_PASSPHRASE);
+    let credentials = Credentials::load(None).expect("no usable credentials");
+    let mut private_client = credentials.client();
 
     if let Ok(accounts) = private_client.get_accounts() {
         println!("Accounts: {:?}", accounts);
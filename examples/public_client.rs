@@ -4,6 +4,7 @@ extern crate gdax_client;
 
 use chrono::TimeZone;
 use gdax_client::PublicClient;
+use gdax_client::public::Granularity;
 
 
 fn main() {
@@ -18,9 +19,9 @@ fn main() {
     println!("Latest Trades: {:?}", public_client.get_trades("BTC-USD"));
     println!("Historic Rates: {:?}",
              public_client.get_historic_rates("BTC-USD",
-                                              chrono::Utc.ymd(2016, 6, 10).and_hms(0, 0, 0),
-                                              chrono::Utc.ymd(2016, 6, 11).and_hms(12, 0, 0),
-                                              15 * 60));
+                                              chrono::Utc.with_ymd_and_hms(2016, 6, 10, 0, 0, 0).unwrap(),
+                                              chrono::Utc.with_ymd_and_hms(2016, 6, 11, 12, 0, 0).unwrap(),
+                                              Granularity::FifteenMinutes));
     println!("24Hr stats: {:?}", public_client.get_24hr_stats("BTC-USD"));
     println!("Currencies: {:?}", public_client.get_currencies());
     println!("Time: {:?}", public_client.get_time());
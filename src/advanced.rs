@@ -0,0 +1,507 @@
+//! The Advanced Trade API.
+//!
+//! This is the exchange's current API, served from `api.coinbase.com` under
+//! `/api/v3/brokerage`. Requests are authenticated with a `JwtSigner`
+//! rather than the API key headers of the legacy API, amounts are decimal
+//! strings, and lists are paginated with a `cursor` in the response body.
+//! The list methods here follow the cursor and return every page.
+
+use chrono::{DateTime, Utc};
+
+use serde::Deserialize;
+use serde_json::{from_value, ser, Value};
+
+use std::fmt;
+use std::str::FromStr;
+
+use super::ApiError;
+use super::Error;
+use super::Response;
+use super::Side;
+use super::{Funds, NewOrder, Size};
+use super::clock::local_now;
+use super::public::{self, Granularity};
+use super::signer::{random_hex, JwtSigner, RequestSigner, SigningRequest};
+use super::transport::{CurlTransport, Method, Request, Transport};
+
+const ADVANCED_API_URL: &str = "https://api.coinbase.com";
+const ADVANCED_API_PATH: &str = "/api/v3/brokerage";
+
+// Sides are upper case in this API, unlike the legacy one
+mod upper_case_side {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::Side;
+
+    pub fn serialize<S>(side: &Side, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        serializer.serialize_str(match *side {
+            Side::Buy => "BUY",
+            Side::Sell => "SELL"
+        })
+    }
+
+    // Side's own Deserialize ignores case
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Side, D::Error>
+        where D: Deserializer<'de>
+    {
+        Side::deserialize(deserializer)
+    }
+}
+
+/// An amount of a currency.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Balance {
+    pub value: String,
+    pub currency: String
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Account {
+    pub uuid: String,
+    pub name: String,
+    pub currency: String,
+    pub available_balance: Balance,
+    pub hold: Option<Balance>,
+    #[serde(default)]
+    pub default: bool,
+    #[serde(default)]
+    pub active: bool,
+    #[serde(rename = "type", default)]
+    pub account_type: String,
+    #[serde(default)]
+    pub ready: bool
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Product {
+    pub product_id: String,
+    #[serde(default)]
+    pub price: String,
+    pub base_currency_id: String,
+    pub quote_currency_id: String,
+    pub base_increment: String,
+    pub quote_increment: String,
+    pub base_min_size: String,
+    pub base_max_size: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub trading_disabled: bool,
+    #[serde(default)]
+    pub product_type: String
+}
+
+/// A candle, with its prices as the API returns them. `to_candle` converts
+/// it to the legacy API's `public::Candle`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Candle {
+    /// Seconds since the epoch.
+    pub start: String,
+    pub low: String,
+    pub high: String,
+    pub open: String,
+    pub close: String,
+    pub volume: String
+}
+
+impl Candle {
+    pub fn to_candle(&self) -> Result<public::Candle, Error> {
+        fn parse<T: FromStr>(s: &str) -> Result<T, Error> {
+            s.parse().map_err(|_| Error::InvalidNumber(s.to_owned()))
+        }
+
+        Ok(public::Candle {
+            time: parse(&self.start)?,
+            low: parse(&self.low)?,
+            high: parse(&self.high)?,
+            open: parse(&self.open)?,
+            close: parse(&self.close)?,
+            volume: parse(&self.volume)?
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct MarketTrade {
+    pub trade_id: String,
+    pub product_id: String,
+    pub price: String,
+    pub size: String,
+    pub time: DateTime<Utc>,
+    #[serde(with = "upper_case_side")]
+    pub side: Side
+}
+
+/// The latest trades of a product, with the best bid and ask.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct MarketTrades {
+    pub trades: Vec<MarketTrade>,
+    #[serde(default)]
+    pub best_bid: String,
+    #[serde(default)]
+    pub best_ask: String
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Order {
+    pub order_id: String,
+    pub product_id: String,
+    #[serde(with = "upper_case_side")]
+    pub side: Side,
+    #[serde(default)]
+    pub client_order_id: String,
+    pub status: String,
+    #[serde(default)]
+    pub time_in_force: String,
+    pub created_time: DateTime<Utc>,
+    #[serde(default)]
+    pub filled_size: String,
+    #[serde(default)]
+    pub average_filled_price: String,
+    #[serde(default)]
+    pub number_of_fills: String,
+    #[serde(default)]
+    pub filled_value: String,
+    #[serde(default)]
+    pub total_fees: String,
+    #[serde(default)]
+    pub order_type: String,
+    /// The order's type and parameters, as sent when it was created.
+    #[serde(default)]
+    pub order_configuration: Value
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Fill {
+    pub entry_id: String,
+    pub trade_id: String,
+    pub order_id: String,
+    pub trade_time: DateTime<Utc>,
+    #[serde(default)]
+    pub trade_type: String,
+    pub price: String,
+    pub size: String,
+    pub commission: String,
+    pub product_id: String,
+    #[serde(default)]
+    pub liquidity_indicator: String,
+    #[serde(with = "upper_case_side")]
+    pub side: Side
+}
+
+/// The outcome of cancelling one order.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct CancelResult {
+    pub success: bool,
+    #[serde(default)]
+    pub failure_reason: String,
+    pub order_id: String
+}
+
+// The name of each candle width in this API
+fn granularity_name(granularity: Granularity) -> &'static str {
+    match granularity {
+        Granularity::OneMinute => "ONE_MINUTE",
+        Granularity::FiveMinutes => "FIVE_MINUTE",
+        Granularity::FifteenMinutes => "FIFTEEN_MINUTE",
+        Granularity::OneHour => "ONE_HOUR",
+        Granularity::SixHours => "SIX_HOUR",
+        Granularity::OneDay => "ONE_DAY"
+    }
+}
+
+// Percent-encodes a query string value, leaving only unreserved
+// characters as they are
+fn query_value(value: &str) -> String {
+    value.bytes().map(|b| match b {
+        b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+        _ if b.is_ascii_alphanumeric() => (b as char).to_string(),
+        _ => format!("%{:02X}", b)
+    }).collect()
+}
+
+// The order_configuration object for a legacy order description. Stop
+// orders become stop-limit orders limited at the stop price, triggered on
+// the way up for buys and on the way down for sells. Stop-limit orders
+// can only be sized in the base currency, so stop orders for funds are
+// refused.
+fn order_configuration(order: &NewOrder) -> Result<Value, Error> {
+    let amount = |size_or_funds| match size_or_funds {
+        Size(size) => ("base_size", size.to_string()),
+        Funds(funds) => ("quote_size", funds.to_string())
+    };
+
+    Ok(match *order {
+        NewOrder::Limit { price, size, .. } => json!({
+            "limit_limit_gtc": {
+                "base_size": size.to_string(),
+                "limit_price": price.to_string()
+            }
+        }),
+        NewOrder::Market { size_or_funds, .. } => {
+            let (name, value) = amount(size_or_funds);
+            json!({ "market_market_ioc": { name: value } })
+        }
+        NewOrder::Stop { size_or_funds: Funds(_), .. } => {
+            return Err(Error::Api(ApiError {
+                message: "stop orders must be sized in the base currency".to_owned(),
+                status: None
+            }));
+        }
+        NewOrder::Stop { side, price, size_or_funds: Size(size), .. } => {
+            json!({
+                "stop_limit_stop_limit_gtc": {
+                    "base_size": size.to_string(),
+                    "limit_price": price.to_string(),
+                    "stop_price": price.to_string(),
+                    "stop_direction": match side {
+                        Side::Buy => "STOP_DIRECTION_STOP_UP",
+                        Side::Sell => "STOP_DIRECTION_STOP_DOWN"
+                    }
+                }
+            })
+        }
+    })
+}
+
+/// A client for the Advanced Trade API.
+pub struct Client {
+    transport: Box<dyn Transport + Send>,
+    signer: Box<dyn RequestSigner>
+}
+
+impl Client {
+    /// `key_name` and `pem` are the name and private key of a Cloud API
+    /// key. Fails with `Error::InvalidSecretKey` if the key can't be read.
+    pub fn new(key_name: &str, pem: &str) -> Result<Client, Error> {
+        Ok(Client::with_signer(JwtSigner::new(key_name, pem)?, CurlTransport::new()))
+    }
+
+    /// Creates a client that authenticates its requests with `signer` and
+    /// sends them through `transport`.
+    pub fn with_signer<S, T>(signer: S, transport: T) -> Client
+        where S: RequestSigner + 'static,
+              T: Transport + Send + 'static
+    {
+        Client {
+            transport: Box::new(transport),
+            signer: Box::new(signer)
+        }
+    }
+
+    /// Issues a signed request to `path` (relative to
+    /// `/api/v3/brokerage`, including any query string) and returns the
+    /// decoded value together with the raw response.
+    pub fn request<T>(&mut self, method: Method, path: &str, body: &str) -> Result<Response<T>, Error>
+        where for<'de> T: Deserialize<'de>
+    {
+        let path = format!("{}{}", ADVANCED_API_PATH, path);
+        let url = format!("{}{}", ADVANCED_API_URL, path);
+
+        let mut headers = vec![("Accept".to_owned(), "application/json".to_owned())];
+        headers.extend(self.signer.sign(&SigningRequest {
+            method,
+            url: &url,
+            path: &path,
+            body,
+            timestamp: &(local_now() as i64).to_string()
+        })?);
+        if !body.is_empty() {
+            headers.push(("Content-Type".to_owned(), "application/json".to_owned()));
+        }
+
+        let request = Request {
+            method,
+            url,
+            headers,
+            body: body.to_owned()
+        };
+        self.transport.execute(&request)?.decode()
+    }
+
+    fn get_and_decode<T>(&mut self, path: &str) -> Result<T, Error>
+        where for<'de> T: Deserialize<'de>
+    {
+        Ok(self.request(Method::Get, path, "")?.value)
+    }
+
+    // Follows the cursor of a paginated list, collecting the items of the
+    // list called `name` on each page. `path` must already have a query
+    // string.
+    fn get_all<T>(&mut self, path: &str, name: &str) -> Result<Vec<T>, Error>
+        where for<'de> T: Deserialize<'de>
+    {
+        let mut all = Vec::new();
+        let mut cursor = String::new();
+        loop {
+            let page_path = if cursor.is_empty() {
+                path.to_owned()
+            } else {
+                format!("{}&cursor={}", path, query_value(&cursor))
+            };
+            let mut page: Value = self.get_and_decode(&page_path)?;
+
+            if let Some(items) = page.get_mut(name) {
+                all.extend(from_value::<Vec<T>>(items.take())?);
+            }
+
+            // Accounts and orders say whether there's more; fills only
+            // leave the cursor empty on the last page
+            let next = page["cursor"].as_str().unwrap_or("").to_owned();
+            if next.is_empty() || next == cursor || page["has_next"] == Value::Bool(false) {
+                return Ok(all);
+            }
+            cursor = next;
+        }
+    }
+
+    pub fn get_accounts(&mut self) -> Result<Vec<Account>, Error> {
+        self.get_all("/accounts?limit=250", "accounts")
+    }
+
+    pub fn get_account(&mut self, uuid: &str) -> Result<Account, Error> {
+        #[derive(Deserialize)]
+        struct AccountResult { account: Account }
+
+        Ok(self.get_and_decode::<AccountResult>(&format!("/accounts/{}", uuid))?.account)
+    }
+
+    pub fn get_products(&mut self) -> Result<Vec<Product>, Error> {
+        #[derive(Deserialize)]
+        struct ProductsResult { products: Vec<Product> }
+
+        Ok(self.get_and_decode::<ProductsResult>("/products")?.products)
+    }
+
+    pub fn get_product(&mut self, product_id: &str) -> Result<Product, Error> {
+        self.get_and_decode(&format!("/products/{}", product_id))
+    }
+
+    /// Candles between `start` and `end`, newest first. The API returns at
+    /// most 350 candles per request.
+    pub fn get_candles(&mut self,
+                       product_id: &str,
+                       start: DateTime<Utc>,
+                       end: DateTime<Utc>,
+                       granularity: Granularity)
+        -> Result<Vec<Candle>, Error>
+    {
+        #[derive(Deserialize)]
+        struct CandlesResult { candles: Vec<Candle> }
+
+        let path = format!("/products/{}/candles?start={}&end={}&granularity={}",
+                           product_id,
+                           start.timestamp(),
+                           end.timestamp(),
+                           granularity_name(granularity));
+        Ok(self.get_and_decode::<CandlesResult>(&path)?.candles)
+    }
+
+    /// The `limit` latest trades of a product.
+    pub fn get_market_trades(&mut self, product_id: &str, limit: u32) -> Result<MarketTrades, Error> {
+        self.get_and_decode(&format!("/products/{}/ticker?limit={}", product_id, limit))
+    }
+
+    /// Places `order` and returns its id. Limit orders are good until
+    /// cancelled and market orders immediate or cancel; stop orders are
+    /// placed as stop-limit orders limited at the stop price, and must be
+    /// sized in the base currency.
+    pub fn create_order(&mut self, order: &NewOrder) -> Result<String, Error> {
+        #[derive(Serialize)]
+        struct CreateOrder<'a> {
+            client_order_id: String,
+            product_id: &'a str,
+            #[serde(with = "upper_case_side")]
+            side: Side,
+            order_configuration: Value
+        }
+
+        #[derive(Deserialize)]
+        struct CreateOrderResult {
+            success: bool,
+            #[serde(default)]
+            success_response: Option<SuccessResponse>,
+            #[serde(default)]
+            error_response: Option<Value>
+        }
+
+        #[derive(Deserialize)]
+        struct SuccessResponse { order_id: String }
+
+        let (side, product_id) = match *order {
+            NewOrder::Limit { side, ref product_id, .. } |
+            NewOrder::Market { side, ref product_id, .. } |
+            NewOrder::Stop { side, ref product_id, .. } => (side, product_id)
+        };
+        let body = ser::to_string(&CreateOrder {
+            client_order_id: random_hex(16)?,
+            product_id,
+            side,
+            order_configuration: order_configuration(order)?
+        })?;
+
+        // Rejected orders still come back with a success status
+        let result: CreateOrderResult = self.request(Method::Post, "/orders", &body)?.value;
+        match result.success_response {
+            Some(response) if result.success => Ok(response.order_id),
+            _ => {
+                let message = result.error_response
+                                    .map(|e| e["message"].as_str().or(e["error"].as_str()).unwrap_or("").to_owned())
+                                    .unwrap_or_default();
+                Err(Error::Api(ApiError { message, status: None }))
+            }
+        }
+    }
+
+    /// Cancels the orders with the given ids, reporting on each.
+    pub fn cancel_orders(&mut self, order_ids: &[&str]) -> Result<Vec<CancelResult>, Error> {
+        #[derive(Deserialize)]
+        struct CancelResults { results: Vec<CancelResult> }
+
+        let body = ser::to_string(&json!({ "order_ids": order_ids }))?;
+        Ok(self.request::<CancelResults>(Method::Post, "/orders/batch_cancel", &body)?.value.results)
+    }
+
+    /// Orders of `product_id`, or of every product, optionally with the
+    /// given status, such as `OPEN` or `FILLED`.
+    pub fn list_orders(&mut self, product_id: Option<&str>, status: Option<&str>) -> Result<Vec<Order>, Error> {
+        let mut path = "/orders/historical/batch?limit=1000".to_owned();
+        if let Some(product_id) = product_id {
+            path.push_str(&format!("&product_id={}", product_id));
+        }
+        if let Some(status) = status {
+            path.push_str(&format!("&order_status={}", status));
+        }
+        self.get_all(&path, "orders")
+    }
+
+    pub fn get_order(&mut self, order_id: &str) -> Result<Order, Error> {
+        #[derive(Deserialize)]
+        struct OrderResult { order: Order }
+
+        Ok(self.get_and_decode::<OrderResult>(&format!("/orders/historical/{}", order_id))?.order)
+    }
+
+    /// Fills of the order `order_id`, or of every order of `product_id`.
+    pub fn get_fills(&mut self, order_id: Option<&str>, product_id: Option<&str>) -> Result<Vec<Fill>, Error> {
+        let mut path = "/orders/historical/fills?limit=1000".to_owned();
+        if let Some(order_id) = order_id {
+            path.push_str(&format!("&order_id={}", order_id));
+        }
+        if let Some(product_id) = product_id {
+            path.push_str(&format!("&product_id={}", product_id));
+        }
+        self.get_all(&path, "fills")
+    }
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Client")
+         .field("signer", &self.signer)
+         .finish()
+    }
+}
+
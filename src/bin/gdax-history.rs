@@ -0,0 +1,95 @@
+extern crate chrono;
+extern crate gdax_client;
+
+use chrono::{DateTime, Utc};
+use gdax_client::PublicClient;
+use gdax_client::history::{self, Format};
+use gdax_client::public::Granularity;
+
+use std::env;
+use std::process;
+
+const USAGE: &str = "\
+usage: gdax-history (candles|trades) PRODUCT START END OUTPUT [options]
+
+Downloads candles or trades for PRODUCT made between START and END
+(RFC 3339 times) into OUTPUT, continuing after the last row already there.
+
+options:
+    --granularity SECONDS  candle width: 60, 300, 900, 3600, 21600 or 86400
+                           (default 60)
+    --format FORMAT        csv or columnar (default: csv if OUTPUT ends in
+                           .csv, columnar otherwise)";
+
+fn fail(message: &str) -> ! {
+    eprintln!("gdax-history: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn parse_time(s: &str) -> DateTime<Utc> {
+    match DateTime::parse_from_rfc3339(s) {
+        Ok(time) => time.with_timezone(&Utc),
+        Err(e) => fail(&format!("invalid time {:?}: {}", s, e))
+    }
+}
+
+fn main() {
+    let mut positional = Vec::new();
+    let mut granularity = Granularity::OneMinute;
+    let mut format = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--granularity" => {
+                granularity = match args.next().and_then(|s| s.parse().ok()).and_then(Granularity::from_seconds) {
+                    Some(granularity) => granularity,
+                    None => fail("--granularity needs one of the supported widths")
+                };
+            }
+            "--format" => {
+                format = match args.next().as_ref().and_then(|s| Format::from_name(s)) {
+                    Some(format) => Some(format),
+                    None => fail("--format needs csv or columnar")
+                };
+            }
+            _ if arg.starts_with("--") => fail(&format!("unknown option {}", arg)),
+            _ => positional.push(arg)
+        }
+    }
+
+    if positional.len() != 5 {
+        fail("expected a kind, product, start, end and output");
+    }
+    let (kind, product, output) = (&positional[0], &positional[1], &positional[4]);
+    let start = parse_time(&positional[2]);
+    let end = parse_time(&positional[3]);
+    let format = format.unwrap_or_else(|| Format::from_path(output));
+
+    let mut client = PublicClient::new();
+    let result = match kind.as_str() {
+        "candles" => history::download_candles(&mut client, product, start, end, granularity, output, format),
+        "trades" => history::download_trades(&mut client, product, start, end, output, format),
+        _ => fail(&format!("unknown kind {:?}", kind))
+    };
+
+    match result {
+        Ok(download) => {
+            if let Some(last) = download.resumed_from {
+                eprintln!("resumed after {}", last);
+            }
+            eprintln!("wrote {} {} to {}", download.written, kind, output);
+            for (from, to) in download.gaps {
+                eprintln!("no trades in [{}, {})", from, to);
+            }
+        }
+        Err(e) => {
+            eprintln!("gdax-history: {}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,358 @@
+extern crate chrono;
+extern crate gdax_client;
+extern crate serde;
+extern crate serde_json;
+extern crate uuid;
+
+use chrono::{DateTime, Utc};
+use gdax_client::{Credentials, Error, NewOrder, PrivateClient, PublicClient, Side, SizeOrFunds};
+use gdax_client::public::Granularity;
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+use std::env;
+use std::io;
+use std::process;
+
+const USAGE: &str = "\
+usage: gdax [--json] [--config FILE] [--profile NAME] COMMAND [ARGS]
+
+Public commands:
+    products                          list the products traded
+    book PRODUCT [--level 1|2|3]      show the order book (default level 2)
+    ticker PRODUCT                    show the last trade and best bid and ask
+    trades PRODUCT                    show the latest trades
+    candles PRODUCT START END [--granularity SECONDS]
+                                      show candles between two RFC 3339 times
+
+Private commands:
+    accounts                          list accounts
+    ledger ACCOUNT_ID                 show an account's ledger
+    holds ACCOUNT_ID                  show an account's holds
+    orders [ORDER_ID]                 list open orders, or show one order
+    place (buy|sell) PRODUCT SIZE [--price PRICE] [--stop PRICE] [--funds] [--yes]
+                                      place a limit order with --price, a
+                                      stop order with --stop, or a market
+                                      order; --funds spends SIZE in the
+                                      quote currency instead. Asks for
+                                      confirmation unless --yes is given
+    cancel (ORDER_ID | --all [--product PRODUCT])
+                                      cancel one or all open orders
+
+Options:
+    --json                print JSON instead of tables
+    --config FILE         read credentials from a TOML or JSON file
+    --profile NAME        use the named profile from the credentials file
+    --credentials-fd FD   read credentials from an inherited file descriptor,
+                          3 or above
+
+Private commands read credentials from CB_KEY, CB_SECRET and
+CB_PASSPHRASE, or else from the \"default\" profile (or --profile) of
+$GDAX_CREDENTIALS or ~/.gdax/credentials.toml.
+
+Requests go to $GDAX_API_URL if it's set, e.g. to use a sandbox.";
+
+fn fail(message: &str) -> ! {
+    eprintln!("gdax: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, Option<String>)>
+}
+
+impl Args {
+    // Options that take a value; everything else starting with `--` is a flag
+    const VALUED: &'static [&'static str] = &["--config", "--profile", "--credentials-fd", "--level",
+                                              "--granularity", "--price", "--stop", "--product"];
+
+    fn parse() -> Args {
+        let mut args = Args { positional: Vec::new(), options: Vec::new() };
+        let mut iter = env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            if Args::VALUED.contains(&arg.as_str()) {
+                match iter.next() {
+                    Some(value) => args.options.push((arg, Some(value))),
+                    None => fail(&format!("{} needs a value", arg))
+                }
+            } else if arg.starts_with("--") || arg == "-h" {
+                args.options.push((arg, None));
+            } else {
+                args.positional.push(arg);
+            }
+        }
+        args
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(n, _)| n == name)
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        self.options.iter().find(|(n, _)| n == name).and_then(|(_, v)| v.as_ref().map(|v| v.as_str()))
+    }
+
+    fn arg(&self, index: usize, what: &str) -> &str {
+        match self.positional.get(index) {
+            Some(arg) => arg,
+            None => fail(&format!("missing {}", what))
+        }
+    }
+}
+
+fn parse<T: std::str::FromStr>(s: &str, what: &str) -> T {
+    match s.parse() {
+        Ok(value) => value,
+        Err(_) => fail(&format!("invalid {} {:?}", what, s))
+    }
+}
+
+fn parse_time(s: &str) -> DateTime<Utc> {
+    match DateTime::parse_from_rfc3339(s) {
+        Ok(time) => time.with_timezone(&Utc),
+        Err(_) => fail(&format!("invalid time {:?}", s))
+    }
+}
+
+fn api_url() -> Option<String> {
+    env::var("GDAX_API_URL").ok()
+}
+
+fn public_client() -> PublicClient {
+    match api_url() {
+        Some(url) => PublicClient::new().base_url(&url),
+        None => PublicClient::new()
+    }
+}
+
+fn private_client(args: &Args) -> PrivateClient {
+    let profile = args.value("--profile");
+    let credentials = if let Some(fd) = args.value("--credentials-fd") {
+        read_fd(parse(fd, "file descriptor"), profile)
+    } else if let Some(path) = args.value("--config") {
+        Credentials::from_file(path, profile)
+    } else {
+        Credentials::load(profile)
+    };
+
+    let client = match credentials {
+        Ok(credentials) => credentials.client(),
+        Err(Error::MissingCredentials(why)) => fail(&format!("no credentials: {}", why)),
+        Err(e) => fail(&format!("can't load credentials: {}", e))
+    };
+    match api_url() {
+        Some(url) => client.base_url(&url),
+        None => client
+    }
+}
+
+// Asks on the terminal before an order is placed, and exits unless the
+// answer is yes
+fn confirm(order: &NewOrder) {
+    eprint!("place {}? [y/N] ", serde_json::to_string(order).unwrap());
+    // A failed read leaves the answer empty, which is a no
+    let mut answer = String::new();
+    let _ = io::stdin().read_line(&mut answer);
+    match answer.trim() {
+        "y" | "Y" | "yes" => {}
+        _ => {
+            eprintln!("gdax: order not placed");
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn read_fd(fd: u32, profile: Option<&str>) -> Result<Credentials, Error> {
+    if fd < 3 {
+        fail("--credentials-fd can't be stdin, stdout or stderr");
+    }
+    // Opening the descriptor through /dev/fd fails cleanly if it isn't
+    // open, and leaves the inherited descriptor itself alone
+    let file = std::fs::File::open(format!("/dev/fd/{}", fd))?;
+    Credentials::from_reader(file, profile)
+}
+
+#[cfg(not(unix))]
+fn read_fd(_: u32, _: Option<&str>) -> Result<Credentials, Error> {
+    fail("--credentials-fd is only supported on Unix")
+}
+
+fn cell(value: &Value) -> String {
+    match *value {
+        Value::String(ref s) => s.clone(),
+        Value::Null => String::new(),
+        ref other => other.to_string()
+    }
+}
+
+fn print_table(headers: &[String], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: &[String]| {
+        let padded: Vec<String> = cells.iter().zip(&widths).map(|(c, w)| format!("{:<1$}", c, w)).collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(headers);
+    for row in rows {
+        line(row);
+    }
+}
+
+// Prints an object as name/value pairs, and an array of objects with a
+// column per field. Arrays of arrays, such as candles, need `columns`.
+fn print_value(value: &Value, columns: &[&str]) {
+    match *value {
+        Value::Object(ref fields) => {
+            let rows: Vec<Vec<String>> = fields.iter().map(|(k, v)| vec![k.clone(), cell(v)]).collect();
+            print_table(&["field".to_owned(), "value".to_owned()], &rows);
+        }
+        Value::Array(ref items) => {
+            let headers: Vec<String> = match items.first() {
+                Some(Value::Object(fields)) => fields.keys().cloned().collect(),
+                _ => columns.iter().map(|c| c.to_string()).collect()
+            };
+            let rows: Vec<Vec<String>> = items.iter()
+                                              .map(|item| match *item {
+                                                  Value::Object(ref fields) => {
+                                                      headers.iter().map(|h| fields.get(h).map(cell).unwrap_or_default()).collect()
+                                                  }
+                                                  Value::Array(ref values) => values.iter().map(cell).collect(),
+                                                  ref other => vec![cell(other)]
+                                              })
+                                              .collect();
+            print_table(&headers, &rows);
+        }
+        ref other => println!("{}", cell(other))
+    }
+}
+
+fn output<T: Serialize>(json: bool, result: Result<T, Error>, columns: &[&str]) {
+    let value = match result.and_then(|v| Ok(serde_json::to_value(v)?)) {
+        Ok(value) => value,
+        Err(e) => {
+            eprintln!("gdax: {}", e);
+            process::exit(1);
+        }
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&value).unwrap());
+    } else {
+        print_value(&value, columns);
+    }
+}
+
+// Level 3 books list individual orders rather than counting them
+fn print_book(json: bool, book: Result<Value, Error>, last_column: &str) {
+    let book = match book {
+        Ok(book) if !json => book,
+        other => return output(json, other, &[])
+    };
+    let mut rows = Vec::new();
+    // Asks from the highest down, so the spread is in the middle
+    let asks = book["asks"].as_array().cloned().unwrap_or_default();
+    for (side, entries) in &[("ask", asks.into_iter().rev().collect::<Vec<_>>()),
+                             ("bid", book["bids"].as_array().cloned().unwrap_or_default())] {
+        for entry in entries {
+            let mut row = vec![side.to_string()];
+            row.extend(entry.as_array().into_iter().flatten().map(cell));
+            rows.push(row);
+        }
+    }
+    println!("sequence {}", cell(&book["sequence"]));
+    print_table(&["side".to_owned(), "price".to_owned(), "size".to_owned(), last_column.to_owned()], &rows);
+}
+
+fn main() {
+    let args = Args::parse();
+    if args.flag("--help") || args.flag("-h") {
+        println!("{}", USAGE);
+        return;
+    }
+    let json = args.flag("--json");
+    let command = args.arg(0, "command").to_owned();
+
+    match command.as_str() {
+        "products" => output(json, public_client().get_products(), &[]),
+        "book" => {
+            let product = args.arg(1, "product");
+            let mut client = public_client();
+            let level = args.value("--level").unwrap_or("2");
+            let book = match level {
+                "1" => client.get_best_order(product).and_then(|b| Ok(serde_json::to_value(b)?)),
+                "2" => client.get_top50_orders(product).and_then(|b| Ok(serde_json::to_value(b)?)),
+                "3" => client.get_full_book(product).and_then(|b| Ok(serde_json::to_value(b)?)),
+                level => fail(&format!("invalid level {:?}", level))
+            };
+            print_book(json, book, if level == "3" { "order_id" } else { "orders" });
+        }
+        "ticker" => output(json, public_client().get_product_ticker(args.arg(1, "product")), &[]),
+        "trades" => output(json, public_client().get_trades(args.arg(1, "product")), &[]),
+        "candles" => {
+            let granularity = match args.value("--granularity") {
+                Some(s) => match Granularity::from_seconds(parse(s, "granularity")) {
+                    Some(granularity) => granularity,
+                    None => fail(&format!("unsupported granularity {}", s))
+                },
+                None => Granularity::OneMinute
+            };
+            let range = public_client().get_historic_rates_range(args.arg(1, "product"),
+                                                                 parse_time(args.arg(2, "start")),
+                                                                 parse_time(args.arg(3, "end")),
+                                                                 granularity);
+            output(json, range.map(|r| r.candles), &["time", "low", "high", "open", "close", "volume"]);
+        }
+        "accounts" => output(json, private_client(&args).get_accounts(), &[]),
+        "ledger" => {
+            let id = parse(args.arg(1, "account id"), "account id");
+            output(json, private_client(&args).get_account_history(id), &[]);
+        }
+        "holds" => {
+            let id = parse(args.arg(1, "account id"), "account id");
+            output(json, private_client(&args).get_account_holds(id), &[]);
+        }
+        "orders" => match args.positional.get(1) {
+            Some(id) => output(json, private_client(&args).get_order(parse::<Uuid>(id, "order id")), &[]),
+            None => output(json, private_client(&args).get_orders(), &[])
+        },
+        "place" => {
+            let side = match args.arg(1, "side") {
+                "buy" => Side::Buy,
+                "sell" => Side::Sell,
+                other => fail(&format!("invalid side {:?}", other))
+            };
+            let product = args.arg(2, "product");
+            let amount: f64 = parse(args.arg(3, "size"), "size");
+            let size_or_funds = if args.flag("--funds") { SizeOrFunds::Funds(amount) } else { SizeOrFunds::Size(amount) };
+
+            let order = match (args.value("--price"), args.value("--stop")) {
+                (Some(_), Some(_)) => fail("--price and --stop can't be combined"),
+                (Some(_), None) if args.flag("--funds") => fail("limit orders are sized, not funded"),
+                (Some(price), None) => NewOrder::limit(side, product, amount, parse(price, "price")),
+                (None, Some(stop)) => NewOrder::stop(side, product, size_or_funds, parse(stop, "stop price")),
+                (None, None) => NewOrder::market(side, product, size_or_funds)
+            };
+            let mut client = private_client(&args);
+            if !args.flag("--yes") {
+                confirm(&order);
+            }
+            output(json, client.post_order(&order).map(|id| vec![id]), &["id"]);
+        }
+        "cancel" => {
+            if args.flag("--all") {
+                output(json, private_client(&args).cancel_all_orders(args.value("--product")), &["id"]);
+            } else {
+                let id = parse(args.arg(1, "order id"), "order id");
+                output(json, private_client(&args).cancel_order(id).map(|id| vec![id]), &["id"]);
+            }
+        }
+        other => fail(&format!("unknown command {:?}", other))
+    }
+}
@@ -0,0 +1,718 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use super::Error;
+use super::Side;
+use super::feed::{Change, Done, Level2Snapshot, Level2Update, Match, Message, Open};
+use super::PublicClient;
+use super::public::{BookEntry, FullBookEntry, OrderBook};
+
+/// A price usable as an ordered map key. Prices come from the exchange as
+/// decimal strings, so the same string always parses to the same value.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Price(pub f64);
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Price) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Price) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Price) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+pub(crate) fn parse_decimal(s: &str) -> Result<f64, Error> {
+    s.parse().map_err(|_| Error::InvalidNumber(s.to_owned()))
+}
+
+/// Aggregated depth at a single price.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BookLevel {
+    pub price: f64,
+    pub size: f64,
+    pub num_orders: usize
+}
+
+/// An order resting on a `FullBook`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BookOrder {
+    pub order_id: Uuid,
+    pub side: Side,
+    pub price: String,
+    pub size: f64
+}
+
+struct Level {
+    size: f64,
+    // Order IDs in time priority
+    orders: Vec<Uuid>
+}
+
+/// The outcome of applying a feed message to a `FullBook`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BookEvent {
+    /// The message was applied and the book is current.
+    Applied,
+    /// The message was for another product, had no sequence number or was
+    /// already reflected in the book.
+    Ignored,
+    /// The book is waiting for a snapshot, so the message was buffered.
+    Buffered,
+    /// Messages between `expected` and `received` were missed. The book is
+    /// out of sync until a new snapshot is loaded; the message that
+    /// revealed the gap, and every message after it, is buffered for replay.
+    Gap {
+        expected: u64,
+        received: u64
+    }
+}
+
+/// A level 3 order book for a single product, kept current from the `full`
+/// channel.
+///
+/// Feed messages applied before the first snapshot is loaded are buffered
+/// and replayed on top of it, so the usual way to build a book is to
+/// subscribe to the feed first, start applying messages, and then load
+/// `PublicClient::get_full_book`. `SyncedBook` does this automatically, and
+/// reloads the snapshot whenever a gap in the sequence is detected.
+pub struct FullBook {
+    product_id: String,
+    sequence: Option<u64>,
+    orders: HashMap<Uuid, BookOrder>,
+    bids: BTreeMap<Price, Level>,
+    asks: BTreeMap<Price, Level>,
+    buffer: Vec<Message>
+}
+
+impl FullBook {
+    pub fn new(product_id: &str) -> FullBook {
+        FullBook {
+            product_id: product_id.to_owned(),
+            sequence: None,
+            orders: HashMap::new(),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            buffer: Vec::new()
+        }
+    }
+
+    pub fn product_id(&self) -> &str {
+        &self.product_id
+    }
+
+    /// The sequence number of the last message applied, or `None` while the
+    /// book is out of sync, i.e. before the first snapshot is loaded and
+    /// after a gap.
+    pub fn sequence(&self) -> Option<u64> {
+        self.sequence
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.sequence.is_some()
+    }
+
+    /// Replaces the book's contents with `snapshot`, then replays any
+    /// buffered messages newer than it. Returns `BookEvent::Gap` if the
+    /// buffered messages don't follow on from the snapshot, in which case
+    /// a newer snapshot is needed. If the snapshot can't be read the book
+    /// is left out of sync, still buffering.
+    pub fn load_snapshot(&mut self, snapshot: &OrderBook<FullBookEntry>) -> Result<BookEvent, Error> {
+        self.sequence = None;
+        self.orders.clear();
+        self.bids.clear();
+        self.asks.clear();
+
+        for &(side, entries) in &[(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)] {
+            for entry in entries {
+                let size = parse_decimal(&entry.size)?;
+                self.insert(entry.order_id, side, &entry.price, size)?;
+            }
+        }
+        self.sequence = Some(snapshot.sequence as u64);
+
+        let mut buffer = ::std::mem::take(&mut self.buffer);
+        buffer.sort_by_key(|m| m.sequence());
+        for (i, message) in buffer.iter().enumerate() {
+            match self.apply(message) {
+                Ok(gap @ BookEvent::Gap { .. }) => {
+                    // Everything after the gap was buffered behind it by apply
+                    self.buffer.extend(buffer.drain(i + 1..));
+                    return Ok(gap);
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    // Keep what's left for the next snapshot
+                    self.buffer.extend(buffer.drain(i + 1..));
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(BookEvent::Applied)
+    }
+
+    /// Applies a feed message.
+    ///
+    /// Order messages (`open`, `done`, `match` and `change`) must arrive
+    /// in sequence. `received` messages only advance the sequence, and
+    /// `heartbeat` and `ticker` messages, which repeat the sequence of the
+    /// last message sent, are only used to detect gaps.
+    pub fn apply(&mut self, message: &Message) -> Result<BookEvent, Error> {
+        if message.product_id() != Some(&*self.product_id) {
+            return Ok(BookEvent::Ignored);
+        }
+        let sequence = match message.sequence() {
+            Some(sequence) => sequence,
+            None => return Ok(BookEvent::Ignored)
+        };
+        let current = match self.sequence {
+            Some(current) => current,
+            None => {
+                self.buffer.push(message.clone());
+                return Ok(BookEvent::Buffered);
+            }
+        };
+
+        let informational = matches!(*message,
+                                     Message::Heartbeat(_) | Message::Ticker(_) | Message::LastMatch(_));
+        let expected = if informational { current } else { current + 1 };
+
+        if sequence < expected || (informational && sequence == expected) {
+            return Ok(BookEvent::Ignored);
+        }
+        if sequence > expected {
+            self.sequence = None;
+            self.buffer.push(message.clone());
+            return Ok(BookEvent::Gap { expected: current + 1, received: sequence });
+        }
+
+        match *message {
+            Message::Open(ref m) => self.open(m)?,
+            Message::Done(ref m) => self.done(m),
+            Message::Match(ref m) => self.fill(m)?,
+            Message::Change(ref m) => self.change(m)?,
+            _ => {}
+        }
+        self.sequence = Some(sequence);
+
+        Ok(BookEvent::Applied)
+    }
+
+    fn open(&mut self, m: &Open) -> Result<(), Error> {
+        let size = parse_decimal(&m.remaining_size)?;
+        self.insert(m.order_id, m.side, &m.price, size)
+    }
+
+    fn done(&mut self, m: &Done) {
+        self.remove(m.order_id);
+    }
+
+    fn fill(&mut self, m: &Match) -> Result<(), Error> {
+        let size = parse_decimal(&m.size)?;
+        let remaining = match self.orders.get(&m.maker_order_id) {
+            Some(order) => order.size - size,
+            None => return Ok(())
+        };
+        self.resize(m.maker_order_id, remaining)
+    }
+
+    fn change(&mut self, m: &Change) -> Result<(), Error> {
+        if let Some(ref new_size) = m.new_size {
+            if self.orders.contains_key(&m.order_id) {
+                let new_size = parse_decimal(new_size)?;
+                self.resize(m.order_id, new_size)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn levels_mut(&mut self, side: Side) -> &mut BTreeMap<Price, Level> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks
+        }
+    }
+
+    fn insert(&mut self, order_id: Uuid, side: Side, price: &str, size: f64) -> Result<(), Error> {
+        let key = Price(parse_decimal(price)?);
+        self.remove(order_id);
+        {
+            let level = self.levels_mut(side).entry(key).or_insert_with(|| Level {
+                size: 0.0,
+                orders: Vec::new()
+            });
+            level.size += size;
+            level.orders.push(order_id);
+        }
+
+        self.orders.insert(order_id, BookOrder {
+            order_id,
+            side,
+            price: price.to_owned(),
+            size
+        });
+
+        Ok(())
+    }
+
+    fn remove(&mut self, order_id: Uuid) -> Option<BookOrder> {
+        let order = self.orders.remove(&order_id)?;
+        let key = Price(parse_decimal(&order.price).ok()?);
+        let levels = self.levels_mut(order.side);
+
+        let empty = match levels.get_mut(&key) {
+            Some(level) => {
+                level.size -= order.size;
+                level.orders.retain(|id| *id != order_id);
+                level.orders.is_empty()
+            }
+            None => false
+        };
+        if empty {
+            levels.remove(&key);
+        }
+
+        Some(order)
+    }
+
+    fn resize(&mut self, order_id: Uuid, size: f64) -> Result<(), Error> {
+        let (side, key, delta) = match self.orders.get_mut(&order_id) {
+            Some(order) => {
+                let delta = size - order.size;
+                order.size = size;
+                (order.side, Price(parse_decimal(&order.price)?), delta)
+            }
+            None => return Ok(())
+        };
+        if let Some(level) = self.levels_mut(side).get_mut(&key) {
+            level.size += delta;
+        }
+        Ok(())
+    }
+
+    pub fn best_bid(&self) -> Option<BookLevel> {
+        self.bids.iter().next_back().map(|(key, level)| book_level(key, level))
+    }
+
+    pub fn best_ask(&self) -> Option<BookLevel> {
+        self.asks.iter().next().map(|(key, level)| book_level(key, level))
+    }
+
+    /// Aggregated depth at exactly `price` on `side`.
+    pub fn depth_at(&self, side: Side, price: f64) -> Option<BookLevel> {
+        let levels = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks
+        };
+        levels.get(&Price(price)).map(|level| book_level(&Price(price), level))
+    }
+
+    /// Bid levels, best first.
+    pub fn bids(&self) -> Vec<BookLevel> {
+        self.bids.iter().rev().map(|(key, level)| book_level(key, level)).collect()
+    }
+
+    /// Ask levels, best first.
+    pub fn asks(&self) -> Vec<BookLevel> {
+        self.asks.iter().map(|(key, level)| book_level(key, level)).collect()
+    }
+
+    pub fn order(&self, order_id: &Uuid) -> Option<&BookOrder> {
+        self.orders.get(order_id)
+    }
+
+    /// The number of orders resting on the book.
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    /// The book in the shape returned by `PublicClient::get_full_book`,
+    /// with orders at each price in time priority.
+    pub fn to_order_book(&self) -> OrderBook<FullBookEntry> {
+        let entries = |levels: Vec<&Level>| {
+            levels.into_iter()
+                  .flat_map(|level| level.orders.iter())
+                  .map(|id| {
+                      let order = &self.orders[id];
+                      FullBookEntry {
+                          price: order.price.clone(),
+                          size: order.size.to_string(),
+                          order_id: order.order_id
+                      }
+                  })
+                  .collect()
+        };
+
+        OrderBook {
+            sequence: self.sequence.unwrap_or(0) as usize,
+            bids: entries(self.bids.values().rev().collect()),
+            asks: entries(self.asks.values().collect())
+        }
+    }
+}
+
+fn book_level(key: &Price, level: &Level) -> BookLevel {
+    BookLevel {
+        price: key.0,
+        size: level.size,
+        num_orders: level.orders.len()
+    }
+}
+
+/// Something that can provide level 3 snapshots for a `SyncedBook`.
+pub trait SnapshotSource {
+    fn full_book(&mut self, product_id: &str) -> Result<OrderBook<FullBookEntry>, Error>;
+}
+
+impl SnapshotSource for PublicClient {
+    fn full_book(&mut self, product_id: &str) -> Result<OrderBook<FullBookEntry>, Error> {
+        self.get_full_book(product_id)
+    }
+}
+
+impl<F> SnapshotSource for F
+    where F: FnMut(&str) -> Result<OrderBook<FullBookEntry>, Error>
+{
+    fn full_book(&mut self, product_id: &str) -> Result<OrderBook<FullBookEntry>, Error> {
+        self(product_id)
+    }
+}
+
+/// Reported by `SyncedBook` each time it (re)loads a snapshot.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ResyncEvent {
+    pub product_id: String,
+    /// The first gap detected since the book was last synced, as
+    /// `(expected, received)`, or `None` for the initial load.
+    pub gap: Option<(u64, u64)>,
+    /// The sequence of the snapshot that was loaded.
+    pub snapshot_sequence: u64,
+    /// Whether the buffered messages followed on from the snapshot. If not,
+    /// another is fetched on the first sequenced message after the retry
+    /// delay.
+    pub synced: bool
+}
+
+// The longest a `SyncedBook` waits between snapshots
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// A `FullBook` that fetches its snapshot from a `SnapshotSource` as soon as
+/// the first sequenced message arrives, and fetches a fresh one whenever a
+/// gap or out-of-order message is detected.
+///
+/// One snapshot is fetched per gap. If it's older than the messages
+/// buffered since, they keep being buffered until the retry delay has
+/// passed, which doubles with every snapshot that's still too old.
+pub struct SyncedBook<S> {
+    book: FullBook,
+    source: S,
+    gap: Option<(u64, u64)>,
+    retry_delay: Duration,
+    // While out of sync after fetching a snapshot: when the next one may
+    // be fetched, and the delay to wait after that
+    retry: Option<(Instant, Duration)>
+}
+
+impl<S: SnapshotSource> SyncedBook<S> {
+    pub fn new(product_id: &str, source: S) -> SyncedBook<S> {
+        SyncedBook {
+            book: FullBook::new(product_id),
+            source,
+            gap: None,
+            retry_delay: Duration::from_secs(1),
+            retry: None
+        }
+    }
+
+    /// Sets how long to wait after a snapshot that was too old before
+    /// fetching another. Defaults to a second.
+    pub fn retry_delay(mut self, delay: Duration) -> SyncedBook<S> {
+        self.retry_delay = delay;
+        self
+    }
+
+    pub fn book(&self) -> &FullBook {
+        &self.book
+    }
+
+    pub fn source_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// Applies a feed message, resynchronizing from a new snapshot if
+    /// needed. Returns the resync that took place, if any.
+    pub fn process(&mut self, message: &Message) -> Result<Option<ResyncEvent>, Error> {
+        match self.book.apply(message)? {
+            BookEvent::Applied | BookEvent::Ignored => return Ok(None),
+            BookEvent::Gap { expected, received } => {
+                self.gap = Some((expected, received));
+                self.retry = None;
+            }
+            BookEvent::Buffered => match self.retry {
+                Some((at, _)) if Instant::now() < at => return Ok(None),
+                _ => {}
+            }
+        }
+
+        // Set before fetching, so that a failed fetch is retried no sooner
+        let delay = self.retry.map_or(self.retry_delay, |(_, delay)| delay);
+        self.retry = Some((Instant::now() + delay, (delay * 2).min(MAX_RETRY_DELAY)));
+
+        let snapshot = self.source.full_book(&self.book.product_id)?;
+        let synced = match self.book.load_snapshot(&snapshot)? {
+            BookEvent::Gap { expected, received } => {
+                self.gap.get_or_insert((expected, received));
+                false
+            }
+            _ => true
+        };
+
+        let event = ResyncEvent {
+            product_id: self.book.product_id.clone(),
+            gap: self.gap,
+            snapshot_sequence: snapshot.sequence as u64,
+            synced
+        };
+        if synced {
+            self.gap = None;
+            self.retry = None;
+        }
+        Ok(Some(event))
+    }
+}
+
+struct L2Level {
+    price: String,
+    size: String,
+    size_value: f64,
+    num_orders: u64
+}
+
+impl L2Level {
+    fn new(price: &str, size: &str, size_value: f64, num_orders: u64) -> L2Level {
+        L2Level {
+            price: price.to_owned(),
+            size: size.to_owned(),
+            size_value,
+            num_orders
+        }
+    }
+}
+
+/// An aggregated (level 2) order book for a single product, kept current
+/// from the `level2` channel.
+///
+/// The `level2` channel doesn't report order counts, so levels changed by
+/// an update report zero orders. Likewise it carries no sequence numbers;
+/// `to_order_book` reports the sequence of the REST snapshot the book was
+/// loaded from, if any.
+pub struct L2Book {
+    product_id: String,
+    sequence: usize,
+    bids: BTreeMap<Price, L2Level>,
+    asks: BTreeMap<Price, L2Level>
+}
+
+impl L2Book {
+    pub fn new(product_id: &str) -> L2Book {
+        L2Book {
+            product_id: product_id.to_owned(),
+            sequence: 0,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new()
+        }
+    }
+
+    /// Builds a book from the `snapshot` message sent when subscribing to
+    /// the `level2` channel.
+    pub fn from_snapshot(snapshot: &Level2Snapshot) -> Result<L2Book, Error> {
+        let mut book = L2Book::new(&snapshot.product_id);
+        book.load_snapshot(snapshot)?;
+        Ok(book)
+    }
+
+    /// Builds a book from a REST snapshot such as
+    /// `PublicClient::get_top50_orders`.
+    pub fn from_order_book(product_id: &str, snapshot: &OrderBook<BookEntry>) -> Result<L2Book, Error> {
+        let mut book = L2Book::new(product_id);
+        for &(side, entries) in &[(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)] {
+            for entry in entries {
+                book.set(side, &entry.price, &entry.size, entry.num_orders)?;
+            }
+        }
+        book.sequence = snapshot.sequence;
+        Ok(book)
+    }
+
+    pub fn product_id(&self) -> &str {
+        &self.product_id
+    }
+
+    pub fn load_snapshot(&mut self, snapshot: &Level2Snapshot) -> Result<(), Error> {
+        self.bids.clear();
+        self.asks.clear();
+        self.sequence = 0;
+
+        for &(side, levels) in &[(Side::Buy, &snapshot.bids), (Side::Sell, &snapshot.asks)] {
+            for level in levels {
+                self.set(side, &level.price, &level.size, 0)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn apply_update(&mut self, update: &Level2Update) -> Result<(), Error> {
+        for change in &update.changes {
+            self.set(change.side, &change.price, &change.size, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Applies a `snapshot` or `l2update` message. Other messages, and
+    /// messages for other products, are ignored.
+    pub fn apply(&mut self, message: &Message) -> Result<(), Error> {
+        match *message {
+            Message::Snapshot(ref m) if m.product_id == self.product_id => self.load_snapshot(m),
+            Message::L2Update(ref m) if m.product_id == self.product_id => self.apply_update(m),
+            _ => Ok(())
+        }
+    }
+
+    fn set(&mut self, side: Side, price: &str, size: &str, num_orders: u64) -> Result<(), Error> {
+        let key = Price(parse_decimal(price)?);
+        let size_value = parse_decimal(size)?;
+        let levels = match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks
+        };
+
+        if size_value == 0.0 {
+            levels.remove(&key);
+        } else {
+            levels.insert(key, L2Level::new(price, size, size_value, num_orders));
+        }
+        Ok(())
+    }
+
+    fn levels(&self, side: Side) -> Box<dyn Iterator<Item = (&Price, &L2Level)> + '_> {
+        match side {
+            Side::Buy => Box::new(self.bids.iter().rev()),
+            Side::Sell => Box::new(self.asks.iter())
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<BookLevel> {
+        self.levels(Side::Buy).next().map(|(key, level)| l2_book_level(key, level))
+    }
+
+    pub fn best_ask(&self) -> Option<BookLevel> {
+        self.levels(Side::Sell).next().map(|(key, level)| l2_book_level(key, level))
+    }
+
+    /// Bid levels, best first.
+    pub fn bids(&self) -> Vec<BookLevel> {
+        self.levels(Side::Buy).map(|(key, level)| l2_book_level(key, level)).collect()
+    }
+
+    /// Ask levels, best first.
+    pub fn asks(&self) -> Vec<BookLevel> {
+        self.levels(Side::Sell).map(|(key, level)| l2_book_level(key, level)).collect()
+    }
+
+    pub fn depth_at(&self, side: Side, price: f64) -> Option<BookLevel> {
+        let levels = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks
+        };
+        levels.get(&Price(price)).map(|level| l2_book_level(&Price(price), level))
+    }
+
+    /// Total size on `side` at prices at least as good as `price`.
+    pub fn depth_to(&self, side: Side, price: f64) -> f64 {
+        self.levels(side)
+            .take_while(|&(key, _)| match side {
+                Side::Buy => key.0 >= price,
+                Side::Sell => key.0 <= price
+            })
+            .map(|(_, level)| level.size_value)
+            .sum()
+    }
+
+    /// Total size in the best `n` levels on `side`.
+    pub fn depth_levels(&self, side: Side, n: usize) -> f64 {
+        self.levels(side).take(n).map(|(_, level)| level.size_value).sum()
+    }
+
+    /// Levels on `side`, best first, with `size` replaced by the cumulative
+    /// size up to and including each level.
+    pub fn cumulative_depth(&self, side: Side) -> Vec<BookLevel> {
+        let mut total = 0.0;
+        self.levels(side)
+            .map(|(key, level)| {
+                total += level.size_value;
+                BookLevel { size: total, ..l2_book_level(key, level) }
+            })
+            .collect()
+    }
+
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+
+    pub fn mid_price(&self) -> Option<f64> {
+        Some((self.best_ask()?.price + self.best_bid()?.price) / 2.0)
+    }
+
+    /// The mid price weighted by the size at the top of each side, which
+    /// leans towards the side more likely to trade through.
+    pub fn microprice(&self) -> Option<f64> {
+        let bid = self.best_bid()?;
+        let ask = self.best_ask()?;
+        Some((bid.price * ask.size + ask.price * bid.size) / (bid.size + ask.size))
+    }
+
+    /// The book in the shape returned by `PublicClient::get_top50_orders`.
+    pub fn to_order_book(&self) -> OrderBook<BookEntry> {
+        let entries = |side| {
+            self.levels(side)
+                .map(|(_, level)| BookEntry {
+                    price: level.price.clone(),
+                    size: level.size.clone(),
+                    num_orders: level.num_orders
+                })
+                .collect()
+        };
+
+        OrderBook {
+            sequence: self.sequence,
+            bids: entries(Side::Buy),
+            asks: entries(Side::Sell)
+        }
+    }
+}
+
+fn l2_book_level(key: &Price, level: &L2Level) -> BookLevel {
+    BookLevel {
+        price: key.0,
+        size: level.size_value,
+        num_orders: level.num_orders as usize
+    }
+}
@@ -0,0 +1,165 @@
+//! Candles built from trades as they happen.
+//!
+//! `get_historic_rates` only covers completed intervals and is rate
+//! limited. A `CandleBuilder` produces the same `Candle` bars from a stream
+//! of trades or match messages, at any granularity, updating the open bar
+//! with every trade.
+
+use chrono::{DateTime, Utc};
+
+use super::Error;
+use super::book::parse_decimal;
+use super::feed::Message;
+use super::public::{Candle, Trade};
+
+/// A change to the bars of a `CandleBuilder`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CandleEvent {
+    /// The open bar after a trade was added to it.
+    Updated(Candle),
+    /// A bar that won't receive any more trades.
+    Closed(Candle)
+}
+
+/// Aggregates trades for a single product into candles of `granularity`
+/// seconds.
+///
+/// Bars are aligned to the Unix epoch like the exchange's own, so a bar's
+/// `time` is the start of its interval. As with `get_historic_rates`,
+/// intervals without any trades produce no bar. Trades must arrive in
+/// order: trades older than the open bar, and trades whose id was already
+/// seen, are ignored.
+#[derive(Clone, Debug)]
+pub struct CandleBuilder {
+    product_id: String,
+    granularity: u64,
+    current: Option<Candle>,
+    last_closed: Option<u64>,
+    last_trade_id: Option<u64>
+}
+
+impl CandleBuilder {
+    /// # Panics
+    ///
+    /// If `granularity` is zero.
+    pub fn new(product_id: &str, granularity: u64) -> CandleBuilder {
+        assert!(granularity > 0, "candle granularity must be at least one second");
+        CandleBuilder {
+            product_id: product_id.to_owned(),
+            granularity,
+            current: None,
+            last_closed: None,
+            last_trade_id: None
+        }
+    }
+
+    pub fn product_id(&self) -> &str {
+        &self.product_id
+    }
+
+    pub fn granularity(&self) -> u64 {
+        self.granularity
+    }
+
+    /// The bar currently being built, if any trades have been seen.
+    pub fn current(&self) -> Option<&Candle> {
+        self.current.as_ref()
+    }
+
+    /// The start of the interval containing `time`.
+    pub fn bucket(&self, time: DateTime<Utc>) -> u64 {
+        let secs = time.timestamp().max(0) as u64;
+        secs - secs % self.granularity
+    }
+
+    /// Adds a trade of `size` at `price`.
+    pub fn push(&mut self, time: DateTime<Utc>, price: f64, size: f64) -> Vec<CandleEvent> {
+        let bucket = self.bucket(time);
+        let mut events = Vec::new();
+
+        if self.last_closed.is_some_and(|closed| bucket <= closed) {
+            return events;
+        }
+        match self.current {
+            Some(ref candle) if bucket < candle.time => return events,
+            Some(ref candle) if bucket > candle.time => {
+                self.last_closed = Some(candle.time);
+                events.push(CandleEvent::Closed(candle.clone()));
+            }
+            _ => {}
+        }
+
+        let candle = match self.current {
+            Some(ref mut candle) if candle.time == bucket => {
+                candle.low = candle.low.min(price);
+                candle.high = candle.high.max(price);
+                candle.close = price;
+                candle.volume += size;
+                candle.clone()
+            }
+            _ => {
+                let candle = Candle {
+                    time: bucket,
+                    low: price,
+                    high: price,
+                    open: price,
+                    close: price,
+                    volume: size
+                };
+                self.current = Some(candle.clone());
+                candle
+            }
+        };
+        events.push(CandleEvent::Updated(candle));
+        events
+    }
+
+    /// Adds a trade, such as one returned by `get_trades` or
+    /// `Match::to_trade`.
+    pub fn push_trade(&mut self, trade: &Trade) -> Result<Vec<CandleEvent>, Error> {
+        if self.last_trade_id.is_some_and(|last| trade.trade_id <= last) {
+            return Ok(Vec::new());
+        }
+        let price = parse_decimal(&trade.price)?;
+        let size = parse_decimal(&trade.size)?;
+
+        let events = self.push(trade.time, price, size);
+        if !events.is_empty() {
+            self.last_trade_id = Some(trade.trade_id);
+        }
+        Ok(events)
+    }
+
+    /// Adds the trade from a `match` or `last_match` message for this
+    /// builder's product. Other messages are ignored.
+    pub fn apply(&mut self, message: &Message) -> Result<Vec<CandleEvent>, Error> {
+        match *message {
+            Message::Match(ref m) | Message::LastMatch(ref m) if m.product_id == self.product_id => {
+                self.push_trade(&m.to_trade())
+            }
+            _ => Ok(Vec::new())
+        }
+    }
+
+    /// Closes the open bar if `now` is past the end of its interval, so a
+    /// bar isn't left open through a quiet period. Typically driven by
+    /// heartbeats or a timer.
+    pub fn close_until(&mut self, now: DateTime<Utc>) -> Option<Candle> {
+        let closed = self.current.as_ref().is_some_and(|c| self.bucket(now) > c.time);
+        if closed {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    /// Closes and returns the open bar regardless of time. Later trades in
+    /// its interval are ignored.
+    pub fn flush(&mut self) -> Option<Candle> {
+        let candle = self.current.take();
+        if let Some(ref candle) = candle {
+            self.last_closed = Some(candle.time);
+        }
+        candle
+    }
+}
@@ -0,0 +1,76 @@
+//! Tracking the offset between the local clock and the exchange's.
+//!
+//! Signed requests carry a timestamp that the exchange rejects if it's more
+//! than thirty seconds from its own clock. A `ClockSync` estimates how far
+//! the local clock is off from samples of the server time, smoothing out
+//! network jitter, so signatures can use the server's idea of now.
+
+use std::time::{Duration, Instant};
+
+use time::get_time;
+
+// Weight of each new sample in the smoothed offset
+const SMOOTHING: f64 = 0.25;
+
+/// The local time, in seconds since the epoch.
+pub fn local_now() -> f64 {
+    let now = get_time();
+    now.sec as f64 + f64::from(now.nsec) / 1e9
+}
+
+/// A smoothed estimate of the server clock's offset from the local clock.
+#[derive(Clone, Debug)]
+pub struct ClockSync {
+    offset: Option<f64>,
+    refresh_interval: Duration,
+    last_sync: Option<Instant>,
+    stale: bool
+}
+
+impl ClockSync {
+    /// Tracks the offset, asking to be refreshed every `refresh_interval`.
+    pub fn new(refresh_interval: Duration) -> ClockSync {
+        ClockSync {
+            offset: None,
+            refresh_interval,
+            last_sync: None,
+            stale: false
+        }
+    }
+
+    /// Seconds to add to the local clock to get the server's time, once at
+    /// least one sample has been taken.
+    pub fn offset(&self) -> Option<f64> {
+        self.offset
+    }
+
+    /// Adds a sample of the server time `server`, requested at local time
+    /// `sent` and received at `received`. The server is assumed to have
+    /// read its clock halfway through the round trip.
+    pub fn record(&mut self, sent: f64, server: f64, received: f64) {
+        let sample = server - (sent + received) / 2.0;
+        self.offset = Some(match self.offset {
+            // A rejected timestamp means the estimate was wrong, so start over
+            Some(offset) if !self.stale => offset + SMOOTHING * (sample - offset),
+            _ => sample
+        });
+        self.last_sync = Some(Instant::now());
+        self.stale = false;
+    }
+
+    /// Whether a new sample should be taken before the next signature.
+    pub fn is_due(&self) -> bool {
+        self.stale || self.last_sync.is_none_or(|last| last.elapsed() >= self.refresh_interval)
+    }
+
+    /// Forces a fresh sample before the next signature, e.g. after the
+    /// exchange rejected a timestamp.
+    pub fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    /// The estimated server time, in seconds since the epoch.
+    pub fn now(&self) -> f64 {
+        local_now() + self.offset.unwrap_or(0.0)
+    }
+}
@@ -0,0 +1,205 @@
+//! API credentials loaded at runtime.
+//!
+//! Credentials can come from the `CB_KEY`, `CB_SECRET` and `CB_PASSPHRASE`
+//! environment variables, or from a TOML or JSON file holding one set of
+//! credentials or several named profiles:
+//!
+//! ```toml
+//! [default]
+//! key = "..."
+//! secret = "..."
+//! passphrase = "..."
+//!
+//! [sandbox]
+//! key = "..."
+//! secret = "..."
+//! passphrase = "..."
+//! ```
+//!
+//! The secret is checked to be valid base64 as soon as it's loaded, so a
+//! bad secret is reported before any request is signed with it.
+
+use base64;
+use serde_json;
+use toml;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+#[cfg(unix)]
+use std::os::unix::io::OwnedFd;
+use std::path::{Path, PathBuf};
+
+use zeroize::{Zeroize, Zeroizing};
+
+use super::Error;
+use super::PrivateClient;
+
+/// The profile used when none is named.
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Deserialize)]
+struct RawCredentials {
+    key: String,
+    secret: String,
+    passphrase: String
+}
+
+impl Drop for RawCredentials {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+        self.passphrase.zeroize();
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CredentialsFile {
+    Single(RawCredentials),
+    Profiles(BTreeMap<String, RawCredentials>)
+}
+
+/// An API key with its secret and passphrase.
+///
+/// The secret and passphrase are wiped from memory when dropped, and left
+/// out of the `Debug` output. Credentials can't be compared, as a plain
+/// comparison would take longer the more of a secret matched.
+#[derive(Clone)]
+pub struct Credentials {
+    key: String,
+    secret: Zeroizing<String>,
+    decoded_secret: Zeroizing<Vec<u8>>,
+    passphrase: Zeroizing<String>
+}
+
+impl Credentials {
+    /// Fails with `Error::InvalidSecretKey` if `secret` isn't base64.
+    pub fn new(key: &str, secret: &str, passphrase: &str) -> Result<Credentials, Error> {
+        Ok(Credentials {
+            key: key.to_owned(),
+            secret: Zeroizing::new(secret.to_owned()),
+            decoded_secret: Zeroizing::new(base64::decode(secret)?),
+            passphrase: Zeroizing::new(passphrase.to_owned())
+        })
+    }
+
+    /// Reads `CB_KEY`, `CB_SECRET` and `CB_PASSPHRASE`.
+    pub fn from_env() -> Result<Credentials, Error> {
+        let var = |name: &str| {
+            env::var(name).map(Zeroizing::new)
+                          .map_err(|_| Error::MissingCredentials(format!("{} is not set", name)))
+        };
+        Credentials::new(&var("CB_KEY")?, &var("CB_SECRET")?, &var("CB_PASSPHRASE")?)
+    }
+
+    /// Reads `profile`, or the default profile, from the file at `path`.
+    /// Files ending in `.json` are read as JSON and others as TOML.
+    pub fn from_file<P: AsRef<Path>>(path: P, profile: Option<&str>) -> Result<Credentials, Error> {
+        let path = path.as_ref();
+        let mut contents = Zeroizing::new(String::new());
+        File::open(path)?.read_to_string(&mut contents)?;
+
+        let is_json = match path.extension() {
+            Some(ext) => ext.eq_ignore_ascii_case("json"),
+            None => contents.trim_start().starts_with('{')
+        };
+        Credentials::parse(&contents, is_json, profile)
+    }
+
+    /// Reads credentials in either format from `reader`, e.g. a pipe set up
+    /// by a secrets manager.
+    pub fn from_reader<R: Read>(mut reader: R, profile: Option<&str>) -> Result<Credentials, Error> {
+        let mut contents = Zeroizing::new(String::new());
+        reader.read_to_string(&mut contents)?;
+        let is_json = contents.trim_start().starts_with('{');
+        Credentials::parse(&contents, is_json, profile)
+    }
+
+    /// Reads credentials from an inherited file descriptor, which is
+    /// closed afterwards.
+    #[cfg(unix)]
+    pub fn from_fd(fd: OwnedFd, profile: Option<&str>) -> Result<Credentials, Error> {
+        Credentials::from_reader(File::from(fd), profile)
+    }
+
+    fn parse(contents: &str, is_json: bool, profile: Option<&str>) -> Result<Credentials, Error> {
+        let file: CredentialsFile = if is_json {
+            serde_json::from_str(contents)?
+        } else {
+            toml::from_str(contents)?
+        };
+
+        let name = profile.unwrap_or(DEFAULT_PROFILE);
+        let raw = match file {
+            CredentialsFile::Single(raw) if name == DEFAULT_PROFILE => raw,
+            CredentialsFile::Single(_) => {
+                return Err(Error::MissingCredentials(format!("no profile named {:?}", name)))
+            }
+            CredentialsFile::Profiles(mut profiles) => match profiles.remove(name) {
+                Some(raw) => raw,
+                None => return Err(Error::MissingCredentials(format!("no profile named {:?}", name)))
+            }
+        };
+        Credentials::new(&raw.key, &raw.secret, &raw.passphrase)
+    }
+
+    /// Where credential files are looked for by default: `$GDAX_CREDENTIALS`
+    /// if set, otherwise `~/.gdax/credentials.toml`, or
+    /// `~/.gdax/credentials.json` if only that exists.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os("GDAX_CREDENTIALS") {
+            return Some(PathBuf::from(path));
+        }
+        let dir = PathBuf::from(env::var_os("HOME")?).join(".gdax");
+        let json = dir.join("credentials.json");
+        if json.exists() && !dir.join("credentials.toml").exists() {
+            Some(json)
+        } else {
+            Some(dir.join("credentials.toml"))
+        }
+    }
+
+    /// Loads credentials from the environment if no profile is named and
+    /// `CB_KEY` is set, and otherwise from the file at `default_path`.
+    pub fn load(profile: Option<&str>) -> Result<Credentials, Error> {
+        if profile.is_none() && env::var_os("CB_KEY").is_some() {
+            return Credentials::from_env();
+        }
+        match Credentials::default_path() {
+            Some(ref path) if path.exists() => Credentials::from_file(path, profile),
+            _ => Err(Error::MissingCredentials("CB_KEY is not set and there is no credentials file".to_owned()))
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    pub fn passphrase(&self) -> &str {
+        &self.passphrase
+    }
+
+    pub(crate) fn decoded_secret(&self) -> &Zeroizing<Vec<u8>> {
+        &self.decoded_secret
+    }
+
+    pub fn client(&self) -> PrivateClient {
+        PrivateClient::from_credentials(self)
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Credentials")
+         .field("key", &self.key)
+         .field("secret", &"<redacted>")
+         .field("passphrase", &"<redacted>")
+         .finish()
+    }
+}
@@ -0,0 +1,1157 @@
+use chrono::{DateTime, Utc};
+
+use futures::{Async, Poll, Stream};
+use futures::task::{self, Task};
+
+use serde::{self, Serialize};
+use serde::de::Error as DeError;
+use serde::ser::Error as SerError;
+use serde_json::{self, from_value, Value};
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::net::TcpStream;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tungstenite::{self, WebSocket};
+use tungstenite::stream::MaybeTlsStream;
+
+use uuid::Uuid;
+use zeroize::Zeroize;
+
+use super::ApiError;
+use super::Error;
+use super::PrivateClient;
+use super::Side;
+use super::public::{Tick, Trade};
+use super::replay::Recorder;
+
+const FEED_URL: &str = "wss://ws-feed.gdax.com";
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Channel {
+    Heartbeat,
+    Ticker,
+    Level2,
+    Matches,
+    Full,
+    Status,
+    User
+}
+
+impl Channel {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Channel::Heartbeat => "heartbeat",
+            Channel::Ticker => "ticker",
+            Channel::Level2 => "level2",
+            Channel::Matches => "matches",
+            Channel::Full => "full",
+            Channel::Status => "status",
+            Channel::User => "user"
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Channel {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ChannelSubscription {
+    pub name: String,
+    #[serde(default)]
+    pub product_ids: Vec<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Subscriptions {
+    pub channels: Vec<ChannelSubscription>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Heartbeat {
+    pub sequence: u64,
+    pub last_trade_id: u64,
+    pub product_id: String,
+    pub time: DateTime<Utc>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Ticker {
+    pub sequence: u64,
+    pub product_id: String,
+    pub price: String,
+    pub open_24h: Option<String>,
+    pub volume_24h: Option<String>,
+    pub low_24h: Option<String>,
+    pub high_24h: Option<String>,
+    pub volume_30d: Option<String>,
+    pub best_bid: Option<String>,
+    pub best_ask: Option<String>,
+    pub side: Option<Side>,
+    pub time: Option<DateTime<Utc>>,
+    pub trade_id: Option<u64>,
+    pub last_size: Option<String>
+}
+
+impl Ticker {
+    /// Converts to the REST ticker type. Returns `None` for the initial
+    /// ticker message sent on subscription, which carries no trade.
+    pub fn to_tick(&self) -> Option<Tick> {
+        Some(Tick {
+            trade_id: self.trade_id?,
+            price: self.price.clone(),
+            size: self.last_size.clone()?,
+            bid: self.best_bid.clone()?,
+            ask: self.best_ask.clone()?,
+            volume: self.volume_24h.clone()?,
+            time: self.time?
+        })
+    }
+}
+
+/// A price level in a level 2 snapshot, encoded on the wire as
+/// `[price, size]`.
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct PriceLevel {
+    pub price: String,
+    pub size: String
+}
+
+impl Serialize for PriceLevel {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        (&self.price, &self.size).serialize(serializer)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Level2Snapshot {
+    pub product_id: String,
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>
+}
+
+/// A change to a level 2 price level, encoded on the wire as
+/// `[side, price, size]`. A size of zero removes the level.
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+pub struct Level2Change {
+    pub side: Side,
+    pub price: String,
+    pub size: String
+}
+
+impl Serialize for Level2Change {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        (self.side, &self.price, &self.size).serialize(serializer)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Level2Update {
+    pub product_id: String,
+    pub time: Option<DateTime<Utc>>,
+    pub changes: Vec<Level2Change>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Received {
+    pub time: DateTime<Utc>,
+    pub product_id: String,
+    pub sequence: u64,
+    pub order_id: Uuid,
+    pub side: Side,
+    pub order_type: OrderType,
+    pub size: Option<String>,
+    pub price: Option<String>,
+    pub funds: Option<String>,
+    pub client_oid: Option<String>,
+    pub user_id: Option<String>,
+    pub profile_id: Option<Uuid>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Open {
+    pub time: DateTime<Utc>,
+    pub product_id: String,
+    pub sequence: u64,
+    pub order_id: Uuid,
+    pub price: String,
+    pub remaining_size: String,
+    pub side: Side,
+    pub user_id: Option<String>,
+    pub profile_id: Option<Uuid>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Done {
+    pub time: DateTime<Utc>,
+    pub product_id: String,
+    pub sequence: u64,
+    pub order_id: Uuid,
+    pub reason: DoneReason,
+    pub side: Side,
+    pub price: Option<String>,
+    pub remaining_size: Option<String>,
+    pub user_id: Option<String>,
+    pub profile_id: Option<Uuid>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Match {
+    pub trade_id: u64,
+    pub sequence: u64,
+    pub maker_order_id: Uuid,
+    pub taker_order_id: Uuid,
+    pub time: DateTime<Utc>,
+    pub product_id: String,
+    pub size: String,
+    pub price: String,
+    pub side: Side,
+    pub user_id: Option<String>,
+    pub profile_id: Option<Uuid>,
+    pub maker_user_id: Option<String>,
+    pub maker_profile_id: Option<Uuid>,
+    pub taker_user_id: Option<String>,
+    pub taker_profile_id: Option<Uuid>
+}
+
+impl Match {
+    /// Converts to the REST trade type. Both use the maker's side.
+    pub fn to_trade(&self) -> Trade {
+        Trade {
+            time: self.time,
+            trade_id: self.trade_id,
+            price: self.price.clone(),
+            size: self.size.clone(),
+            side: self.side
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Change {
+    pub time: DateTime<Utc>,
+    pub sequence: u64,
+    pub order_id: Uuid,
+    pub product_id: String,
+    pub side: Side,
+    pub price: Option<String>,
+    pub new_size: Option<String>,
+    pub old_size: Option<String>,
+    pub new_funds: Option<String>,
+    pub old_funds: Option<String>,
+    pub user_id: Option<String>,
+    pub profile_id: Option<Uuid>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Activate {
+    pub product_id: String,
+    pub timestamp: String,
+    pub order_id: Uuid,
+    pub stop_type: String,
+    pub side: Side,
+    pub stop_price: String,
+    pub size: Option<String>,
+    pub funds: Option<String>,
+    pub user_id: Option<String>,
+    pub profile_id: Option<Uuid>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct StatusProduct {
+    pub id: String,
+    pub base_currency: String,
+    pub quote_currency: String,
+    pub status: String,
+    pub status_message: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct StatusCurrency {
+    pub id: String,
+    pub name: String,
+    pub min_size: String,
+    pub status: String,
+    pub status_message: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Status {
+    pub products: Vec<StatusProduct>,
+    pub currencies: Vec<StatusCurrency>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FeedError {
+    pub message: String,
+    pub reason: Option<String>
+}
+
+/// The type of order in a `received` message. Types this crate doesn't
+/// recognise yet are kept verbatim in `Unknown`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OrderType {
+    Limit,
+    Market,
+    Unknown(String)
+}
+
+impl OrderType {
+    pub fn as_str(&self) -> &str {
+        match *self {
+            OrderType::Limit => "limit",
+            OrderType::Market => "market",
+            OrderType::Unknown(ref t) => t
+        }
+    }
+}
+
+impl Serialize for OrderType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+// We manually implement Deserialize for OrderType here
+// because the default encoding/decoding scheme that derive
+// gives us isn't the straightforward mapping unfortunately
+impl<'de> serde::Deserialize<'de> for OrderType {
+    fn deserialize<D>(deserializer: D) -> Result<OrderType, D::Error>
+        where D: serde::Deserializer<'de> {
+
+        struct OrderTypeVisitor;
+        impl<'a> serde::de::Visitor<'a> for OrderTypeVisitor {
+            type Value = OrderType;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+                formatter.write_str("an order type string")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+                where E: serde::de::Error {
+                match &*v.to_lowercase() {
+                    "limit" => Ok(OrderType::Limit),
+                    "market" => Ok(OrderType::Market),
+                    _ => Ok(OrderType::Unknown(v.to_owned()))
+                }
+            }
+        }
+        deserializer.deserialize_identifier(OrderTypeVisitor)
+    }
+}
+
+/// Why an order left the book. Reasons this crate doesn't recognise yet are
+/// kept verbatim in `Unknown`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DoneReason {
+    Filled,
+    Canceled,
+    Unknown(String)
+}
+
+impl DoneReason {
+    pub fn as_str(&self) -> &str {
+        match *self {
+            DoneReason::Filled => "filled",
+            DoneReason::Canceled => "canceled",
+            DoneReason::Unknown(ref r) => r
+        }
+    }
+}
+
+impl Serialize for DoneReason {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+// We manually implement Deserialize for DoneReason here
+// because the default encoding/decoding scheme that derive
+// gives us isn't the straightforward mapping unfortunately
+impl<'de> serde::Deserialize<'de> for DoneReason {
+    fn deserialize<D>(deserializer: D) -> Result<DoneReason, D::Error>
+        where D: serde::Deserializer<'de> {
+
+        struct DoneReasonVisitor;
+        impl<'a> serde::de::Visitor<'a> for DoneReasonVisitor {
+            type Value = DoneReason;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+                formatter.write_str("a done reason string")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+                where E: serde::de::Error {
+                match &*v.to_lowercase() {
+                    "filled" => Ok(DoneReason::Filled),
+                    "canceled" => Ok(DoneReason::Canceled),
+                    _ => Ok(DoneReason::Unknown(v.to_owned()))
+                }
+            }
+        }
+        deserializer.deserialize_identifier(DoneReasonVisitor)
+    }
+}
+
+/// A message received from the feed, keyed by its `type` field.
+///
+/// Messages of a type this crate doesn't recognise yet are kept as
+/// `Unknown`; messages of a known type that fail to decode are an error.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Message {
+    Subscriptions(Subscriptions),
+    Heartbeat(Heartbeat),
+    Ticker(Ticker),
+    Snapshot(Level2Snapshot),
+    L2Update(Level2Update),
+    Received(Received),
+    Open(Open),
+    Done(Done),
+    Match(Match),
+    LastMatch(Match),
+    Change(Change),
+    Activate(Activate),
+    Status(Status),
+    Error(FeedError),
+    Unknown(Value)
+}
+
+impl<'de> serde::Deserialize<'de> for Message {
+    fn deserialize<D>(deserializer: D) -> Result<Message, D::Error>
+        where D: serde::Deserializer<'de> {
+
+        let value = Value::deserialize(deserializer)?;
+        let message_type = match value.get("type").and_then(Value::as_str) {
+            Some(t) => t.to_owned(),
+            None => return Err(D::Error::missing_field("type"))
+        };
+
+        let message = match &*message_type {
+            "subscriptions" => from_value(value).map(Message::Subscriptions),
+            "heartbeat" => from_value(value).map(Message::Heartbeat),
+            "ticker" => from_value(value).map(Message::Ticker),
+            "snapshot" => from_value(value).map(Message::Snapshot),
+            "l2update" => from_value(value).map(Message::L2Update),
+            "received" => from_value(value).map(Message::Received),
+            "open" => from_value(value).map(Message::Open),
+            "done" => from_value(value).map(Message::Done),
+            "match" => from_value(value).map(Message::Match),
+            "last_match" => from_value(value).map(Message::LastMatch),
+            "change" => from_value(value).map(Message::Change),
+            "activate" => from_value(value).map(Message::Activate),
+            "status" => from_value(value).map(Message::Status),
+            "error" => from_value(value).map(Message::Error),
+            _ => return Ok(Message::Unknown(value))
+        };
+
+        message.map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for Message {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        let encoded = match *self {
+            Message::Subscriptions(ref m) => serde_json::to_value(m),
+            Message::Heartbeat(ref m) => serde_json::to_value(m),
+            Message::Ticker(ref m) => serde_json::to_value(m),
+            Message::Snapshot(ref m) => serde_json::to_value(m),
+            Message::L2Update(ref m) => serde_json::to_value(m),
+            Message::Received(ref m) => serde_json::to_value(m),
+            Message::Open(ref m) => serde_json::to_value(m),
+            Message::Done(ref m) => serde_json::to_value(m),
+            Message::Match(ref m) | Message::LastMatch(ref m) => serde_json::to_value(m),
+            Message::Change(ref m) => serde_json::to_value(m),
+            Message::Activate(ref m) => serde_json::to_value(m),
+            Message::Status(ref m) => serde_json::to_value(m),
+            Message::Error(ref m) => serde_json::to_value(m),
+            Message::Unknown(ref v) => return v.serialize(serializer)
+        };
+
+        let mut encoded = encoded.map_err(S::Error::custom)?;
+        if let Value::Object(ref mut map) = encoded {
+            map.insert("type".to_owned(), Value::String(self.message_type().to_owned()));
+        }
+        encoded.serialize(serializer)
+    }
+}
+
+impl Message {
+    /// The sequence number of the message, for the message types that
+    /// carry one.
+    pub fn sequence(&self) -> Option<u64> {
+        match *self {
+            Message::Heartbeat(ref m) => Some(m.sequence),
+            Message::Ticker(ref m) => Some(m.sequence),
+            Message::Received(ref m) => Some(m.sequence),
+            Message::Open(ref m) => Some(m.sequence),
+            Message::Done(ref m) => Some(m.sequence),
+            Message::Match(ref m) | Message::LastMatch(ref m) => Some(m.sequence),
+            Message::Change(ref m) => Some(m.sequence),
+            _ => None
+        }
+    }
+
+    /// The product the message refers to, for the message types that
+    /// refer to a single product.
+    pub fn product_id(&self) -> Option<&str> {
+        match *self {
+            Message::Heartbeat(ref m) => Some(&m.product_id),
+            Message::Ticker(ref m) => Some(&m.product_id),
+            Message::Snapshot(ref m) => Some(&m.product_id),
+            Message::L2Update(ref m) => Some(&m.product_id),
+            Message::Received(ref m) => Some(&m.product_id),
+            Message::Open(ref m) => Some(&m.product_id),
+            Message::Done(ref m) => Some(&m.product_id),
+            Message::Match(ref m) | Message::LastMatch(ref m) => Some(&m.product_id),
+            Message::Change(ref m) => Some(&m.product_id),
+            Message::Activate(ref m) => Some(&m.product_id),
+            _ => None
+        }
+    }
+
+    /// The wire value of the message's `type` field.
+    pub fn message_type(&self) -> &str {
+        match *self {
+            Message::Subscriptions(_) => "subscriptions",
+            Message::Heartbeat(_) => "heartbeat",
+            Message::Ticker(_) => "ticker",
+            Message::Snapshot(_) => "snapshot",
+            Message::L2Update(_) => "l2update",
+            Message::Received(_) => "received",
+            Message::Open(_) => "open",
+            Message::Done(_) => "done",
+            Message::Match(_) => "match",
+            Message::LastMatch(_) => "last_match",
+            Message::Change(_) => "change",
+            Message::Activate(_) => "activate",
+            Message::Status(_) => "status",
+            Message::Error(_) => "error",
+            Message::Unknown(ref v) => v.get("type").and_then(Value::as_str).unwrap_or("")
+        }
+    }
+}
+
+impl Message {
+    /// Converts an order lifecycle message received on the authenticated
+    /// `user` channel into an `OrderEvent`. Returns `None` for every other
+    /// message, including order messages from the public `full` channel,
+    /// which don't carry a user ID.
+    pub fn into_order_event(self) -> Option<OrderEvent> {
+        match self {
+            Message::Received(m) => m.user_id.is_some().then_some(OrderEvent::Received(m)),
+            Message::Open(m) => m.user_id.is_some().then_some(OrderEvent::Open(m)),
+            Message::Done(m) => m.user_id.is_some().then_some(OrderEvent::Done(m)),
+            Message::Match(m) => {
+                let ours = m.user_id.is_some() || m.maker_user_id.is_some() || m.taker_user_id.is_some();
+                ours.then_some(OrderEvent::Match(m))
+            }
+            Message::Change(m) => m.user_id.is_some().then_some(OrderEvent::Change(m)),
+            Message::Activate(m) => m.user_id.is_some().then_some(OrderEvent::Activate(m)),
+            _ => None
+        }
+    }
+}
+
+/// An update to one of our own orders, from the `user` channel.
+#[derive(Clone, PartialEq, Debug)]
+pub enum OrderEvent {
+    Received(Received),
+    Open(Open),
+    Match(Match),
+    Done(Done),
+    Change(Change),
+    Activate(Activate)
+}
+
+impl OrderEvent {
+    pub fn order_id(&self) -> Uuid {
+        match *self {
+            OrderEvent::Received(ref m) => m.order_id,
+            OrderEvent::Open(ref m) => m.order_id,
+            OrderEvent::Match(ref m) => {
+                if m.taker_user_id.is_some() { m.taker_order_id } else { m.maker_order_id }
+            }
+            OrderEvent::Done(ref m) => m.order_id,
+            OrderEvent::Change(ref m) => m.order_id,
+            OrderEvent::Activate(ref m) => m.order_id
+        }
+    }
+}
+
+/// Credentials for an authenticated subscription. The signature covers a
+/// `GET /users/self/verify` request, as for the REST API.
+#[derive(Serialize)]
+pub(crate) struct Auth {
+    pub key: String,
+    pub passphrase: String,
+    pub signature: String,
+    pub timestamp: String
+}
+
+impl Drop for Auth {
+    fn drop(&mut self) {
+        self.passphrase.zeroize();
+    }
+}
+
+#[derive(Serialize)]
+struct Subscribe<'a> {
+    #[serde(rename = "type")]
+    t: &'static str,
+    product_ids: &'a [&'a str],
+    channels: &'a [Channel],
+    #[serde(flatten)]
+    auth: Option<Auth>
+}
+
+// Messages dominate the traffic, so boxing them to shrink the other
+// variants would only add an allocation per message
+#[allow(clippy::large_enum_variant)]
+enum Read {
+    Message(Message),
+    TimedOut,
+    Closed
+}
+
+/// A connection to the websocket feed.
+///
+/// Messages can be read one at a time with `next_message`, or consumed
+/// through the `Iterator` implementation; both block until a message
+/// arrives. `into_stream` hands the feed to a thread of its own and returns
+/// a `futures::Stream` that doesn't block.
+pub struct Feed {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    recorder: Option<Recorder>
+}
+
+impl Feed {
+    /// Connects to the exchange feed and subscribes to `channels` for each
+    /// of `product_ids`.
+    pub fn connect(product_ids: &[&str], channels: &[Channel]) -> Result<Feed, Error> {
+        Feed::connect_to(FEED_URL, product_ids, channels)
+    }
+
+    /// Like `connect`, but against a feed at `url`, e.g. a sandbox.
+    pub fn connect_to(url: &str, product_ids: &[&str], channels: &[Channel]) -> Result<Feed, Error> {
+        let (socket, _) = tungstenite::connect(url)?;
+        let mut feed = Feed { socket, recorder: None };
+        feed.subscribe(product_ids, channels)?;
+        Ok(feed)
+    }
+
+    /// Connects to the exchange feed and subscribes to `channels` for each
+    /// of `product_ids`, signing the subscription with `client`'s
+    /// credentials. This is required for the `user` channel.
+    pub fn connect_authenticated(client: &PrivateClient, product_ids: &[&str], channels: &[Channel])
+        -> Result<Feed, Error>
+    {
+        Feed::connect_authenticated_to(FEED_URL, client, product_ids, channels)
+    }
+
+    /// Like `connect_authenticated`, but against a feed at `url`.
+    pub fn connect_authenticated_to(url: &str,
+                                    client: &PrivateClient,
+                                    product_ids: &[&str],
+                                    channels: &[Channel])
+        -> Result<Feed, Error>
+    {
+        let (socket, _) = tungstenite::connect(url)?;
+        let mut feed = Feed { socket, recorder: None };
+        feed.subscribe_authenticated(client, product_ids, channels)?;
+        Ok(feed)
+    }
+
+    pub fn subscribe(&mut self, product_ids: &[&str], channels: &[Channel]) -> Result<(), Error> {
+        self.send_subscription("subscribe", product_ids, channels, None)
+    }
+
+    pub fn subscribe_authenticated(&mut self,
+                                   client: &PrivateClient,
+                                   product_ids: &[&str],
+                                   channels: &[Channel])
+        -> Result<(), Error>
+    {
+        let auth = client.feed_auth()?;
+        self.send_subscription("subscribe", product_ids, channels, Some(auth))
+    }
+
+    pub fn unsubscribe(&mut self, product_ids: &[&str], channels: &[Channel]) -> Result<(), Error> {
+        self.send_subscription("unsubscribe", product_ids, channels, None)
+    }
+
+    fn send_subscription(&mut self,
+                         t: &'static str,
+                         product_ids: &[&str],
+                         channels: &[Channel],
+                         auth: Option<Auth>)
+        -> Result<(), Error>
+    {
+        let request = serde_json::to_string(&Subscribe { t, product_ids, channels, auth })?;
+        self.socket.send(tungstenite::Message::Text(request))?;
+        Ok(())
+    }
+
+    /// Blocks until the next message arrives. Returns `None` once the
+    /// server has closed the connection.
+    pub fn next_message(&mut self) -> Result<Option<Message>, Error> {
+        match self.read()? {
+            Read::Message(message) => Ok(Some(message)),
+            Read::Closed => Ok(None),
+            Read::TimedOut => Err(Error::Io(io::ErrorKind::TimedOut.into()))
+        }
+    }
+
+    fn read(&mut self) -> Result<Read, Error> {
+        loop {
+            let frame = match self.socket.read() {
+                Ok(frame) => frame,
+                Err(tungstenite::Error::ConnectionClosed) |
+                Err(tungstenite::Error::AlreadyClosed) => return Ok(Read::Closed),
+                Err(tungstenite::Error::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock ||
+                                                      e.kind() == io::ErrorKind::TimedOut => {
+                    return Ok(Read::TimedOut)
+                }
+                Err(e) => return Err(e.into())
+            };
+
+            match frame {
+                tungstenite::Message::Text(text) => return self.decode(&text).map(Read::Message),
+                tungstenite::Message::Binary(data) => {
+                    return self.decode(&String::from_utf8_lossy(&data)).map(Read::Message)
+                }
+                tungstenite::Message::Close(_) => return Ok(Read::Closed),
+                // Pings are answered by tungstenite itself
+                _ => continue
+            }
+        }
+    }
+
+    fn decode(&self, text: &str) -> Result<Message, Error> {
+        if let Some(ref recorder) = self.recorder {
+            recorder.record_message(text)?;
+        }
+        Ok(serde_json::from_str(text)?)
+    }
+
+    /// Records every message received from now on.
+    pub fn record_to(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Sets how long `next_message` blocks before failing with a timed out
+    /// `Error::Io`. `None` blocks indefinitely.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        match *self.socket.get_mut() {
+            MaybeTlsStream::Plain(ref stream) => stream.set_read_timeout(timeout)?,
+            MaybeTlsStream::NativeTls(ref stream) => stream.get_ref().set_read_timeout(timeout)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn close(&mut self) -> Result<(), Error> {
+        self.socket.close(None)?;
+        Ok(())
+    }
+
+    /// Reads the feed on a thread of its own, delivering its messages
+    /// through a non-blocking `Stream`.
+    pub fn into_stream(mut self) -> FeedStream<Message> {
+        FeedStream::spawn(move || self.next_message())
+    }
+}
+
+impl Iterator for Feed {
+    type Item = Result<Message, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_message().transpose()
+    }
+}
+
+/// A feed read on a thread of its own, as a `futures::Stream`.
+///
+/// The thread blocks on the connection and passes what it reads over a
+/// channel, waking the task that last polled the stream, so polling never
+/// blocks. The stream ends when the connection is closed, or after an
+/// error other than a message that couldn't be decoded. Once the stream is
+/// dropped, the thread stops after its next read.
+pub struct FeedStream<T> {
+    receiver: mpsc::Receiver<Result<Option<T>, Error>>,
+    task: Arc<Mutex<Option<Task>>>,
+    done: bool
+}
+
+impl<T: Send + 'static> FeedStream<T> {
+    fn spawn<F>(mut next: F) -> FeedStream<T>
+        where F: FnMut() -> Result<Option<T>, Error> + Send + 'static
+    {
+        let (sender, receiver) = mpsc::channel();
+        let task: Arc<Mutex<Option<Task>>> = Arc::new(Mutex::new(None));
+        let waker = task.clone();
+        thread::spawn(move || loop {
+            let read = next();
+            let last = !matches!(read, Ok(Some(_)) | Err(Error::Json(_)));
+            if sender.send(read).is_err() {
+                return;
+            }
+            if let Some(ref task) = *waker.lock().unwrap() {
+                task.notify();
+            }
+            if last {
+                return;
+            }
+        });
+        FeedStream { receiver, task, done: false }
+    }
+}
+
+impl<T> Stream for FeedStream<T> {
+    type Item = T;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<T>, Error> {
+        if self.done {
+            return Ok(Async::Ready(None));
+        }
+        // Registered before looking, so a message sent in between still
+        // wakes this task
+        *self.task.lock().unwrap() = Some(task::current());
+        match self.receiver.try_recv() {
+            Ok(Ok(Some(item))) => Ok(Async::Ready(Some(item))),
+            Ok(Err(e)) => Err(e),
+            Err(mpsc::TryRecvError::Empty) => Ok(Async::NotReady),
+            Ok(Ok(None)) | Err(mpsc::TryRecvError::Disconnected) => {
+                self.done = true;
+                Ok(Async::Ready(None))
+            }
+        }
+    }
+}
+
+/// A feed subscribed to the authenticated `user` channel, yielding only
+/// updates to our own orders.
+///
+/// Error messages from the server, such as a rejected signature, are
+/// returned as `Error::Api`.
+pub struct UserFeed {
+    feed: Feed
+}
+
+impl UserFeed {
+    pub fn connect(client: &PrivateClient, product_ids: &[&str]) -> Result<UserFeed, Error> {
+        UserFeed::connect_to(FEED_URL, client, product_ids)
+    }
+
+    pub fn connect_to(url: &str, client: &PrivateClient, product_ids: &[&str]) -> Result<UserFeed, Error> {
+        Ok(UserFeed {
+            feed: Feed::connect_authenticated_to(url, client, product_ids, &[Channel::User])?
+        })
+    }
+
+    /// Blocks until the next update to one of our orders arrives. Returns
+    /// `None` once the server has closed the connection.
+    pub fn next_event(&mut self) -> Result<Option<OrderEvent>, Error> {
+        loop {
+            match self.feed.next_message()? {
+                Some(Message::Error(e)) => return Err(Error::Api(ApiError { message: e.message, status: None })),
+                Some(message) => {
+                    if let Some(event) = message.into_order_event() {
+                        return Ok(Some(event));
+                    }
+                }
+                None => return Ok(None)
+            }
+        }
+    }
+
+    pub fn close(&mut self) -> Result<(), Error> {
+        self.feed.close()
+    }
+
+    /// Reads the feed on a thread of its own, delivering the events through
+    /// a non-blocking `Stream`.
+    pub fn into_stream(mut self) -> FeedStream<OrderEvent> {
+        FeedStream::spawn(move || self.next_event())
+    }
+}
+
+impl Iterator for UserFeed {
+    type Item = Result<OrderEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+/// An event from a `ReconnectingFeed`: either a feed message or a change
+/// in the state of the connection.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, PartialEq, Debug)]
+pub enum FeedEvent {
+    Message(Message),
+    /// The connection was (re)established and every subscription restored.
+    Connected,
+    /// The connection was lost, closed by the server, or went silent for
+    /// longer than the heartbeat timeout.
+    Disconnected {
+        reason: String
+    },
+    /// The next connection attempt will be made after `delay`.
+    Reconnecting {
+        attempt: u32,
+        delay: Duration
+    }
+}
+
+/// A feed connection that survives disconnects.
+///
+/// The `heartbeat` channel is subscribed to for every product, and if no
+/// heartbeat arrives within the heartbeat timeout the connection is
+/// considered dead. Lost connections are re-established with exponential
+/// backoff and all subscriptions are restored. Connection state changes are
+/// delivered as events alongside feed messages.
+///
+/// Error messages from the server, such as an invalid subscription, are
+/// returned as `Error::Api` and close the feed.
+pub struct ReconnectingFeed {
+    url: String,
+    subscriptions: Vec<(Vec<String>, Vec<Channel>)>,
+    heartbeat_timeout: Duration,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    max_attempts: Option<u32>,
+    feed: Option<Feed>,
+    attempt: u32,
+    retry_delay: Option<Duration>,
+    last_heartbeat: Instant,
+    last_heartbeat_time: Option<DateTime<Utc>>,
+    pending: VecDeque<FeedEvent>,
+    recorder: Option<Recorder>,
+    closed: bool
+}
+
+impl ReconnectingFeed {
+    pub fn new(product_ids: &[&str], channels: &[Channel]) -> ReconnectingFeed {
+        ReconnectingFeed::with_url(FEED_URL, product_ids, channels)
+    }
+
+    /// Like `new`, but against a feed at `url`. No connection is made until
+    /// the first event is requested.
+    pub fn with_url(url: &str, product_ids: &[&str], channels: &[Channel]) -> ReconnectingFeed {
+        ReconnectingFeed {
+            url: url.to_owned(),
+            subscriptions: vec![(product_ids.iter().map(|p| p.to_string()).collect(), channels.to_vec())],
+            heartbeat_timeout: Duration::from_secs(5),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_attempts: None,
+            feed: None,
+            attempt: 0,
+            retry_delay: None,
+            last_heartbeat: Instant::now(),
+            last_heartbeat_time: None,
+            pending: VecDeque::new(),
+            recorder: None,
+            closed: false
+        }
+    }
+
+    /// How long the connection may go without a heartbeat before it is
+    /// dropped. Defaults to five seconds; heartbeats are sent every second.
+    pub fn heartbeat_timeout(mut self, timeout: Duration) -> ReconnectingFeed {
+        self.heartbeat_timeout = timeout;
+        self
+    }
+
+    /// The delay before the first reconnection attempt, doubling with each
+    /// failed attempt up to `max`. Defaults to one second and one minute.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> ReconnectingFeed {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Gives up after `attempts` consecutive failed reconnection attempts,
+    /// returning the last connection error. Retries forever by default.
+    pub fn max_attempts(mut self, attempts: u32) -> ReconnectingFeed {
+        self.max_attempts = Some(attempts);
+        self
+    }
+
+    /// Records the messages received on every connection.
+    pub fn record_to(mut self, recorder: Recorder) -> ReconnectingFeed {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Adds a subscription, which is also restored on every reconnect.
+    pub fn subscribe(&mut self, product_ids: &[&str], channels: &[Channel]) -> Result<(), Error> {
+        let mut with_heartbeat = channels.to_vec();
+        with_heartbeat.push(Channel::Heartbeat);
+        if let Some(ref mut feed) = self.feed {
+            feed.subscribe(product_ids, &with_heartbeat)?;
+        }
+        self.subscriptions.push((product_ids.iter().map(|p| p.to_string()).collect(), channels.to_vec()));
+        Ok(())
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.feed.is_some()
+    }
+
+    /// The server timestamp of the most recent heartbeat.
+    pub fn last_heartbeat(&self) -> Option<DateTime<Utc>> {
+        self.last_heartbeat_time
+    }
+
+    fn connect(&self) -> Result<Feed, Error> {
+        let mut feed: Option<Feed> = None;
+        for (product_ids, channels) in &self.subscriptions {
+            let product_ids: Vec<&str> = product_ids.iter().map(|p| p.as_str()).collect();
+            let mut channels = channels.clone();
+            channels.push(Channel::Heartbeat);
+            match feed {
+                Some(ref mut feed) => feed.subscribe(&product_ids, &channels)?,
+                None => feed = Some(Feed::connect_to(&self.url, &product_ids, &channels)?)
+            }
+        }
+        let mut feed = feed.expect("the subscription given to new is never removed");
+        if let Some(ref recorder) = self.recorder {
+            feed.record_to(recorder.clone());
+        }
+        Ok(feed)
+    }
+
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        self.initial_backoff.checked_mul(factor).map_or(self.max_backoff, |d| d.min(self.max_backoff))
+    }
+
+    fn disconnect(&mut self, reason: String) {
+        if let Some(mut feed) = self.feed.take() {
+            let _ = feed.close();
+            self.pending.push_back(FeedEvent::Disconnected { reason });
+        }
+        self.attempt += 1;
+        let delay = self.backoff_delay(self.attempt);
+        self.retry_delay = Some(delay);
+        self.pending.push_back(FeedEvent::Reconnecting { attempt: self.attempt, delay });
+    }
+
+    /// Blocks until the next message or connection event. Returns `None`
+    /// once the feed has been closed.
+    pub fn next_event(&mut self) -> Result<Option<FeedEvent>, Error> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+            if self.closed {
+                return Ok(None);
+            }
+
+            if self.feed.is_none() {
+                if let Some(delay) = self.retry_delay.take() {
+                    thread::sleep(delay);
+                }
+                match self.connect() {
+                    Ok(feed) => {
+                        self.feed = Some(feed);
+                        self.attempt = 0;
+                        self.last_heartbeat = Instant::now();
+                        self.pending.push_back(FeedEvent::Connected);
+                    }
+                    Err(e) => {
+                        if self.max_attempts.is_some_and(|max| self.attempt >= max) {
+                            self.closed = true;
+                            return Err(e);
+                        }
+                        self.disconnect(format!("{:?}", e));
+                    }
+                }
+                continue;
+            }
+
+            let remaining = match self.heartbeat_timeout.checked_sub(self.last_heartbeat.elapsed()) {
+                Some(remaining) if remaining > Duration::from_millis(0) => remaining,
+                _ => {
+                    self.disconnect("heartbeat timeout".to_owned());
+                    continue;
+                }
+            };
+
+            let read = {
+                let feed = self.feed.as_mut().unwrap();
+                feed.set_read_timeout(Some(remaining)).and_then(|_| feed.read())
+            };
+            match read {
+                // Reconnecting would only repeat whatever the server rejected
+                Ok(Read::Message(Message::Error(e))) => {
+                    let _ = self.close();
+                    return Err(Error::Api(ApiError { message: e.message, status: None }));
+                }
+                Ok(Read::Message(message)) => {
+                    if let Message::Heartbeat(ref heartbeat) = message {
+                        self.last_heartbeat = Instant::now();
+                        self.last_heartbeat_time = Some(heartbeat.time);
+                    }
+                    return Ok(Some(FeedEvent::Message(message)));
+                }
+                Ok(Read::TimedOut) => continue,
+                Ok(Read::Closed) => self.disconnect("connection closed".to_owned()),
+                // A message we couldn't decode doesn't mean the connection
+                // is broken
+                Err(Error::Json(e)) => return Err(Error::Json(e)),
+                Err(e) => self.disconnect(format!("{:?}", e))
+            }
+        }
+    }
+
+    pub fn close(&mut self) -> Result<(), Error> {
+        self.closed = true;
+        self.pending.clear();
+        match self.feed.take() {
+            Some(mut feed) => feed.close(),
+            None => Ok(())
+        }
+    }
+
+    /// Reads the feed on a thread of its own, reconnecting as needed, and
+    /// delivers the events through a non-blocking `Stream`.
+    pub fn into_stream(mut self) -> FeedStream<FeedEvent> {
+        FeedStream::spawn(move || self.next_event())
+    }
+}
+
+impl Iterator for ReconnectingFeed {
+    type Item = Result<FeedEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
@@ -0,0 +1,820 @@
+//! Order entry over the exchange's FIX 4.2 gateway.
+//!
+//! A `Session` logs on with the same key, secret and passphrase as the REST
+//! API, keeps the session alive with heartbeats, numbers its messages and
+//! answers resend requests. Orders are sent as NewOrderSingle and cancels as
+//! OrderCancelRequest, and the execution reports that come back can be
+//! turned into the crate's `Order` model.
+//!
+//! The session reads and writes any `Read + Write` stream. `connect` opens
+//! a TLS connection to the gateway with a one second read timeout, so that
+//! `poll` returns regularly to send heartbeats; other streams should be set
+//! up the same way.
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use native_tls::{TlsConnector, TlsStream};
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use super::ApiError;
+use super::Credentials;
+use super::Error;
+use super::Side;
+use super::{Funds, NewOrder, Size};
+use super::private::{Order, OrderId};
+use super::signer::{random_hex, random_uuid, HmacSigner};
+
+pub const BEGIN_STRING: &str = "FIX.4.2";
+pub const FIX_API_ADDR: &str = "fix.gdax.com:4198";
+
+const FIX_API_HOST: &str = "fix.gdax.com";
+const TARGET_COMP_ID: &str = "Coinbase";
+const SOH: u8 = 1;
+
+// How long `connect` lets a read block
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+// The longest message body `decode` accepts; the gateway's are far shorter
+const MAX_BODY_LENGTH: usize = 64 * 1024;
+
+// How many application messages a session keeps for resending. Older ones
+// are gap filled instead.
+const MAX_RESENDABLE: usize = 1000;
+
+/// The tags used by the gateway.
+pub mod tag {
+    pub const BEGIN_SEQ_NO: u32 = 7;
+    pub const BEGIN_STRING: u32 = 8;
+    pub const BODY_LENGTH: u32 = 9;
+    pub const CHECK_SUM: u32 = 10;
+    pub const CL_ORD_ID: u32 = 11;
+    pub const END_SEQ_NO: u32 = 16;
+    pub const HANDL_INST: u32 = 21;
+    pub const LAST_SHARES: u32 = 32;
+    pub const MSG_SEQ_NUM: u32 = 34;
+    pub const MSG_TYPE: u32 = 35;
+    pub const NEW_SEQ_NO: u32 = 36;
+    pub const ORDER_ID: u32 = 37;
+    pub const ORDER_QTY: u32 = 38;
+    pub const ORD_STATUS: u32 = 39;
+    pub const ORD_TYPE: u32 = 40;
+    pub const ORIG_CL_ORD_ID: u32 = 41;
+    pub const POSS_DUP_FLAG: u32 = 43;
+    pub const PRICE: u32 = 44;
+    pub const REF_SEQ_NUM: u32 = 45;
+    pub const SENDER_COMP_ID: u32 = 49;
+    pub const SENDING_TIME: u32 = 52;
+    pub const SIDE: u32 = 54;
+    pub const SYMBOL: u32 = 55;
+    pub const TARGET_COMP_ID: u32 = 56;
+    pub const TEXT: u32 = 58;
+    pub const TIME_IN_FORCE: u32 = 59;
+    pub const TRANSACT_TIME: u32 = 60;
+    pub const RAW_DATA: u32 = 96;
+    pub const ENCRYPT_METHOD: u32 = 98;
+    pub const STOP_PX: u32 = 99;
+    pub const CXL_REJ_REASON: u32 = 102;
+    pub const ORD_REJ_REASON: u32 = 103;
+    pub const HEART_BT_INT: u32 = 108;
+    pub const TEST_REQ_ID: u32 = 112;
+    pub const ORIG_SENDING_TIME: u32 = 122;
+    pub const GAP_FILL_FLAG: u32 = 123;
+    pub const MISC_FEE_AMT: u32 = 137;
+    pub const EXEC_TYPE: u32 = 150;
+    pub const CASH_ORDER_QTY: u32 = 152;
+    pub const PASSWORD: u32 = 554;
+    pub const TRADE_ID: u32 = 1003;
+    pub const CANCEL_ORDERS_ON_DISCONNECT: u32 = 8013;
+}
+
+/// The message types used by the gateway.
+pub mod msg_type {
+    pub const HEARTBEAT: &str = "0";
+    pub const TEST_REQUEST: &str = "1";
+    pub const RESEND_REQUEST: &str = "2";
+    pub const REJECT: &str = "3";
+    pub const SEQUENCE_RESET: &str = "4";
+    pub const LOGOUT: &str = "5";
+    pub const EXECUTION_REPORT: &str = "8";
+    pub const ORDER_CANCEL_REJECT: &str = "9";
+    pub const LOGON: &str = "A";
+    pub const NEW_ORDER_SINGLE: &str = "D";
+    pub const ORDER_CANCEL_REQUEST: &str = "F";
+}
+
+// Header fields that `Session` fills in itself
+const HEADER_TAGS: &[u32] = &[tag::MSG_TYPE, tag::SENDER_COMP_ID, tag::TARGET_COMP_ID, tag::MSG_SEQ_NUM,
+                               tag::SENDING_TIME, tag::POSS_DUP_FLAG, tag::ORIG_SENDING_TIME];
+
+fn invalid_data(message: String) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+fn tls_error<E: ToString>(e: E) -> Error {
+    Error::Io(io::Error::other(e.to_string()))
+}
+
+/// A UTC timestamp in the format used for `SendingTime` and
+/// `TransactTime`.
+pub fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%d-%H:%M:%S%.3f").to_string()
+}
+
+fn parse_time(s: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(s, "%Y%m%d-%H:%M:%S%.f").ok().map(|t| Utc.from_utc_datetime(&t))
+}
+
+/// A FIX message: its fields in order, starting with `MsgType`. The
+/// `BeginString`, `BodyLength` and `CheckSum` fields are added by `encode`
+/// and removed by `decode`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    fields: Vec<(u32, String)>
+}
+
+impl Message {
+    pub fn new(msg_type: &str) -> Message {
+        Message { fields: vec![(tag::MSG_TYPE, msg_type.to_owned())] }
+    }
+
+    /// Appends a field.
+    pub fn with<V: ToString>(mut self, tag: u32, value: V) -> Message {
+        self.fields.push((tag, value.to_string()));
+        self
+    }
+
+    pub fn msg_type(&self) -> &str {
+        self.get(tag::MSG_TYPE).unwrap_or("")
+    }
+
+    /// The value of the first field with `tag`.
+    pub fn get(&self, tag: u32) -> Option<&str> {
+        self.fields.iter().find(|&&(t, _)| t == tag).map(|(_, v)| v.as_str())
+    }
+
+    pub fn fields(&self) -> &[(u32, String)] {
+        &self.fields
+    }
+
+    fn parse<T: FromStr>(&self, tag: u32) -> Result<Option<T>, Error> {
+        match self.get(tag) {
+            Some(value) => value.parse().map(Some).map_err(|_| Error::InvalidNumber(value.to_owned())),
+            None => Ok(None)
+        }
+    }
+
+    fn require(&self, tag: u32) -> Result<&str, Error> {
+        self.get(tag).ok_or_else(|| invalid_data(format!("message {:?} has no tag {}", self.msg_type(), tag)))
+    }
+
+    /// The message on the wire, framed with `BeginString`, `BodyLength` and
+    /// `CheckSum`.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (tag, value) in &self.fields {
+            write!(body, "{}={}\x01", tag, value).expect("writing to a Vec can't fail");
+        }
+
+        let mut bytes = format!("{}={}\x01{}={}\x01", tag::BEGIN_STRING, BEGIN_STRING, tag::BODY_LENGTH, body.len())
+            .into_bytes();
+        bytes.extend(body);
+        let checksum = bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        write!(bytes, "{}={:03}\x01", tag::CHECK_SUM, checksum).expect("writing to a Vec can't fail");
+        bytes
+    }
+
+    /// Decodes the message at the start of `bytes`, returning it with the
+    /// number of bytes it took up, or `None` if `bytes` doesn't hold a
+    /// whole message yet.
+    pub fn decode(bytes: &[u8]) -> Result<Option<(Message, usize)>, Error> {
+        let field_end = |from: usize| bytes[from..].iter().position(|&b| b == SOH).map(|idx| from + idx);
+
+        let begin_end = match field_end(0) {
+            Some(end) => end,
+            None => return Ok(None)
+        };
+        if !bytes.starts_with(b"8=") {
+            return Err(invalid_data("FIX message doesn't start with BeginString".to_owned()));
+        }
+        let length_end = match field_end(begin_end + 1) {
+            Some(end) => end,
+            None => return Ok(None)
+        };
+        let length = String::from_utf8_lossy(&bytes[begin_end + 1..length_end]);
+        let length: usize = match length.strip_prefix("9=").and_then(|l| l.parse().ok()) {
+            Some(length) if length <= MAX_BODY_LENGTH => length,
+            _ => return Err(invalid_data(format!("invalid BodyLength {:?}", length)))
+        };
+
+        // The body is followed by the seven bytes of `10=NNN<SOH>`
+        let body_start = length_end + 1;
+        let body_end = match body_start.checked_add(length) {
+            Some(end) => end,
+            None => return Err(invalid_data(format!("invalid BodyLength {}", length)))
+        };
+        if bytes.len() < body_end + 7 {
+            return Ok(None);
+        }
+        let checksum = bytes[..body_end].iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        let trailer = String::from_utf8_lossy(&bytes[body_end..body_end + 7]);
+        if trailer != format!("10={:03}\x01", checksum) {
+            return Err(invalid_data(format!("bad CheckSum {:?}, expected {:03}", trailer, checksum)));
+        }
+
+        let mut fields = Vec::new();
+        for field in bytes[body_start..body_end].split(|&b| b == SOH).filter(|f| !f.is_empty()) {
+            let field = String::from_utf8_lossy(field);
+            let (tag, value) = match field.find('=') {
+                Some(idx) => (&field[..idx], &field[idx + 1..]),
+                None => return Err(invalid_data(format!("malformed field {:?}", field)))
+            };
+            let tag = tag.parse().map_err(|_| invalid_data(format!("malformed tag {:?}", tag)))?;
+            fields.push((tag, value.to_owned()));
+        }
+        if fields.first().map(|f| f.0) != Some(tag::MSG_TYPE) {
+            return Err(invalid_data("FIX message doesn't start with MsgType".to_owned()));
+        }
+
+        Ok(Some((Message { fields }, body_end + 7)))
+    }
+}
+
+/// What an execution report says happened to an order. Types this crate
+/// doesn't recognise yet are kept verbatim in `Unknown`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ExecType {
+    New,
+    Fill,
+    Done,
+    Canceled,
+    Stopped,
+    Rejected,
+    Changed,
+    Status,
+    Unknown(String)
+}
+
+impl ExecType {
+    pub fn from_code(code: &str) -> ExecType {
+        match code {
+            "0" => ExecType::New,
+            "1" => ExecType::Fill,
+            "3" => ExecType::Done,
+            "4" => ExecType::Canceled,
+            "7" => ExecType::Stopped,
+            "8" => ExecType::Rejected,
+            "D" => ExecType::Changed,
+            "I" => ExecType::Status,
+            other => ExecType::Unknown(other.to_owned())
+        }
+    }
+}
+
+/// An ExecutionReport, sent when an order is accepted, filled, changed or
+/// finished.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecutionReport {
+    pub order_id: OrderId,
+    pub client_order_id: Option<Uuid>,
+    pub product_id: String,
+    pub side: Side,
+    pub exec_type: ExecType,
+    /// The price of the fill for fills, and of the order otherwise.
+    pub price: Option<f64>,
+    pub size: Option<f64>,
+    pub funds: Option<f64>,
+    /// The size of the fill, for fills.
+    pub last_size: Option<f64>,
+    pub trade_id: Option<u64>,
+    pub fee: Option<f64>,
+    pub reject_reason: Option<String>,
+    pub text: Option<String>,
+    pub transact_time: Option<DateTime<Utc>>
+}
+
+impl ExecutionReport {
+    pub fn from_message(message: &Message) -> Result<ExecutionReport, Error> {
+        let uuid = |s: &str| Uuid::parse_str(s).map_err(|_| invalid_data(format!("invalid order id {:?}", s)));
+
+        Ok(ExecutionReport {
+            order_id: uuid(message.require(tag::ORDER_ID)?)?,
+            client_order_id: message.get(tag::CL_ORD_ID).and_then(|id| Uuid::parse_str(id).ok()),
+            product_id: message.require(tag::SYMBOL)?.to_owned(),
+            side: parse_side(message.require(tag::SIDE)?)?,
+            exec_type: ExecType::from_code(message.require(tag::EXEC_TYPE)?),
+            price: message.parse(tag::PRICE)?,
+            size: message.parse(tag::ORDER_QTY)?,
+            funds: message.parse(tag::CASH_ORDER_QTY)?,
+            last_size: message.parse(tag::LAST_SHARES)?,
+            trade_id: message.parse(tag::TRADE_ID)?,
+            fee: message.parse(tag::MISC_FEE_AMT)?,
+            reject_reason: message.get(tag::ORD_REJ_REASON).map(|s| s.to_owned()),
+            text: message.get(tag::TEXT).map(|s| s.to_owned()),
+            transact_time: message.get(tag::TRANSACT_TIME).and_then(parse_time)
+        })
+    }
+
+    /// The order as of this report, for the first report of an order.
+    pub fn to_order(&self) -> Order {
+        let mut order = Order {
+            id: self.order_id,
+            size: self.size.unwrap_or(0.0),
+            price: self.price.unwrap_or(0.0),
+            done_reason: None,
+            status: "open".to_owned(),
+            settled: false,
+            filled_size: 0.0,
+            executed_value: 0.0,
+            product_id: self.product_id.clone(),
+            fill_fees: 0.0,
+            side: self.side,
+            created_at: self.transact_time.unwrap_or_else(Utc::now),
+            done_at: None
+        };
+        self.update(&mut order);
+        order
+    }
+
+    /// Applies this report to `order`, adding fills to its filled size,
+    /// value and fees, and marking it done when it's finished.
+    pub fn update(&self, order: &mut Order) {
+        let done = |order: &mut Order, status: &str, reason: &str| {
+            order.status = status.to_owned();
+            order.done_reason = Some(reason.to_owned());
+            order.done_at = self.transact_time.or_else(|| Some(Utc::now()));
+        };
+
+        match self.exec_type {
+            ExecType::Fill => {
+                let (size, price) = (self.last_size.unwrap_or(0.0), self.price.unwrap_or(0.0));
+                order.filled_size += size;
+                order.executed_value += size * price;
+                order.fill_fees += self.fee.unwrap_or(0.0);
+            }
+            ExecType::Changed => {
+                order.size = self.size.unwrap_or(order.size);
+                order.price = self.price.unwrap_or(order.price);
+            }
+            ExecType::Done => done(order, "done", "filled"),
+            ExecType::Canceled => done(order, "done", "canceled"),
+            ExecType::Rejected => {
+                let reason = self.text.as_ref().or(self.reject_reason.as_ref()).cloned().unwrap_or_default();
+                done(order, "rejected", &reason)
+            }
+            _ => ()
+        }
+    }
+}
+
+fn parse_side(code: &str) -> Result<Side, Error> {
+    match code {
+        "1" => Ok(Side::Buy),
+        "2" => Ok(Side::Sell),
+        other => Err(invalid_data(format!("invalid Side {:?}", other)))
+    }
+}
+
+fn side_code(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "1",
+        Side::Sell => "2"
+    }
+}
+
+/// Something the gateway sent that the application should know about.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Execution(ExecutionReport),
+    CancelRejected {
+        order_id: Option<OrderId>,
+        client_order_id: Option<Uuid>,
+        reason: Option<String>
+    },
+    /// The gateway rejected the message with sequence number `ref_seq_num`.
+    Rejected {
+        ref_seq_num: Option<u64>,
+        text: Option<String>
+    },
+    LoggedOut(Option<String>),
+    /// An application message this crate doesn't model.
+    Message(Message)
+}
+
+/// A FIX session with the order entry gateway.
+pub struct Session<S> {
+    stream: S,
+    buffer: Vec<u8>,
+    sender_comp_id: String,
+    signer: HmacSigner,
+    passphrase: Zeroizing<String>,
+    heartbeat_interval: Duration,
+    cancel_on_disconnect: bool,
+    next_out: u64,
+    next_in: u64,
+    // The last `MAX_RESENDABLE` application messages sent, with their
+    // sending time, for resending
+    sent: BTreeMap<u64, (Message, String)>,
+    resend_requested: bool,
+    // Events that arrived while `logon` waited, for `poll` to return
+    pending: VecDeque<Event>,
+    test_request: Option<(String, Instant)>,
+    last_sent: Instant,
+    last_received: Instant,
+    logged_on: bool
+}
+
+impl Session<TlsStream<TcpStream>> {
+    /// Opens a TLS connection to the gateway. Call `logon` next.
+    pub fn connect(credentials: &Credentials) -> Result<Session<TlsStream<TcpStream>>, Error> {
+        Session::connect_to(FIX_API_ADDR, FIX_API_HOST, credentials)
+    }
+
+    /// Like `connect`, but to the gateway at `addr` with the certificate
+    /// for `host`, e.g. a sandbox.
+    pub fn connect_to(addr: &str, host: &str, credentials: &Credentials)
+        -> Result<Session<TlsStream<TcpStream>>, Error>
+    {
+        let tcp = TcpStream::connect(addr)?;
+        tcp.set_read_timeout(Some(READ_TIMEOUT))?;
+        let tls = TlsConnector::new().map_err(tls_error)?.connect(host, tcp).map_err(tls_error)?;
+        Ok(Session::new(tls, credentials))
+    }
+}
+
+impl<S: Read + Write> Session<S> {
+    /// A session over `stream` that isn't logged on yet.
+    pub fn new(stream: S, credentials: &Credentials) -> Session<S> {
+        Session {
+            stream,
+            buffer: Vec::new(),
+            sender_comp_id: credentials.key().to_owned(),
+            signer: HmacSigner::from_decoded(credentials.key(),
+                                             credentials.decoded_secret().clone(),
+                                             credentials.passphrase()),
+            passphrase: Zeroizing::new(credentials.passphrase().to_owned()),
+            heartbeat_interval: Duration::from_secs(30),
+            cancel_on_disconnect: false,
+            next_out: 1,
+            next_in: 1,
+            sent: BTreeMap::new(),
+            resend_requested: false,
+            pending: VecDeque::new(),
+            test_request: None,
+            last_sent: Instant::now(),
+            last_received: Instant::now(),
+            logged_on: false
+        }
+    }
+
+    /// How often to send heartbeats, and to expect them. Whole seconds;
+    /// defaults to 30.
+    pub fn heartbeat_interval(mut self, interval: Duration) -> Session<S> {
+        self.heartbeat_interval = interval;
+        self
+    }
+
+    /// Asks the gateway to cancel every open order of the session when it
+    /// disconnects.
+    pub fn cancel_on_disconnect(mut self, cancel: bool) -> Session<S> {
+        self.cancel_on_disconnect = cancel;
+        self
+    }
+
+    pub fn is_logged_on(&self) -> bool {
+        self.logged_on
+    }
+
+    /// The sequence number of the next message sent.
+    pub fn next_sender_seq_num(&self) -> u64 {
+        self.next_out
+    }
+
+    /// The sequence number expected of the next message received.
+    pub fn next_target_seq_num(&self) -> u64 {
+        self.next_in
+    }
+
+    /// Sends a signed Logon and waits for the gateway's. Fails with
+    /// `Error::Api` if the gateway logs out instead.
+    pub fn logon(&mut self) -> Result<(), Error> {
+        let sending_time = format_time(Utc::now());
+        // The signature covers these header fields and the passphrase
+        let prehash = [sending_time.as_str(),
+                       msg_type::LOGON,
+                       &self.next_out.to_string(),
+                       &self.sender_comp_id,
+                       TARGET_COMP_ID,
+                       &self.passphrase].join("\x01");
+
+        let logon = Message::new(msg_type::LOGON)
+            .with(tag::ENCRYPT_METHOD, 0)
+            .with(tag::HEART_BT_INT, self.heartbeat_interval.as_secs())
+            .with(tag::PASSWORD, &*self.passphrase)
+            .with(tag::RAW_DATA, self.signer.signature(&prehash))
+            .with(tag::CANCEL_ORDERS_ON_DISCONNECT, if self.cancel_on_disconnect { "Y" } else { "N" });
+        self.send_at(logon, sending_time)?;
+
+        let deadline = Instant::now() + self.heartbeat_interval * 2;
+        while !self.logged_on {
+            match self.read_event()? {
+                Some(Event::LoggedOut(text)) => {
+                    return Err(Error::Api(ApiError { message: text.unwrap_or_default(), status: None }));
+                }
+                Some(event) => self.pending.push_back(event),
+                None => ()
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::Io(io::Error::new(io::ErrorKind::TimedOut, "no Logon from the gateway")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends a Logout. The gateway confirms with its own, which `poll`
+    /// reports as `Event::LoggedOut`.
+    pub fn logout(&mut self) -> Result<(), Error> {
+        self.send(Message::new(msg_type::LOGOUT))
+    }
+
+    /// Sends `order` as a NewOrderSingle and returns its client order id.
+    /// Limit and stop orders are good until cancelled.
+    pub fn place_order(&mut self, order: &NewOrder) -> Result<Uuid, Error> {
+        let client_order_id = random_uuid()?;
+        let (side, product_id) = match *order {
+            NewOrder::Limit { side, ref product_id, .. } |
+            NewOrder::Market { side, ref product_id, .. } |
+            NewOrder::Stop { side, ref product_id, .. } => (side, product_id)
+        };
+
+        let message = Message::new(msg_type::NEW_ORDER_SINGLE)
+            .with(tag::HANDL_INST, 1)
+            .with(tag::CL_ORD_ID, client_order_id)
+            .with(tag::SYMBOL, product_id)
+            .with(tag::SIDE, side_code(side));
+        let message = match *order {
+            NewOrder::Limit { price, size, .. } => message.with(tag::ORD_TYPE, 2)
+                                                          .with(tag::PRICE, price)
+                                                          .with(tag::ORDER_QTY, size)
+                                                          .with(tag::TIME_IN_FORCE, 1),
+            NewOrder::Market { size_or_funds, .. } => with_amount(message.with(tag::ORD_TYPE, 1), size_or_funds),
+            NewOrder::Stop { price, size_or_funds, .. } => with_amount(message.with(tag::ORD_TYPE, 3)
+                                                                              .with(tag::STOP_PX, price)
+                                                                              .with(tag::TIME_IN_FORCE, 1),
+                                                                       size_or_funds)
+        };
+
+        self.send(message)?;
+        Ok(client_order_id)
+    }
+
+    /// Sends an OrderCancelRequest for the order `order_id` of `product_id`
+    /// and returns the request's client order id.
+    pub fn cancel_order(&mut self, product_id: &str, order_id: OrderId) -> Result<Uuid, Error> {
+        let client_order_id = random_uuid()?;
+        self.send(Message::new(msg_type::ORDER_CANCEL_REQUEST)
+                      .with(tag::CL_ORD_ID, client_order_id)
+                      .with(tag::ORDER_ID, order_id)
+                      .with(tag::SYMBOL, product_id))?;
+        Ok(client_order_id)
+    }
+
+    /// Reads what the gateway has sent, answering heartbeats, test requests
+    /// and resend requests, and returns the first event for the
+    /// application. Returns `None` when the stream's read times out, or once
+    /// the gateway's Logon arrives. A heartbeat or test request is sent
+    /// whenever one is due.
+    pub fn poll(&mut self) -> Result<Option<Event>, Error> {
+        match self.pending.pop_front() {
+            Some(event) => Ok(Some(event)),
+            None => self.read_event()
+        }
+    }
+
+    fn read_event(&mut self) -> Result<Option<Event>, Error> {
+        loop {
+            self.keep_alive()?;
+            if let Some((message, len)) = Message::decode(&self.buffer)? {
+                self.buffer.drain(..len);
+                self.last_received = Instant::now();
+                let logging_on = !self.logged_on;
+                if let Some(event) = self.handle(message)? {
+                    return Ok(Some(event));
+                }
+                if logging_on && self.logged_on {
+                    return Ok(None);
+                }
+                continue;
+            }
+
+            let mut chunk = [0u8; 4096];
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                                             "the gateway closed the connection"))),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+                    return Ok(None);
+                }
+                Err(e) => return Err(e.into())
+            }
+        }
+    }
+
+    // Sends a heartbeat when we've been quiet for an interval, and a test
+    // request when the gateway has
+    fn keep_alive(&mut self) -> Result<(), Error> {
+        if self.last_sent.elapsed() >= self.heartbeat_interval {
+            self.send(Message::new(msg_type::HEARTBEAT))?;
+        }
+
+        match self.test_request {
+            Some((_, sent)) if sent.elapsed() >= self.heartbeat_interval => {
+                Err(Error::Io(io::Error::new(io::ErrorKind::TimedOut, "the gateway stopped responding")))
+            }
+            None if self.last_received.elapsed() >= self.heartbeat_interval + self.heartbeat_interval / 5 => {
+                let id = random_hex(8)?;
+                self.send(Message::new(msg_type::TEST_REQUEST).with(tag::TEST_REQ_ID, &id))?;
+                self.test_request = Some((id, Instant::now()));
+                Ok(())
+            }
+            _ => Ok(())
+        }
+    }
+
+    fn handle(&mut self, message: Message) -> Result<Option<Event>, Error> {
+        let seq: u64 = message.parse(tag::MSG_SEQ_NUM)?
+                              .ok_or_else(|| invalid_data("message without MsgSeqNum".to_owned()))?;
+        let poss_dup = message.get(tag::POSS_DUP_FLAG) == Some("Y");
+
+        // A reset, as opposed to a gap fill, applies whatever its own number
+        if message.msg_type() == msg_type::SEQUENCE_RESET && message.get(tag::GAP_FILL_FLAG) != Some("Y") {
+            self.next_in = message.parse(tag::NEW_SEQ_NO)?.unwrap_or(self.next_in);
+            return Ok(None);
+        }
+
+        if seq > self.next_in {
+            // Everything from the gap on is asked for again, so the messages
+            // received meanwhile are dropped. Logon and Logout can't wait for
+            // the gap to be filled: the logon is taken before the resend is
+            // asked for, and the resend asked for before confirming a logout.
+            if message.msg_type() == msg_type::LOGON {
+                self.logged_on = true;
+            }
+            self.request_resend()?;
+            if message.msg_type() == msg_type::LOGOUT {
+                return self.logged_out(message.get(tag::TEXT).map(|s| s.to_owned()));
+            }
+            return Ok(None);
+        }
+        if seq < self.next_in {
+            if poss_dup {
+                return Ok(None);
+            }
+            let text = format!("MsgSeqNum too low, expecting {} but received {}", self.next_in, seq);
+            self.send(Message::new(msg_type::LOGOUT).with(tag::TEXT, &text))?;
+            self.logged_on = false;
+            return Err(invalid_data(text));
+        }
+        self.next_in += 1;
+        if !poss_dup {
+            self.resend_requested = false;
+        }
+
+        let text = message.get(tag::TEXT).map(|s| s.to_owned());
+        match message.msg_type() {
+            msg_type::LOGON => self.logged_on = true,
+            msg_type::HEARTBEAT => {
+                if self.test_request.as_ref().map(|t| t.0.as_str()) == message.get(tag::TEST_REQ_ID) {
+                    self.test_request = None;
+                }
+            }
+            msg_type::TEST_REQUEST => {
+                let id = message.get(tag::TEST_REQ_ID).unwrap_or("").to_owned();
+                self.send(Message::new(msg_type::HEARTBEAT).with(tag::TEST_REQ_ID, id))?;
+            }
+            msg_type::RESEND_REQUEST => {
+                let begin = message.parse(tag::BEGIN_SEQ_NO)?.unwrap_or(1);
+                let end = message.parse(tag::END_SEQ_NO)?.unwrap_or(0);
+                self.resend(begin, end)?;
+            }
+            msg_type::SEQUENCE_RESET => {
+                self.next_in = self.next_in.max(message.parse(tag::NEW_SEQ_NO)?.unwrap_or(0));
+            }
+            msg_type::REJECT => {
+                return Ok(Some(Event::Rejected { ref_seq_num: message.parse(tag::REF_SEQ_NUM)?, text }));
+            }
+            msg_type::LOGOUT => return self.logged_out(text),
+            msg_type::EXECUTION_REPORT => {
+                return Ok(Some(Event::Execution(ExecutionReport::from_message(&message)?)));
+            }
+            msg_type::ORDER_CANCEL_REJECT => {
+                return Ok(Some(Event::CancelRejected {
+                    order_id: message.get(tag::ORDER_ID).and_then(|id| Uuid::parse_str(id).ok()),
+                    client_order_id: message.get(tag::CL_ORD_ID).and_then(|id| Uuid::parse_str(id).ok()),
+                    reason: text.or_else(|| message.get(tag::CXL_REJ_REASON).map(|s| s.to_owned()))
+                }));
+            }
+            _ => return Ok(Some(Event::Message(message)))
+        }
+        Ok(None)
+    }
+
+    // Asks for everything from the next expected message on, unless that's
+    // already been asked for
+    fn request_resend(&mut self) -> Result<(), Error> {
+        if !self.resend_requested {
+            self.resend_requested = true;
+            self.send(Message::new(msg_type::RESEND_REQUEST).with(tag::BEGIN_SEQ_NO, self.next_in)
+                                                            .with(tag::END_SEQ_NO, 0))?;
+        }
+        Ok(())
+    }
+
+    // Confirms a logout the gateway started
+    fn logged_out(&mut self, text: Option<String>) -> Result<Option<Event>, Error> {
+        if self.logged_on {
+            self.logged_on = false;
+            self.send(Message::new(msg_type::LOGOUT))?;
+        }
+        Ok(Some(Event::LoggedOut(text)))
+    }
+
+    // Resends the application messages numbered `begin` to `end` (0 for the
+    // last sent), and skips over the session messages with gap fills
+    fn resend(&mut self, begin: u64, end: u64) -> Result<(), Error> {
+        let last = self.next_out - 1;
+        let end = if end == 0 || end > last { last } else { end };
+
+        let now = format_time(Utc::now());
+        let mut seq = begin;
+        while seq <= end {
+            if let Some((message, sending_time)) = self.sent.get(&seq).cloned() {
+                let message = self.stamp(message, seq, &now, Some(&sending_time));
+                self.write(&message)?;
+                seq += 1;
+            } else {
+                let next = self.sent.range(seq..=end).next().map_or(end + 1, |(&next, _)| next);
+                let gap_fill = Message::new(msg_type::SEQUENCE_RESET).with(tag::GAP_FILL_FLAG, "Y")
+                                                                     .with(tag::NEW_SEQ_NO, next);
+                let gap_fill = self.stamp(gap_fill, seq, &now, Some(&now));
+                self.write(&gap_fill)?;
+                seq = next;
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, message: Message) -> Result<(), Error> {
+        self.send_at(message, format_time(Utc::now()))
+    }
+
+    fn send_at(&mut self, message: Message, sending_time: String) -> Result<(), Error> {
+        let seq = self.next_out;
+        let msg_type = message.msg_type().to_owned();
+        if msg_type == msg_type::NEW_ORDER_SINGLE || msg_type == msg_type::ORDER_CANCEL_REQUEST {
+            self.sent.insert(seq, (message.clone(), sending_time.clone()));
+            if self.sent.len() > MAX_RESENDABLE {
+                self.sent.pop_first();
+            }
+        }
+
+        let stamped = self.stamp(message, seq, &sending_time, None);
+        self.write(&stamped)?;
+        self.next_out += 1;
+        Ok(())
+    }
+
+    // Adds the standard header to `message`. Resent messages are flagged as
+    // possible duplicates and carry the time they were first sent.
+    fn stamp(&self, message: Message, seq: u64, sending_time: &str, original_time: Option<&str>) -> Message {
+        let mut stamped = Message::new(message.msg_type())
+            .with(tag::SENDER_COMP_ID, &self.sender_comp_id)
+            .with(tag::TARGET_COMP_ID, TARGET_COMP_ID)
+            .with(tag::MSG_SEQ_NUM, seq)
+            .with(tag::SENDING_TIME, sending_time);
+        if let Some(original_time) = original_time {
+            stamped = stamped.with(tag::POSS_DUP_FLAG, "Y").with(tag::ORIG_SENDING_TIME, original_time);
+        }
+        stamped.fields.extend(message.fields.into_iter().filter(|f| !HEADER_TAGS.contains(&f.0)));
+        stamped
+    }
+
+    fn write(&mut self, message: &Message) -> Result<(), Error> {
+        self.stream.write_all(&message.encode())?;
+        self.stream.flush()?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+}
+
+fn with_amount(message: Message, size_or_funds: super::SizeOrFunds) -> Message {
+    match size_or_funds {
+        Size(size) => message.with(tag::ORDER_QTY, size),
+        Funds(funds) => message.with(tag::CASH_ORDER_QTY, funds)
+    }
+}
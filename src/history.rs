@@ -0,0 +1,404 @@
+//! Downloading candle and trade history to files.
+//!
+//! History is written either as CSV or in a columnar format, and
+//! downloads resume from the last row already in the file, so an
+//! interrupted or periodic download only fetches what's missing.
+//!
+//! The columnar format is modelled on Parquet's row groups without the
+//! binary encoding: every append adds one line holding a JSON object with
+//! the schema name, the column names and one array of values per column.
+//! Both formats use the columns of `Row::COLUMNS`, in order, with the
+//! values encoded as the model structs serialize them.
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{self, Value};
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::thread;
+
+use super::Error;
+use super::PublicClient;
+use super::public::{Candle, Granularity, Trade, RATE_LIMIT_INTERVAL};
+
+// Candles fetched per request, and so appended per row group
+const CANDLES_PER_CHUNK: u64 = 300;
+
+/// A model struct that can be stored in a history file.
+pub trait Row: Serialize + for<'de> Deserialize<'de> {
+    /// Identifies the schema in columnar files.
+    const NAME: &'static str;
+    /// The stored columns, in the order of the struct's fields.
+    const COLUMNS: &'static [&'static str];
+    /// The integer column that increases with every row, used to resume.
+    const KEY: &'static str;
+}
+
+impl Row for Candle {
+    const NAME: &'static str = "candle";
+    const COLUMNS: &'static [&'static str] = &["time", "low", "high", "open", "close", "volume"];
+    const KEY: &'static str = "time";
+}
+
+impl Row for Trade {
+    const NAME: &'static str = "trade";
+    const COLUMNS: &'static [&'static str] = &["time", "trade_id", "price", "size", "side"];
+    const KEY: &'static str = "trade_id";
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Csv,
+    Columnar
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "csv" => Some(Format::Csv),
+            "columnar" => Some(Format::Columnar),
+            _ => None
+        }
+    }
+
+    /// CSV for paths ending in `.csv`, columnar otherwise.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Format {
+        match path.as_ref().extension() {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Format::Csv,
+            _ => Format::Columnar
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RowGroup {
+    schema: String,
+    columns: Vec<String>,
+    rows: usize,
+    data: Vec<Vec<Value>>
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+fn to_values<R: Row>(row: &R) -> Result<Vec<Value>, Error> {
+    match serde_json::to_value(row)? {
+        Value::Array(values) => Ok(values),
+        Value::Object(mut fields) => {
+            Ok(R::COLUMNS.iter().map(|c| fields.remove(*c).unwrap_or(Value::Null)).collect())
+        }
+        other => Err(invalid_data(format!("{} doesn't serialize to a row: {}", R::NAME, other)))
+    }
+}
+
+fn from_values<R: Row>(values: Vec<Value>) -> Result<R, Error> {
+    Ok(serde_json::from_value(Value::Array(values))?)
+}
+
+fn key_index<R: Row>() -> usize {
+    R::COLUMNS.iter().position(|c| *c == R::KEY).expect("key column must be one of the columns")
+}
+
+// Strings are always quoted and numbers never are, which is how the
+// reader tells them apart.
+fn csv_cell(value: &Value) -> String {
+    match *value {
+        Value::String(ref s) => format!("\"{}\"", s.replace('"', "\"\"")),
+        Value::Null => String::new(),
+        ref other => other.to_string()
+    }
+}
+
+fn parse_csv_line(line: &str) -> Result<Vec<Value>, Error> {
+    let mut values = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        let value = if chars.peek() == Some(&'"') {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        s.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => s.push(c),
+                    None => return Err(invalid_data(format!("unterminated quote in {:?}", line)))
+                }
+            }
+            Value::String(s)
+        } else {
+            let mut cell = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == ',' {
+                    break;
+                }
+                cell.push(c);
+                chars.next();
+            }
+            if cell.is_empty() { Value::Null } else { serde_json::from_str(&cell)? }
+        };
+        values.push(value);
+
+        match chars.next() {
+            Some(',') => {}
+            None => return Ok(values),
+            Some(c) => return Err(invalid_data(format!("unexpected {:?} after quoted cell in {:?}", c, line)))
+        }
+    }
+}
+
+fn parse_row_group<R: Row>(line: &str) -> Result<RowGroup, Error> {
+    let group: RowGroup = serde_json::from_str(line)?;
+    if group.schema != R::NAME || group.columns != R::COLUMNS {
+        return Err(invalid_data(format!("expected {} rows with columns {:?}, found {} rows with columns {:?}",
+                                        R::NAME, R::COLUMNS, group.schema, group.columns)));
+    }
+    Ok(group)
+}
+
+/// Appends `rows` to the file at `path`, creating it if needed.
+pub fn append<R: Row, P: AsRef<Path>>(path: P, format: Format, rows: &[R]) -> Result<(), Error> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let is_new = file.metadata()?.len() == 0;
+
+    let mut out = String::new();
+    match format {
+        Format::Csv => {
+            if is_new {
+                out.push_str(&R::COLUMNS.join(","));
+                out.push('\n');
+            }
+            for row in rows {
+                let cells: Vec<String> = to_values(row)?.iter().map(csv_cell).collect();
+                out.push_str(&cells.join(","));
+                out.push('\n');
+            }
+        }
+        Format::Columnar => {
+            let mut data = vec![Vec::with_capacity(rows.len()); R::COLUMNS.len()];
+            for row in rows {
+                for (column, value) in data.iter_mut().zip(to_values(row)?) {
+                    column.push(value);
+                }
+            }
+            let group = RowGroup {
+                schema: R::NAME.to_owned(),
+                columns: R::COLUMNS.iter().map(|c| c.to_string()).collect(),
+                rows: rows.len(),
+                data
+            };
+            out.push_str(&serde_json::to_string(&group)?);
+            out.push('\n');
+        }
+    }
+
+    file.write_all(out.as_bytes())?;
+    Ok(())
+}
+
+/// Reads every row stored in the file at `path`.
+pub fn read<R: Row, P: AsRef<Path>>(path: P, format: Format) -> Result<Vec<R>, Error> {
+    let mut rows = Vec::new();
+    let mut lines = BufReader::new(File::open(path)?).lines();
+
+    if format == Format::Csv {
+        match lines.next() {
+            Some(header) => {
+                let header = header?;
+                if header.split(',').ne(R::COLUMNS.iter().cloned()) {
+                    return Err(invalid_data(format!("expected header {:?}, found {:?}",
+                                                    R::COLUMNS.join(","), header)));
+                }
+            }
+            None => return Ok(rows)
+        }
+    }
+
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match format {
+            Format::Csv => rows.push(from_values(parse_csv_line(&line)?)?),
+            Format::Columnar => {
+                let group = parse_row_group::<R>(&line)?;
+                let mut columns: Vec<_> = group.data.into_iter().map(|c| c.into_iter()).collect();
+                for _ in 0..group.rows {
+                    let values = columns.iter_mut().map(|c| c.next().unwrap_or(Value::Null)).collect();
+                    rows.push(from_values(values)?);
+                }
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// The key of the last row in the file at `path`, or `None` if the file
+/// doesn't exist or holds no rows.
+pub fn last_key<R: Row, P: AsRef<Path>>(path: P, format: Format) -> Result<Option<u64>, Error> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into())
+    };
+
+    let mut last = None;
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let is_header = format == Format::Csv && i == 0;
+        if !is_header && !line.trim().is_empty() {
+            last = Some(line);
+        }
+    }
+    let last = match last {
+        Some(last) => last,
+        None => return Ok(None)
+    };
+
+    let key = match format {
+        Format::Csv => parse_csv_line(&last)?.into_iter().nth(key_index::<R>()),
+        Format::Columnar => {
+            parse_row_group::<R>(&last)?.data.into_iter().nth(key_index::<R>()).and_then(|c| c.into_iter().last())
+        }
+    };
+    match key.as_ref().and_then(Value::as_u64) {
+        Some(key) => Ok(Some(key)),
+        None => Err(invalid_data(format!("last {} row has no {} key", R::NAME, R::KEY)))
+    }
+}
+
+/// What a download added to a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Download {
+    /// The key of the last row that was already stored, if resuming.
+    pub resumed_from: Option<u64>,
+    pub written: usize,
+    /// For candles, the intervals without trades, as in `CandleRange`.
+    pub gaps: Vec<(u64, u64)>
+}
+
+/// Downloads the candles for `[start, end)` into the file at `path`,
+/// continuing after the last candle already stored there.
+///
+/// Candles are appended as each window of 300 arrives, so an interrupted
+/// download loses at most one window.
+pub fn download_candles<P: AsRef<Path>>(client: &mut PublicClient,
+                                        product: &str,
+                                        start: DateTime<Utc>,
+                                        end: DateTime<Utc>,
+                                        granularity: Granularity,
+                                        path: P,
+                                        format: Format)
+    -> Result<Download, Error>
+{
+    let path = path.as_ref();
+    let width = granularity.seconds();
+    let resumed_from = last_key::<Candle, _>(path, format)?;
+
+    let mut from = start.timestamp().max(0) as u64;
+    if let Some(last) = resumed_from {
+        from = from.max(last + width);
+    }
+    let end = end.timestamp().max(0) as u64;
+
+    let mut download = Download { resumed_from, written: 0, gaps: Vec::new() };
+    while from < end {
+        if download.written > 0 || !download.gaps.is_empty() {
+            thread::sleep(RATE_LIMIT_INTERVAL);
+        }
+        let to = end.min(from - from % width + CANDLES_PER_CHUNK * width);
+        let range = client.get_historic_rates_range(product,
+                                                    Utc.timestamp_opt(from as i64, 0).unwrap(),
+                                                    Utc.timestamp_opt(to as i64, 0).unwrap(),
+                                                    granularity)?;
+        append(path, format, &range.candles)?;
+        download.written += range.candles.len();
+
+        for gap in range.gaps {
+            match download.gaps.last_mut() {
+                Some(last) if last.1 == gap.0 => last.1 = gap.1,
+                _ => download.gaps.push(gap)
+            }
+        }
+        from = to;
+    }
+    Ok(download)
+}
+
+/// Downloads the trades made in `[start, end)` into the file at `path`,
+/// continuing after the last trade already stored there.
+///
+/// The exchange pages trades from newest to oldest. Unless resuming, the
+/// pages are first walked back to the last trade before `start`, keeping
+/// only its id. Trades are then fetched forward from there and each page
+/// is appended as it arrives, so an interrupted download loses at most
+/// one page.
+pub fn download_trades<P: AsRef<Path>>(client: &mut PublicClient,
+                                       product: &str,
+                                       start: DateTime<Utc>,
+                                       end: DateTime<Utc>,
+                                       path: P,
+                                       format: Format)
+    -> Result<Download, Error>
+{
+    let path = path.as_ref();
+    let resumed_from = last_key::<Trade, _>(path, format)?;
+    let mut download = Download { resumed_from, written: 0, gaps: Vec::new() };
+
+    let mut cursor = match resumed_from {
+        Some(last) => last.to_string(),
+        None => match trade_before(client, product, start)? {
+            Some(id) => {
+                thread::sleep(RATE_LIMIT_INTERVAL);
+                id.to_string()
+            }
+            None => return Ok(download)
+        }
+    };
+    loop {
+        let page = client.get_newer_trades_page(product, &cursor)?;
+        let next = page.before().map(|c| c.to_owned());
+
+        let reached_end = page.value.iter().any(|t| t.time >= end);
+        let mut trades: Vec<Trade> = page.value.into_iter().filter(|t| t.time >= start && t.time < end).collect();
+        trades.sort_by_key(|t| t.trade_id);
+        append(path, format, &trades)?;
+        download.written += trades.len();
+
+        match next {
+            Some(next) if !reached_end && next != cursor => cursor = next,
+            _ => return Ok(download)
+        }
+        thread::sleep(RATE_LIMIT_INTERVAL);
+    }
+}
+
+// The id of the newest trade before `start`, or one less than the oldest
+// trade if there's none; `None` if the product has no trades at all.
+fn trade_before(client: &mut PublicClient, product: &str, start: DateTime<Utc>) -> Result<Option<u64>, Error> {
+    let mut oldest = None;
+    let mut cursor: Option<String> = None;
+    loop {
+        if cursor.is_some() {
+            thread::sleep(RATE_LIMIT_INTERVAL);
+        }
+        let page = client.get_trades_page(product, cursor.as_deref())?;
+        if let Some(trade) = page.value.iter().filter(|t| t.time < start).map(|t| t.trade_id).max() {
+            return Ok(Some(trade));
+        }
+        oldest = page.value.iter().map(|t| t.trade_id).min().or(oldest);
+        match page.after() {
+            Some(next) if !page.value.is_empty() => cursor = Some(next.to_owned()),
+            _ => return Ok(oldest.map(|id| id.saturating_sub(1)))
+        }
+    }
+}
@@ -3,38 +3,109 @@ extern crate serde_derive;
 
 extern crate base64;
 extern crate chrono;
-extern crate crypto;
 
 extern crate futures;
+extern crate getrandom;
+extern crate hmac;
+extern crate native_tls;
+extern crate p256;
 
 extern crate curl;
 
 extern crate serde;
+#[macro_use]
 extern crate serde_json;
+extern crate sha2;
 extern crate time;
+extern crate toml;
+extern crate tungstenite;
 extern crate uuid;
+extern crate zeroize;
 
 use std::fmt;
 
+pub mod advanced;
+pub mod book;
+pub mod candles;
+pub mod clock;
+pub mod credentials;
+pub mod feed;
+pub mod fix;
+pub mod history;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod paper;
 pub mod public;
 pub mod private;
+pub mod replay;
+pub mod signer;
+pub mod trading;
+pub mod transport;
+mod response;
 
+pub use credentials::Credentials;
 pub use public::Client as PublicClient;
 pub use private::Client as PrivateClient;
 
 pub use private::NewOrder;
+pub use response::Response;
+pub use trading::{MarketData, Trading};
+pub use transport::Method;
 pub use private::SizeOrFunds::{self, Funds, Size};
 
-#[derive(Debug, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct ApiError {
-    message: String
+    pub message: String,
+    /// The HTTP status of the response the error came from, if it came
+    /// from one. Errors reported in a successful response's body, or raised
+    /// by the crate itself, have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u32>
+}
+
+impl ApiError {
+    // The exchange's own message when `message` holds a JSON error body
+    fn text(&self) -> String {
+        serde_json::from_str::<serde_json::Value>(&self.message).ok()
+            .and_then(|body| body["message"].as_str().map(|m| m.to_owned()))
+            .unwrap_or_else(|| self.message.clone())
+    }
 }
 
 #[derive(Debug)]
 pub enum Error {
     Api(ApiError),
+    Http(curl::Error),
+    WebSocket(Box<tungstenite::Error>),
+    InvalidNumber(String),
+    Io(std::io::Error),
     InvalidSecretKey,
+    MissingCredentials(String),
+    /// The exchange rejected a signed request because its timestamp was
+    /// too far from the server's clock.
+    TimestampSkew(ApiError),
     Json(serde_json::Error),
+    Toml(toml::de::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Api(ref e) => match e.status {
+                Some(status) => write!(f, "{} (HTTP {})", e.text(), status),
+                None => write!(f, "{}", e.text())
+            },
+            Error::Http(ref e) => write!(f, "HTTP error: {}", e),
+            Error::WebSocket(ref e) => write!(f, "websocket error: {}", e),
+            Error::InvalidNumber(ref s) => write!(f, "invalid number {:?}", s),
+            Error::Io(ref e) => write!(f, "{}", e),
+            Error::InvalidSecretKey => write!(f, "the secret key isn't valid base64"),
+            Error::MissingCredentials(ref what) => write!(f, "missing credentials: {}", what),
+            Error::TimestampSkew(ref e) => write!(f, "{} (check the system clock)", e.text()),
+            Error::Json(ref e) => write!(f, "invalid JSON: {}", e),
+            Error::Toml(ref e) => write!(f, "invalid TOML: {}", e)
+        }
+    }
 }
 
 impl std::convert::From<base64::DecodeError> for Error {
@@ -44,12 +115,41 @@ impl std::convert::From<base64::DecodeError> for Error {
     }
 }
 
+impl std::convert::From<curl::Error> for Error {
+    fn from(err: curl::Error) -> Error {
+        Error::Http(err)
+    }
+}
+
+impl std::convert::From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl std::convert::From<tungstenite::Error> for Error {
+    fn from(err: tungstenite::Error) -> Error {
+        Error::WebSocket(Box::new(err))
+    }
+}
+
 impl std::convert::From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Error {
         Error::Json(err)
     }
 }
 
+impl std::convert::From<toml::de::Error> for Error {
+    fn from(err: toml::de::Error) -> Error {
+        Error::Toml(err)
+    }
+}
+
+/// The side of an order or trade.
+///
+/// Unlike most string enums in this crate, `Side` has no catch-all variant:
+/// an order is always either a buy or a sell, so any other value is rejected
+/// with a deserialization error.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Side {
     Buy,
@@ -91,9 +191,8 @@ impl<'de> serde::Deserialize<'de> for Side {
         impl<'a> serde::de::Visitor<'a> for SideVisitor {
             type Value = Side;
 
-            // TODO Implement!
             fn expecting(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-                unimplemented!()
+                formatter.write_str("a side, either \"buy\" or \"sell\"")
             }
 
             fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
@@ -101,7 +200,7 @@ impl<'de> serde::Deserialize<'de> for Side {
                 match &*v.to_lowercase() {
                     "buy" => Ok(Side::Buy),
                     "sell" => Ok(Side::Sell),
-                    _ => Err(E::invalid_value(serde::de::Unexpected::Str("Invalid side"), &self))
+                    _ => Err(E::invalid_value(serde::de::Unexpected::Str(v), &self))
                 }
             }
         }
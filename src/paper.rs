@@ -0,0 +1,782 @@
+//! Simulated trading against live market data.
+//!
+//! `PaperTrader` has the same trading methods as `PrivateClient`, but keeps
+//! its accounts, holds, orders and ledger in memory and fills orders against
+//! the market data it's given instead of sending them to the exchange:
+//!
+//! ```no_run
+//! # use gdax_client::{NewOrder, PublicClient, Side, Size};
+//! # use gdax_client::paper::PaperTrader;
+//! let mut trader = PaperTrader::new().fees(0.0, 0.003);
+//! trader.deposit("USD", 1000.0);
+//!
+//! let book = PublicClient::new().get_top50_orders("BTC-USD").unwrap();
+//! trader.load_book("BTC-USD", &book).unwrap();
+//! let id = trader.post_order(&NewOrder::market(Side::Buy, "BTC-USD", Size(0.01))).unwrap();
+//! println!("{:?}", trader.get_fills(Some(id), None).unwrap());
+//! ```
+//!
+//! Order books come from REST snapshots through `load_book`, or from the
+//! `level2` channel through `apply`, which also takes `match` messages
+//! from the `matches` or `full` channels.
+//!
+//! Orders that cross the book when they're placed take liquidity from it at
+//! each level's price and pay the taker fee. The simulated orders never
+//! change the book itself, so two orders placed one after the other can both
+//! take the same liquidity. Limit orders that rest are filled as makers, at
+//! their own price, once the book moves through them or a trade reaches
+//! their price, up to the size shown or traded there. Depth an order has
+//! taken from a level isn't filled again unless the level first shrinks
+//! below it. Stop orders become
+//! market orders once a trade reaches their stop price. A market order
+//! executes against the book, or at the last trade's price if there's no
+//! book for its side.
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use std::collections::HashMap;
+use std::f64;
+
+use super::ApiError;
+use super::Error;
+use super::NewOrder;
+use super::Side;
+use super::book::{parse_decimal, L2Book};
+use super::feed::{Match, Message};
+use super::private::{Account, Conversion, ConversionDetails, EntryDetails, EntryType, Fill, Hold, HoldType, Ledger,
+                     LedgerEntry, MatchDetails, OpenOrder, Order, OrderId, SizeOrFunds, TransferDetails};
+use super::public::{BookEntry, OrderBook};
+use super::signer::random_uuid;
+
+// Amounts smaller than this are treated as zero
+const EPSILON: f64 = 1e-9;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OrderType {
+    Limit,
+    Market,
+    Stop
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Liquidity {
+    Maker,
+    Taker
+}
+
+#[derive(Clone, Debug)]
+struct PaperOrder {
+    id: OrderId,
+    order_type: OrderType,
+    product_id: String,
+    base: String,
+    quote: String,
+    side: Side,
+    // The limit price, or the stop price
+    price: f64,
+    size: Option<f64>,
+    // For buys, this covers the fees as well as the value bought
+    funds: Option<f64>,
+    filled_size: f64,
+    executed_value: f64,
+    fill_fees: f64,
+    status: &'static str,
+    done_reason: Option<&'static str>,
+    created_at: DateTime<Utc>,
+    done_at: Option<DateTime<Utc>>,
+    // What's on hold in the quote currency for buys, the base for sells
+    held: f64,
+    hold_id: Uuid,
+    // The depth taken from each crossed book level, by price, so that the
+    // same liquidity isn't filled twice
+    taken: Vec<(f64, f64)>
+}
+
+impl PaperOrder {
+    fn taken_at(&self, price: f64) -> f64 {
+        self.taken.iter().find(|l| (l.0 - price).abs() < EPSILON).map_or(0.0, |l| l.1)
+    }
+
+    fn take(&mut self, price: f64, size: f64) {
+        match self.taken.iter_mut().find(|l| (l.0 - price).abs() < EPSILON) {
+            Some(level) => level.1 += size,
+            None => self.taken.push((price, size))
+        }
+    }
+
+    fn remaining_size(&self) -> Option<f64> {
+        self.size.map(|size| size - self.filled_size)
+    }
+
+    fn remaining_funds(&self) -> Option<f64> {
+        self.funds.map(|funds| match self.side {
+            Side::Buy => funds - self.executed_value - self.fill_fees,
+            Side::Sell => funds - self.executed_value
+        })
+    }
+
+    fn hold_currency(&self) -> &str {
+        match self.side {
+            Side::Buy => &self.quote,
+            Side::Sell => &self.base
+        }
+    }
+
+    fn crosses(&self, price: f64) -> bool {
+        match (self.order_type, self.side) {
+            (OrderType::Limit, Side::Buy) => price <= self.price + EPSILON,
+            (OrderType::Limit, Side::Sell) => price >= self.price - EPSILON,
+            _ => true
+        }
+    }
+
+    fn to_order(&self) -> Order {
+        Order {
+            id: self.id,
+            size: self.size.unwrap_or(self.filled_size),
+            price: if self.order_type == OrderType::Limit { self.price } else { 0.0 },
+            done_reason: self.done_reason.map(str::to_owned),
+            status: self.status.to_owned(),
+            settled: self.status == "done",
+            filled_size: self.filled_size,
+            executed_value: self.executed_value,
+            product_id: self.product_id.clone(),
+            fill_fees: self.fill_fees,
+            side: self.side,
+            created_at: self.created_at,
+            done_at: self.done_at
+        }
+    }
+
+    fn to_open_order(&self) -> OpenOrder {
+        OpenOrder {
+            id: self.id,
+            size: self.size.unwrap_or(self.filled_size),
+            price: self.price,
+            product_id: self.product_id.clone(),
+            status: self.status.to_owned(),
+            filled_size: self.filled_size,
+            executed_value: self.executed_value,
+            fill_fees: self.fill_fees,
+            settled: false,
+            side: self.side,
+            created_at: self.created_at
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct PaperAccount {
+    id: Uuid,
+    currency: String,
+    balance: f64,
+    hold: f64,
+    // Oldest first
+    ledger: Ledger
+}
+
+impl PaperAccount {
+    fn to_account(&self) -> Account {
+        Account {
+            id: self.id,
+            balance: self.balance,
+            hold: self.hold,
+            available: self.balance - self.hold,
+            currency: self.currency.clone()
+        }
+    }
+}
+
+fn api_error<T>(message: &str) -> Result<T, Error> {
+    Err(Error::Api(ApiError { message: message.to_owned(), status: None }))
+}
+
+fn new_id() -> Uuid {
+    random_uuid().expect("the OS has randomness")
+}
+
+/// A simulated account, with the trading methods of `PrivateClient`.
+///
+/// Errors the exchange would report, such as insufficient funds or an
+/// unknown order, are returned as `Error::Api` with the exchange's message.
+pub struct PaperTrader {
+    maker_fee: f64,
+    taker_fee: f64,
+    accounts: Vec<PaperAccount>,
+    // In the order they were placed, which gives time priority
+    orders: Vec<PaperOrder>,
+    // Oldest first
+    fills: Vec<Fill>,
+    books: HashMap<String, L2Book>,
+    last_prices: HashMap<String, f64>,
+    next_trade_id: u64,
+    next_ledger_id: u64
+}
+
+impl Default for PaperTrader {
+    fn default() -> PaperTrader {
+        PaperTrader::new()
+    }
+}
+
+impl PaperTrader {
+    /// A trader with no accounts, no market data and no fees.
+    pub fn new() -> PaperTrader {
+        PaperTrader {
+            maker_fee: 0.0,
+            taker_fee: 0.0,
+            accounts: Vec::new(),
+            orders: Vec::new(),
+            fills: Vec::new(),
+            books: HashMap::new(),
+            last_prices: HashMap::new(),
+            next_trade_id: 1,
+            next_ledger_id: 1
+        }
+    }
+
+    /// Sets the fee rates charged on fills, as fractions of their value:
+    /// `0.001` is 0.1%.
+    pub fn fees(mut self, maker: f64, taker: f64) -> PaperTrader {
+        self.maker_fee = maker;
+        self.taker_fee = taker;
+        self
+    }
+
+    /// Credits `amount` of `currency`, opening an account if needed.
+    pub fn deposit(&mut self, currency: &str, amount: f64) {
+        let details = EntryDetails::Transfer(TransferDetails {
+            transfer_id: new_id(),
+            transfer_type: "deposit".to_owned()
+        });
+        self.adjust(currency, amount, EntryType::Transfer, details);
+    }
+
+    /// Replaces the book for `product_id` with a REST snapshot such as
+    /// `PublicClient::get_top50_orders`, and fills the resting orders it
+    /// crosses.
+    pub fn load_book(&mut self, product_id: &str, snapshot: &OrderBook<BookEntry>) -> Result<Vec<Fill>, Error> {
+        let book = L2Book::from_order_book(product_id, snapshot)?;
+        self.books.insert(product_id.to_owned(), book);
+        Ok(self.fill_crossed(product_id))
+    }
+
+    /// Updates the market from a feed message and returns the fills it
+    /// caused. `snapshot` and `l2update` messages maintain the book, `match`
+    /// and `last_match` messages fill resting orders at or through the
+    /// trade's price and trigger stop orders. Other messages are ignored.
+    pub fn apply(&mut self, message: &Message) -> Result<Vec<Fill>, Error> {
+        match *message {
+            Message::Snapshot(ref m) => {
+                let book = L2Book::from_snapshot(m)?;
+                self.books.insert(m.product_id.clone(), book);
+                Ok(self.fill_crossed(&m.product_id))
+            }
+            Message::L2Update(ref m) => match self.books.get_mut(&m.product_id) {
+                Some(book) => {
+                    book.apply_update(m)?;
+                    Ok(self.fill_crossed(&m.product_id))
+                }
+                // Updates mean nothing until there's a snapshot to apply them to
+                None => Ok(Vec::new())
+            },
+            Message::Match(ref m) | Message::LastMatch(ref m) => self.fill_traded(m),
+            _ => Ok(Vec::new())
+        }
+    }
+
+    /// The book for `product_id`, if one has been loaded.
+    pub fn book(&self, product_id: &str) -> Option<&L2Book> {
+        self.books.get(product_id)
+    }
+
+    pub fn get_accounts(&mut self) -> Result<Vec<Account>, Error> {
+        Ok(self.accounts.iter().map(|a| a.to_account()).collect())
+    }
+
+    pub fn get_account(&mut self, id: Uuid) -> Result<Account, Error> {
+        Ok(self.find_account(id)?.to_account())
+    }
+
+    /// The account's ledger, newest first.
+    pub fn get_account_history(&mut self, id: Uuid) -> Result<Ledger, Error> {
+        Ok(self.find_account(id)?.ledger.iter().rev().cloned().collect())
+    }
+
+    pub fn get_account_holds(&mut self, id: Uuid) -> Result<Vec<Hold>, Error> {
+        let currency = self.find_account(id)?.currency.clone();
+        Ok(self.orders.iter()
+                      .filter(|o| o.held > EPSILON && o.hold_currency() == currency)
+                      .map(|o| Hold {
+                          id: o.hold_id,
+                          account_id: Some(id),
+                          created_at: o.created_at,
+                          updated_at: None,
+                          amount: o.held,
+                          hold_type: HoldType::Order,
+                          ref_id: o.id
+                      })
+                      .collect())
+    }
+
+    /// Places `order`, holding what it could spend and executing it right
+    /// away against the book as far as it crosses. Products are named
+    /// `BASE-QUOTE`; accounts for both currencies are opened as needed.
+    pub fn post_order(&mut self, order: &NewOrder) -> Result<OrderId, Error> {
+        let (order_type, side, product_id, price, size_or_funds) = match *order {
+            NewOrder::Limit { side, ref product_id, price, size } =>
+                (OrderType::Limit, side, product_id, price, SizeOrFunds::Size(size)),
+            NewOrder::Market { side, ref product_id, size_or_funds } =>
+                (OrderType::Market, side, product_id, 0.0, size_or_funds),
+            NewOrder::Stop { side, ref product_id, price, size_or_funds } =>
+                (OrderType::Stop, side, product_id, price, size_or_funds)
+        };
+        let (base, quote) = match product_id.find('-') {
+            Some(i) if i > 0 && i + 1 < product_id.len() => (&product_id[..i], &product_id[i + 1..]),
+            _ => return api_error("Product not found")
+        };
+        let (size, funds) = match size_or_funds {
+            SizeOrFunds::Size(size) => (Some(size), None),
+            SizeOrFunds::Funds(funds) => (None, Some(funds))
+        };
+
+        if order_type != OrderType::Market && (price.is_nan() || price <= 0.0) {
+            return api_error("Invalid price");
+        }
+        if !size.or(funds).is_some_and(|amount| amount > 0.0) {
+            return api_error("Invalid size");
+        }
+        if order_type == OrderType::Market && !self.has_liquidity(product_id, side) {
+            return api_error(&format!("No market data for {}", product_id));
+        }
+
+        // Market buys sized in the base currency and market sells sized in
+        // the quote currency hold nothing, and trade what the account has
+        let held = match (side, order_type, size, funds) {
+            (Side::Buy, OrderType::Market, Some(_), _) => 0.0,
+            (Side::Buy, _, Some(size), _) => price * size * (1.0 + self.taker_fee),
+            (Side::Buy, _, None, Some(funds)) => funds,
+            (Side::Sell, _, Some(size), _) => size,
+            (Side::Sell, OrderType::Stop, None, Some(funds)) => funds / price,
+            _ => 0.0
+        };
+        let account = self.account_index(match side {
+            Side::Buy => quote,
+            Side::Sell => base
+        });
+        {
+            let account = &mut self.accounts[account];
+            if account.balance - account.hold < held - EPSILON {
+                return api_error("Insufficient funds");
+            }
+            account.hold += held;
+        }
+        self.account_index(match side {
+            Side::Buy => base,
+            Side::Sell => quote
+        });
+
+        let id = new_id();
+        self.orders.push(PaperOrder {
+            id,
+            order_type,
+            product_id: product_id.clone(),
+            base: base.to_owned(),
+            quote: quote.to_owned(),
+            side,
+            price,
+            size,
+            funds,
+            filled_size: 0.0,
+            executed_value: 0.0,
+            fill_fees: 0.0,
+            status: if order_type == OrderType::Stop { "active" } else { "open" },
+            done_reason: None,
+            created_at: Utc::now(),
+            done_at: None,
+            held,
+            hold_id: new_id(),
+            taken: Vec::new()
+        });
+        if order_type != OrderType::Stop {
+            let idx = self.orders.len() - 1;
+            self.execute(idx);
+        }
+        Ok(id)
+    }
+
+    /// Converts `amount` of `from` into the same amount of `to`, as the
+    /// exchange does between USD and USDC.
+    pub fn convert(&mut self, from: &str, to: &str, amount: f64) -> Result<Conversion, Error> {
+        if amount.is_nan() || amount <= 0.0 {
+            return api_error("Invalid amount");
+        }
+        let (from_idx, to_idx) = (self.account_index(from), self.account_index(to));
+        let from_account = &self.accounts[from_idx];
+        if from_account.balance - from_account.hold < amount - EPSILON {
+            return api_error("Insufficient funds");
+        }
+
+        let id = new_id();
+        let details = EntryDetails::Conversion(ConversionDetails { conversion_id: id });
+        self.adjust(from, -amount, EntryType::Conversion, details.clone());
+        self.adjust(to, amount, EntryType::Conversion, details);
+        Ok(Conversion {
+            id,
+            amount,
+            from_account_id: self.accounts[from_idx].id,
+            to_account_id: self.accounts[to_idx].id,
+            from: from.to_owned(),
+            to: to.to_owned()
+        })
+    }
+
+    pub fn cancel_order(&mut self, order_id: OrderId) -> Result<OrderId, Error> {
+        match self.orders.iter().position(|o| o.id == order_id) {
+            Some(idx) if self.orders[idx].status == "done" => api_error("Order already done"),
+            Some(idx) => {
+                self.finish(idx, "canceled");
+                Ok(order_id)
+            }
+            None => api_error("NotFound")
+        }
+    }
+
+    pub fn cancel_all_orders(&mut self, product_id: Option<&str>) -> Result<Vec<OrderId>, Error> {
+        let mut canceled = Vec::new();
+        for idx in 0..self.orders.len() {
+            let order = &self.orders[idx];
+            if order.status != "done" && product_id.is_none_or(|id| id == order.product_id) {
+                canceled.push(order.id);
+                self.finish(idx, "canceled");
+            }
+        }
+        Ok(canceled)
+    }
+
+    /// Orders that aren't done yet with any of the given statuses, newest
+    /// first. Simulated orders are never pending.
+    pub fn get_orders_with_status(&mut self,
+                                  open: bool,
+                                  pending: bool,
+                                  active: bool)
+        -> Result<Vec<OpenOrder>, Error>
+    {
+        let wanted = |status: &str| match status {
+            "open" => open,
+            "pending" => pending,
+            "active" => active,
+            _ => false
+        };
+        Ok(self.orders.iter().rev().filter(|o| wanted(o.status)).map(|o| o.to_open_order()).collect())
+    }
+
+    pub fn get_orders(&mut self) -> Result<Vec<OpenOrder>, Error> {
+        self.get_orders_with_status(true, true, true)
+    }
+
+    pub fn get_order(&mut self, order_id: OrderId) -> Result<Order, Error> {
+        match self.orders.iter().find(|o| o.id == order_id) {
+            Some(order) => Ok(order.to_order()),
+            None => api_error("NotFound")
+        }
+    }
+
+    /// Fills of the order `order_id`, or else of every order of
+    /// `product_id`, newest first. One of the two is required.
+    pub fn get_fills(&mut self, order_id: Option<OrderId>, product_id: Option<&str>) -> Result<Vec<Fill>, Error> {
+        let matches = |fill: &&Fill| match (order_id, product_id) {
+            (Some(order_id), _) => fill.order_id == order_id,
+            (None, Some(product_id)) => fill.product_id == product_id,
+            (None, None) => false
+        };
+        if order_id.is_none() && product_id.is_none() {
+            return api_error("order_id or product_id is required");
+        }
+        Ok(self.fills.iter().rev().filter(matches).cloned().collect())
+    }
+
+    fn find_account(&self, id: Uuid) -> Result<&PaperAccount, Error> {
+        match self.accounts.iter().find(|a| a.id == id) {
+            Some(account) => Ok(account),
+            None => api_error("NotFound")
+        }
+    }
+
+    fn account_index(&mut self, currency: &str) -> usize {
+        if let Some(idx) = self.accounts.iter().position(|a| a.currency == currency) {
+            return idx;
+        }
+        self.accounts.push(PaperAccount {
+            id: new_id(),
+            currency: currency.to_owned(),
+            balance: 0.0,
+            hold: 0.0,
+            ledger: Vec::new()
+        });
+        self.accounts.len() - 1
+    }
+
+    fn adjust(&mut self, currency: &str, amount: f64, entry_type: EntryType, details: EntryDetails) {
+        let idx = self.account_index(currency);
+        let id = self.next_ledger_id;
+        self.next_ledger_id += 1;
+
+        let account = &mut self.accounts[idx];
+        account.balance += amount;
+        account.ledger.push(LedgerEntry {
+            id,
+            created_at: Utc::now(),
+            amount,
+            balance: account.balance,
+            entry_type,
+            details: Some(details)
+        });
+    }
+
+    fn release(&mut self, idx: usize, amount: f64) {
+        let amount = amount.min(self.orders[idx].held);
+        if amount <= 0.0 {
+            return;
+        }
+        self.orders[idx].held -= amount;
+        let currency = self.orders[idx].hold_currency().to_owned();
+        let account = self.account_index(&currency);
+        self.accounts[account].hold -= amount;
+    }
+
+    fn has_liquidity(&self, product_id: &str, side: Side) -> bool {
+        self.last_prices.contains_key(product_id) || self.opposite_levels(product_id, side).is_some()
+    }
+
+    // The levels an order on `side` would take from, best first
+    fn opposite_levels(&self, product_id: &str, side: Side) -> Option<Vec<(f64, f64)>> {
+        let book = self.books.get(product_id)?;
+        let levels = match side {
+            Side::Buy => book.asks(),
+            Side::Sell => book.bids()
+        };
+        if levels.is_empty() {
+            None
+        } else {
+            Some(levels.into_iter().map(|level| (level.price, level.size)).collect())
+        }
+    }
+
+    // How much of the order at `idx` can fill at `price`, given `depth`
+    // there and what the account can pay with
+    fn fillable(&self, idx: usize, price: f64, depth: f64, fee: f64) -> f64 {
+        let order = &self.orders[idx];
+        let mut size = depth;
+        if let Some(remaining) = order.remaining_size() {
+            size = size.min(remaining);
+        }
+        if let Some(remaining) = order.remaining_funds() {
+            size = size.min(match order.side {
+                Side::Buy => remaining / (price * (1.0 + fee)),
+                Side::Sell => remaining / price
+            });
+        }
+        let spendable = self.accounts.iter()
+                                     .find(|a| a.currency == order.hold_currency())
+                                     .map_or(0.0, |a| a.balance - a.hold + order.held);
+        size.min(match order.side {
+            Side::Buy => spendable / (price * (1.0 + fee)),
+            Side::Sell => spendable
+        })
+    }
+
+    // Takes liquidity for the order at `idx` until it's filled or nothing
+    // crosses. Orders other than limit orders don't rest.
+    fn execute(&mut self, idx: usize) {
+        let (product_id, side) = (self.orders[idx].product_id.clone(), self.orders[idx].side);
+        let levels = match self.opposite_levels(&product_id, side) {
+            Some(levels) => levels,
+            None if self.orders[idx].order_type == OrderType::Limit => Vec::new(),
+            None => self.last_prices.get(&product_id).map(|&price| vec![(price, f64::INFINITY)]).unwrap_or_default()
+        };
+
+        for (price, depth) in levels {
+            if !self.orders[idx].crosses(price) {
+                break;
+            }
+            let size = self.fillable(idx, price, depth, self.taker_fee);
+            if size <= EPSILON {
+                break;
+            }
+            self.fill(idx, price, size, Liquidity::Taker);
+            self.orders[idx].take(price, size);
+        }
+
+        let order = &self.orders[idx];
+        let filled = order.remaining_size().or_else(|| order.remaining_funds()).unwrap_or(0.0) <= EPSILON;
+        if filled || order.order_type != OrderType::Limit {
+            self.finish(idx, "filled");
+        }
+    }
+
+    // Fills resting limit orders of `product_id` that the book has moved
+    // through, with the depth at or beyond their price that they haven't
+    // already taken
+    fn fill_crossed(&mut self, product_id: &str) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        for idx in 0..self.orders.len() {
+            let order = &self.orders[idx];
+            if order.status != "open" || order.order_type != OrderType::Limit || order.product_id != product_id {
+                continue;
+            }
+            let crossed: Vec<(f64, f64)> = match self.opposite_levels(product_id, order.side) {
+                Some(levels) => levels.into_iter().take_while(|&(price, _)| order.crosses(price)).collect(),
+                None => Vec::new()
+            };
+
+            // A level that shrank below what was taken from it has been
+            // traded or canceled away, and whatever is left there is new
+            let taken: Vec<(f64, f64)> = crossed.iter()
+                                                .map(|&(price, depth)| (price, order.taken_at(price).min(depth)))
+                                                .collect();
+            let available: Vec<(f64, f64)> = crossed.iter()
+                                                    .zip(&taken)
+                                                    .map(|(&(price, depth), &(_, taken))| (price, depth - taken))
+                                                    .collect();
+            self.orders[idx].taken = taken.into_iter().filter(|l| l.1 > EPSILON).collect();
+
+            let price = self.orders[idx].price;
+            let size = self.fillable(idx, price, available.iter().map(|l| l.1).sum(), self.maker_fee);
+            if size > EPSILON {
+                fills.push(self.fill(idx, price, size, Liquidity::Maker));
+                let mut left = size;
+                for (level, depth) in available {
+                    let take = left.min(depth);
+                    if take > EPSILON {
+                        self.orders[idx].take(level, take);
+                        left -= take;
+                    }
+                }
+                self.finish_if_filled(idx);
+            }
+        }
+        fills
+    }
+
+    // Fills resting limit orders reached by a trade, best price first and
+    // then in the order they were placed, up to the trade's size. Then
+    // triggers the stop orders the trade reached.
+    fn fill_traded(&mut self, trade: &Match) -> Result<Vec<Fill>, Error> {
+        let price = parse_decimal(&trade.price)?;
+        let mut remaining = parse_decimal(&trade.size)?;
+        self.last_prices.insert(trade.product_id.clone(), price);
+
+        let mut resting: Vec<usize> = (0..self.orders.len()).filter(|&idx| {
+            let order = &self.orders[idx];
+            order.status == "open" && order.order_type == OrderType::Limit && order.product_id == trade.product_id
+                && order.crosses(price)
+        }).collect();
+        resting.sort_by(|&a, &b| {
+            let (a_price, b_price) = (self.orders[a].price, self.orders[b].price);
+            let by_price = match self.orders[a].side {
+                Side::Buy => b_price.total_cmp(&a_price),
+                Side::Sell => a_price.total_cmp(&b_price)
+            };
+            by_price.then(a.cmp(&b))
+        });
+
+        let mut fills = Vec::new();
+        for idx in resting {
+            let limit = self.orders[idx].price;
+            let size = self.fillable(idx, limit, remaining, self.maker_fee);
+            if size > EPSILON {
+                remaining -= size;
+                fills.push(self.fill(idx, limit, size, Liquidity::Maker));
+                self.finish_if_filled(idx);
+            }
+        }
+
+        while let Some(idx) = self.orders.iter().position(|order| {
+            order.status == "active" && order.product_id == trade.product_id && match order.side {
+                Side::Buy => price >= order.price - EPSILON,
+                Side::Sell => price <= order.price + EPSILON
+            }
+        }) {
+            let first = self.fills.len();
+            self.orders[idx].status = "open";
+            self.execute(idx);
+            fills.extend_from_slice(&self.fills[first..]);
+        }
+        Ok(fills)
+    }
+
+    // Records a fill of the order at `idx` and moves the money for it
+    fn fill(&mut self, idx: usize, price: f64, size: f64, liquidity: Liquidity) -> Fill {
+        let trade_id = self.next_trade_id;
+        self.next_trade_id += 1;
+        let value = price * size;
+        let fee = value * match liquidity {
+            Liquidity::Maker => self.maker_fee,
+            Liquidity::Taker => self.taker_fee
+        };
+
+        let order = {
+            let order = &mut self.orders[idx];
+            order.filled_size += size;
+            order.executed_value += value;
+            order.fill_fees += fee;
+            order.clone()
+        };
+        let details = MatchDetails { order_id: order.id, trade_id, product_id: order.product_id.clone() };
+        match order.side {
+            Side::Buy => {
+                let released = match order.order_type {
+                    OrderType::Market | OrderType::Stop if order.funds.is_some() => value + fee,
+                    OrderType::Market => 0.0,
+                    _ => order.price * size * (1.0 + self.taker_fee)
+                };
+                self.release(idx, released);
+                self.adjust(&order.quote, -value, EntryType::Match, EntryDetails::Match(details.clone()));
+                self.adjust(&order.base, size, EntryType::Match, EntryDetails::Match(details.clone()));
+            }
+            Side::Sell => {
+                self.release(idx, size);
+                self.adjust(&order.base, -size, EntryType::Match, EntryDetails::Match(details.clone()));
+                self.adjust(&order.quote, value, EntryType::Match, EntryDetails::Match(details.clone()));
+            }
+        }
+        if fee > 0.0 {
+            self.adjust(&order.quote, -fee, EntryType::Fee, EntryDetails::Fee(details));
+        }
+
+        let fill = Fill {
+            trade_id,
+            product_id: order.product_id,
+            price,
+            size,
+            order_id: order.id,
+            created_at: Utc::now(),
+            liquidity: match liquidity {
+                Liquidity::Maker => "M",
+                Liquidity::Taker => "T"
+            }.to_owned(),
+            fee,
+            settled: true,
+            side: order.side
+        };
+        self.fills.push(fill.clone());
+        fill
+    }
+
+    fn finish_if_filled(&mut self, idx: usize) {
+        if self.orders[idx].remaining_size().unwrap_or(0.0) <= EPSILON {
+            self.finish(idx, "filled");
+        }
+    }
+
+    fn finish(&mut self, idx: usize, reason: &'static str) {
+        let held = self.orders[idx].held;
+        self.release(idx, held);
+        let order = &mut self.orders[idx];
+        order.status = "done";
+        order.done_reason = Some(reason);
+        order.done_at = Some(Utc::now());
+    }
+}
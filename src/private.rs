@@ -1,36 +1,45 @@
-use base64;
 use chrono::{DateTime, Utc};
-use crypto::hmac::Hmac;
-use crypto::mac::Mac;
-use crypto::sha2::Sha256;
-
-use curl::easy::{Easy, List};
-
-use futures::{Async, Poll, Stream};
 
 use serde::{self, Deserialize, Serialize};
-use serde_json::{de, ser};
-use std::ops::Deref;
-use time::get_time;
+use serde_json::{ser, Value};
+use std::ops::{Deref, DerefMut};
+use std::time::Duration;
 use uuid::Uuid;
 
 use std::fmt;
+use std::mem;
 
-use super::Error;
 use super::ApiError;
+use super::Credentials;
+use super::Error;
+use super::Response;
 use super::Side;
+use super::clock::{local_now, ClockSync};
+use super::feed::Auth;
+use super::public::Time;
+use super::signer::{HmacSigner, RequestSigner, SigningRequest};
+use super::transport::{CurlTransport, Method, Request, SharedTransport, Transport};
 
-const PRIVATE_API_URL: &'static str = "https://api.gdax.com";
+const PRIVATE_API_URL: &str = "https://api.gdax.com";
 
 pub struct Client {
     public_client: super::public::Client,
-    curl: Easy,
-    key: String,
-    secret: String,
-    passphrase: String
+    transport: Box<dyn Transport + Send>,
+    signer: Box<dyn RequestSigner>,
+    clock: Option<ClockSync>,
+    url: String
 }
 
-#[derive(Deserialize, Debug)]
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Client")
+         .field("signer", &self.signer)
+         .field("time_offset", &self.time_offset())
+         .finish()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Account {
     pub id: Uuid,
     pub balance: f64,
@@ -41,31 +50,134 @@ pub struct Account {
 
 pub type Ledger = Vec<LedgerEntry>;
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Clone, PartialEq, Debug)]
 pub struct LedgerEntry {
     pub id: u64,
     pub created_at: DateTime<Utc>,
     pub amount: f64,
     pub balance: f64,
-    // #[serde(rename = "type")]
+    #[serde(rename = "type")]
     pub entry_type: EntryType,
     pub details: Option<EntryDetails>
 }
 
-#[derive(Deserialize, Debug)]
-pub struct EntryDetails {
-    pub order_id: Option<Uuid>,
-    pub trade_id: Option<u64>,
-    pub product_id: Option<String>,
-    pub transfer_id: Option<Uuid>,
-    pub transfer_type: Option<String>
+// We manually implement Deserialize for LedgerEntry here
+// because the shape of `details` depends on the entry type
+impl<'de> serde::Deserialize<'de> for LedgerEntry {
+    fn deserialize<D>(deserializer: D) -> Result<LedgerEntry, D::Error>
+        where D: serde::Deserializer<'de> {
+
+        #[derive(Deserialize)]
+        struct RawLedgerEntry {
+            id: u64,
+            created_at: DateTime<Utc>,
+            amount: f64,
+            balance: f64,
+            #[serde(rename = "type")]
+            entry_type: EntryType,
+            #[serde(default)]
+            details: Value
+        }
+
+        let raw = RawLedgerEntry::deserialize(deserializer)?;
+        let details = EntryDetails::from_value(&raw.entry_type, raw.details);
+
+        Ok(LedgerEntry {
+            id: raw.id,
+            created_at: raw.created_at,
+            amount: raw.amount,
+            balance: raw.balance,
+            entry_type: raw.entry_type,
+            details
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct MatchDetails {
+    pub order_id: Uuid,
+    pub trade_id: u64,
+    pub product_id: String
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct TransferDetails {
+    pub transfer_id: Uuid,
+    pub transfer_type: String
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ConversionDetails {
+    pub conversion_id: Uuid
+}
+
+/// Ledger entry details, keyed by the entry's type.
+///
+/// Details that don't have the shape expected for their entry type, as well
+/// as details of entry types this crate doesn't know about, are kept as
+/// `Unknown` so that a single odd record never fails a whole ledger page.
+#[derive(Serialize, Clone, PartialEq, Debug)]
+#[serde(untagged)]
+pub enum EntryDetails {
+    Match(MatchDetails),
+    Fee(MatchDetails),
+    Rebate(MatchDetails),
+    Transfer(TransferDetails),
+    Conversion(ConversionDetails),
+    Unknown(Value)
+}
+
+impl EntryDetails {
+    fn from_value(entry_type: &EntryType, details: Value) -> Option<EntryDetails> {
+        if details.is_null() {
+            return None;
+        }
+
+        // Decoding from a reference leaves `details` to fall back on
+        let typed = match *entry_type {
+            EntryType::Match => MatchDetails::deserialize(&details).map(EntryDetails::Match),
+            EntryType::Fee => MatchDetails::deserialize(&details).map(EntryDetails::Fee),
+            EntryType::Rebate => MatchDetails::deserialize(&details).map(EntryDetails::Rebate),
+            EntryType::Transfer => TransferDetails::deserialize(&details).map(EntryDetails::Transfer),
+            EntryType::Conversion => ConversionDetails::deserialize(&details).map(EntryDetails::Conversion),
+            EntryType::Unknown(_) => return Some(EntryDetails::Unknown(details))
+        };
+
+        Some(typed.unwrap_or(EntryDetails::Unknown(details)))
+    }
+}
+
+/// The type of a ledger entry. Types this crate doesn't recognise yet are
+/// kept verbatim in `Unknown`.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum EntryType {
+    Conversion,
     Fee,
     Match,
-    Transfer
+    Rebate,
+    Transfer,
+    Unknown(String)
+}
+
+impl EntryType {
+    pub fn as_str(&self) -> &str {
+        match *self {
+            EntryType::Conversion => "conversion",
+            EntryType::Fee => "fee",
+            EntryType::Match => "match",
+            EntryType::Rebate => "rebate",
+            EntryType::Transfer => "transfer",
+            EntryType::Unknown(ref t) => t
+        }
+    }
+}
+
+impl Serialize for EntryType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        serializer.serialize_str(self.as_str())
+    }
 }
 
 // We manually implement Deserialize for EntryType here
@@ -80,16 +192,18 @@ impl<'de> serde::Deserialize<'de> for EntryType {
             type Value = EntryType;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-                unimplemented!()
+                formatter.write_str("a ledger entry type string")
             }
 
             fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
                 where E: serde::de::Error {
                 match &*v.to_lowercase() {
+                    "conversion" => Ok(EntryType::Conversion),
                     "fee" => Ok(EntryType::Fee),
                     "match" => Ok(EntryType::Match),
+                    "rebate" => Ok(EntryType::Rebate),
                     "transfer" => Ok(EntryType::Transfer),
-                    _ => Err(E::invalid_value(serde::de::Unexpected::Str("Invalid entry type"), &self))
+                    _ => Ok(EntryType::Unknown(v.to_owned()))
                 }
             }
         }
@@ -97,23 +211,44 @@ impl<'de> serde::Deserialize<'de> for EntryType {
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Hold {
     pub id: Uuid,
     pub account_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: Option<DateTime<Utc>>,
     pub amount: f64,
-    // #[serde(rename = "type")]
+    #[serde(rename = "type")]
     pub hold_type: HoldType,
-    // #[serde(rename = "ref")]
+    #[serde(rename = "ref")]
     pub ref_id: Uuid
 }
 
-#[derive(Debug)]
+/// The type of a hold. Types this crate doesn't recognise yet are kept
+/// verbatim in `Unknown`.
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum HoldType {
     Order,
-    Transfer
+    Transfer,
+    Unknown(String)
+}
+
+impl HoldType {
+    pub fn as_str(&self) -> &str {
+        match *self {
+            HoldType::Order => "order",
+            HoldType::Transfer => "transfer",
+            HoldType::Unknown(ref t) => t
+        }
+    }
+}
+
+impl Serialize for HoldType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        serializer.serialize_str(self.as_str())
+    }
 }
 
 // We manually implement Deserialize for HoldType here
@@ -128,7 +263,7 @@ impl<'de> serde::Deserialize<'de> for HoldType {
             type Value = HoldType;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-                unimplemented!()
+                formatter.write_str("a hold type string")
             }
 
             fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
@@ -136,7 +271,7 @@ impl<'de> serde::Deserialize<'de> for HoldType {
                 match &*v.to_lowercase() {
                     "order" => Ok(HoldType::Order),
                     "transfer" => Ok(HoldType::Transfer),
-                    _ => Err(E::invalid_value(serde::de::Unexpected::Str("Invalid hold type"), &self))
+                    _ => Ok(HoldType::Unknown(v.to_owned()))
                 }
             }
         }
@@ -146,13 +281,13 @@ impl<'de> serde::Deserialize<'de> for HoldType {
 
 pub type OrderId = Uuid;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum SizeOrFunds {
     Size(f64),
     Funds(f64)
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum NewOrder {
     Limit {
         side: Side,
@@ -176,27 +311,27 @@ pub enum NewOrder {
 impl NewOrder {
     pub fn limit(side: Side, product_id: &str, size: f64, price: f64) -> NewOrder {
         NewOrder::Limit {
-            side: side,
+            side,
             product_id: product_id.to_owned(),
-            price: price,
-            size: size
+            price,
+            size
         }
     }
 
     pub fn market(side: Side, product_id: &str, size_or_funds: SizeOrFunds) -> NewOrder {
         NewOrder::Market {
-            side: side,
+            side,
             product_id: product_id.to_owned(),
-            size_or_funds: size_or_funds
+            size_or_funds
         }
     }
 
     pub fn stop(side: Side, product_id: &str, size_or_funds: SizeOrFunds, price: f64) -> NewOrder {
         NewOrder::Stop {
-            side: side,
+            side,
             product_id: product_id.to_owned(),
-            size_or_funds: size_or_funds,
-            price: price
+            size_or_funds,
+            price
         }
     }
 }
@@ -213,7 +348,7 @@ impl Serialize for NewOrder {
                 // and have Serialize auto derived for that
                 #[derive(Serialize)]
                 struct LimitOrder<'a> {
-                    // #[serde(rename = "type")]
+                    #[serde(rename = "type")]
                     t: &'static str,
                     side: Side,
                     product_id: &'a String,
@@ -222,17 +357,17 @@ impl Serialize for NewOrder {
                 }
                 LimitOrder {
                     t: "limit",
-                    side: side,
-                    product_id: &product_id,
-                    price: price,
-                    size: size
+                    side,
+                    product_id,
+                    price,
+                    size
                 }.serialize(serializer)
             }
 
             NewOrder::Market { side, ref product_id, size_or_funds: SizeOrFunds::Size(size) } => {
                 #[derive(Serialize)]
                 struct MarketOrder<'a> {
-                    // #[serde(rename = "type")]
+                    #[serde(rename = "type")]
                     t: &'static str,
                     side: Side,
                     product_id: &'a String,
@@ -240,16 +375,16 @@ impl Serialize for NewOrder {
                 }
                 MarketOrder {
                     t: "market",
-                    side: side,
-                    product_id: &product_id,
-                    size: size
+                    side,
+                    product_id,
+                    size
                 }.serialize(serializer)
             }
 
             NewOrder::Market { side, ref product_id, size_or_funds: SizeOrFunds::Funds(funds) } => {
                 #[derive(Serialize)]
                 struct MarketOrder<'a> {
-                    // #[serde(rename = "type")]
+                    #[serde(rename = "type")]
                     t: &'static str,
                     side: Side,
                     product_id: &'a String,
@@ -257,16 +392,16 @@ impl Serialize for NewOrder {
                 }
                 MarketOrder {
                     t: "market",
-                    side: side,
-                    product_id: &product_id,
-                    funds: funds
+                    side,
+                    product_id,
+                    funds
                 }.serialize(serializer)
             }
 
             NewOrder::Stop { side, ref product_id, price, size_or_funds: SizeOrFunds::Size(size) } => {
                 #[derive(Serialize)]
                 struct StopOrder<'a> {
-                    // #[serde(rename = "type")]
+                    #[serde(rename = "type")]
                     t: &'static str,
                     side: Side,
                     product_id: &'a String,
@@ -275,17 +410,17 @@ impl Serialize for NewOrder {
                 }
                 StopOrder {
                     t: "stop",
-                    side: side,
-                    product_id: &product_id,
-                    price: price,
-                    size: size
+                    side,
+                    product_id,
+                    price,
+                    size
                 }.serialize(serializer)
             }
 
             NewOrder::Stop { side, ref product_id, price, size_or_funds: SizeOrFunds::Funds(funds) } => {
                 #[derive(Serialize)]
                 struct StopOrder<'a> {
-                    // #[serde(rename = "type")]
+                    #[serde(rename = "type")]
                     t: &'static str,
                     side: Side,
                     product_id: &'a String,
@@ -294,17 +429,17 @@ impl Serialize for NewOrder {
                 }
                 StopOrder {
                     t: "stop",
-                    side: side,
-                    product_id: &product_id,
-                    price: price,
-                    funds: funds
+                    side,
+                    product_id,
+                    price,
+                    funds
                 }.serialize(serializer)
             }
         }
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct OpenOrder {
     pub id: OrderId,
     pub size: f64,
@@ -319,7 +454,7 @@ pub struct OpenOrder {
     pub created_at: DateTime<Utc>
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Order {
     pub id: OrderId,
     pub size: f64,
@@ -336,126 +471,241 @@ pub struct Order {
     pub done_at: Option<DateTime<Utc>>
 }
 
+/// A fill of one of the account's orders.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Fill {
+    pub trade_id: u64,
+    pub product_id: String,
+    pub price: f64,
+    pub size: f64,
+    pub order_id: OrderId,
+    pub created_at: DateTime<Utc>,
+    /// `M` if the order was the maker, `T` if it was the taker.
+    pub liquidity: String,
+    pub fee: f64,
+    pub settled: bool,
+    pub side: Side
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Conversion {
+    pub id: Uuid,
+    #[serde(deserialize_with = "decimal")]
+    pub amount: f64,
+    pub from_account_id: Uuid,
+    pub to_account_id: Uuid,
+    pub from: String,
+    pub to: String
+}
+
+// Decimal amounts come as strings, e.g. "10000.00"
+fn decimal<'de, D>(deserializer: D) -> Result<f64, D::Error>
+    where D: serde::Deserializer<'de> {
+
+    struct DecimalVisitor;
+    impl<'a> serde::de::Visitor<'a> for DecimalVisitor {
+        type Value = f64;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+            formatter.write_str("a decimal string")
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where E: serde::de::Error {
+            v.parse().map_err(|_| E::invalid_value(serde::de::Unexpected::Str(v), &self))
+        }
+    }
+    deserializer.deserialize_str(DecimalVisitor)
+}
+
 impl Client {
-    pub fn new(key: &str, secret: &str, passphrase: &str) -> Client {
+    /// Fails with `Error::InvalidSecretKey` if `secret` isn't base64.
+    pub fn new(key: &str, secret: &str, passphrase: &str) -> Result<Client, Error> {
+        Client::with_transport(key, secret, passphrase, CurlTransport::new())
+    }
+
+    pub fn from_credentials(credentials: &Credentials) -> Client {
+        let signer = HmacSigner::from_decoded(credentials.key(),
+                                              credentials.decoded_secret().clone(),
+                                              credentials.passphrase());
+        Client::with_signer(signer, CurlTransport::new())
+    }
+
+    /// Creates a client that sends its requests, public ones included,
+    /// through `transport`, e.g. to record them or to answer them from
+    /// memory.
+    pub fn with_transport<T>(key: &str, secret: &str, passphrase: &str, transport: T) -> Result<Client, Error>
+        where T: Transport + Send + 'static
+    {
+        Ok(Client::with_signer(HmacSigner::new(key, secret, passphrase)?, transport))
+    }
+
+    /// Creates a client that authenticates its requests with `signer`
+    /// instead of an API secret held in memory.
+    pub fn with_signer<S, T>(signer: S, transport: T) -> Client
+        where S: RequestSigner + 'static,
+              T: Transport + Send + 'static
+    {
+        // Public calls made through the client go out on the same transport
+        let transport = SharedTransport::new(transport);
         Client {
-            public_client: super::public::Client::new(),
-            curl: Easy::new(),
-            key: key.to_owned(),
-            secret: secret.to_owned(),
-            passphrase: passphrase.to_owned()
+            public_client: super::public::Client::with_transport(transport.clone()),
+            transport: Box::new(transport),
+            signer: Box::new(signer),
+            clock: None,
+            url: PRIVATE_API_URL.to_owned()
         }
     }
 
-    fn signature(&self, path: &str, body: &str, timestamp: &str, method: &str)
-        -> Result<String, Error> {
+    /// Sends requests, public ones included, to the API at `url` rather
+    /// than the exchange's, e.g. a sandbox or a `mock::MockExchange`.
+    pub fn base_url(mut self, url: &str) -> Client {
+        self.public_client = self.public_client.base_url(url);
+        self.url = url.trim_end_matches('/').to_owned();
+        self
+    }
 
-        let key = base64::decode(&self.secret)?;
-        let what = format!("{}{}{}{}",
-                           timestamp,
-                           method.to_uppercase(),
-                           path,
-                           body);
+    /// Signs requests with the server's time rather than the local clock,
+    /// sampling it now and again every `refresh_interval`. Use this on
+    /// machines whose clock may drift.
+    pub fn enable_time_sync(&mut self, refresh_interval: Duration) -> Result<(), Error> {
+        self.clock = Some(ClockSync::new(refresh_interval));
+        self.sync_time()
+    }
+
+    /// Takes a sample of the server time now. Time sync must be enabled.
+    pub fn sync_time(&mut self) -> Result<(), Error> {
+        let request = Request {
+            method: Method::Get,
+            url: format!("{}/time", self.url),
+            headers: vec![("Accept".to_owned(), "application/json".to_owned())],
+            body: String::new()
+        };
+
+        let sent = local_now();
+        let time: Time = self.transport.execute(&request)?.decode()?.value;
+        let received = local_now();
+
+        if let Some(ref mut clock) = self.clock {
+            clock.record(sent, time.epoch, received);
+        }
+        Ok(())
+    }
 
-        let mut hmac = Hmac::new(Sha256::new(), &key);
-        hmac.input(what.as_bytes());
+    /// Seconds added to the local clock when signing, if time sync is
+    /// enabled and has been sampled.
+    pub fn time_offset(&self) -> Option<f64> {
+        self.clock.as_ref().and_then(|c| c.offset())
+    }
 
-        Ok(base64::encode(hmac.result().code()))
+    fn timestamp(&self) -> String {
+        let now = match self.clock {
+            Some(ref clock) => clock.now(),
+            None => local_now()
+        };
+        (now as i64).to_string()
     }
 
-    fn get_headers(&self, path: &str, body: &str, method: &str) -> Result<List, Error> {
-        let timestamp = get_time().sec.to_string();
-        let signature = self.signature(path, body, &timestamp, method)?;
+    /// The websocket feed authenticates subscriptions with the API key
+    /// headers of a `GET /users/self/verify` request, so this fails for
+    /// signers using any other scheme.
+    pub(crate) fn feed_auth(&self) -> Result<Auth, Error> {
+        let path = "/users/self/verify";
+        let mut headers = self.signer.sign(&SigningRequest {
+            method: Method::Get,
+            url: &format!("{}{}", self.url, path),
+            path,
+            body: "",
+            timestamp: &self.timestamp()
+        })?;
+
+        // Moved rather than copied out, so only the `Auth` holds them
+        let mut header = |name: &str| {
+            headers.iter_mut()
+                   .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                   .map(|(_, v)| mem::take(v))
+                   .ok_or_else(|| Error::MissingCredentials(format!("the signer didn't produce {}", name)))
+        };
+        Ok(Auth {
+            key: header("CB-ACCESS-KEY")?,
+            passphrase: header("CB-ACCESS-PASSPHRASE")?,
+            signature: header("CB-ACCESS-SIGN")?,
+            timestamp: header("CB-ACCESS-TIMESTAMP")?
+        })
+    }
 
-        let mut headers = List::new();
-        headers.append("Accept: application/json").unwrap();
-        headers.append("User-Agent: rust-gdax-client/1.2.0").unwrap();
-        headers.append(["CB-ACCESS-KEY: ", self.key.as_str()].join("").as_str()).unwrap();
-        headers.append(["CB-ACCESS-SIGN: ", signature.as_str()].join("").as_str()).unwrap();
-        headers.append(["CB-ACCESS-PASSPHRASE: ", self.passphrase.as_str()].join("").as_str()).unwrap();
-        headers.append(["CB-ACCESS-TIMESTAMP: ", timestamp.as_str()].join("").as_str()).unwrap();
+    fn get_headers(&self, url: &str, path: &str, body: &str, method: Method) -> Result<Vec<(String, String)>, Error> {
+        let mut headers = vec![("Accept".to_owned(), "application/json".to_owned())];
+        headers.extend(self.signer.sign(&SigningRequest {
+            method,
+            url,
+            path,
+            body,
+            timestamp: &self.timestamp()
+        })?);
+        if !body.is_empty() {
+            headers.push(("Content-Type".to_owned(), "application/json".to_owned()));
+        }
 
         Ok(headers)
     }
 
-    fn get_and_decode<T>(&mut self, path: &str) -> Result<T, Error>
+    /// Issues a signed request to `path` (relative to the API root,
+    /// including any query string) and returns the decoded value together
+    /// with the raw response. This is the escape hatch for endpoints this
+    /// crate doesn't model yet, and for reading response headers such as
+    /// the `CB-BEFORE`/`CB-AFTER` pagination cursors.
+    ///
+    /// Requests rejected for their timestamp fail with
+    /// `Error::TimestampSkew`, and resync the clock if time sync is enabled.
+    pub fn request<T>(&mut self, method: Method, path: &str, body: &str) -> Result<Response<T>, Error>
         where for<'de> T: Deserialize<'de>
     {
-        let headers: List = self.get_headers(path, "", "GET")?;
-        let url = format!("{}{}", PRIVATE_API_URL, path);
-        self.curl.url(url.as_str()).unwrap();
-        self.curl.http_headers(headers).unwrap();
-
-        let mut buf = Vec::new();
-
-        {
-            let mut t = self.curl.transfer();
-            t.write_function(|data| {
-                buf.extend_from_slice(data);
-                Ok(data.len())
-            }).unwrap();
-            t.perform().unwrap();
+        if self.clock.as_ref().is_some_and(|c| c.is_due()) {
+            // A failed refresh leaves the previous estimate in place, unless
+            // there is none yet
+            if let Err(e) = self.sync_time() {
+                if self.time_offset().is_none() {
+                    return Err(e);
+                }
+            }
         }
 
-        if self.curl.response_code().unwrap() != 200 {
-            return Err(Error::Api(ApiError{ message: String::from_utf8(buf).unwrap()}));
-        } else {
-            return Ok(de::from_reader(&mut buf.as_slice())?);
+        let url = format!("{}{}", self.url, path);
+        let request = Request {
+            method,
+            headers: self.get_headers(&url, path, body, method)?,
+            url,
+            body: body.to_owned()
+        };
+
+        match self.transport.execute(&request)?.decode() {
+            Err(Error::Api(ref e)) if is_timestamp_rejection(e) => {
+                if let Some(ref mut clock) = self.clock {
+                    clock.invalidate();
+                }
+                Err(Error::TimestampSkew(e.clone()))
+            }
+            result => result
         }
     }
 
-    fn post_and_decode<T>(&mut self, path: &str, body: &str) -> Result<T, Error>
+    fn get_and_decode<T>(&mut self, path: &str) -> Result<T, Error>
         where for<'de> T: Deserialize<'de>
     {
-        let headers: List = self.get_headers(path, body, "POST")?;
-        let url = format!("{}{}", PRIVATE_API_URL, path);
-        self.curl.url(url.as_str()).unwrap();
-        self.curl.http_headers(headers).unwrap();
-
-        let mut buf = Vec::new();
-
-        {
-            let mut t = self.curl.transfer();
-            t.write_function(|data| {
-                buf.extend_from_slice(data);
-                Ok(data.len())
-            }).unwrap();
-            t.perform().unwrap();
-        }
+        Ok(self.request(Method::Get, path, "")?.value)
+    }
 
-        // TODO success codes can be more than just 200
-        if self.curl.response_code().unwrap() != 200 {
-            return Err(Error::Api(ApiError{ message: String::from_utf8(buf).unwrap()}));
-        } else {
-            return Ok(de::from_reader(&mut buf.as_slice())?);
-        }
+    fn post_and_decode<T>(&mut self, path: &str, body: &str) -> Result<T, Error>
+        where for<'de> T: Deserialize<'de>
+    {
+        Ok(self.request(Method::Post, path, body)?.value)
     }
 
     fn delete_and_decode<T>(&mut self, path: &str) -> Result<T, Error>
         where for<'de> T: Deserialize<'de>
     {
-        let headers: List = self.get_headers(path, "", "DELETE")?;
-        let url = format!("{}{}", PRIVATE_API_URL, path);
-        self.curl.url(url.as_str()).unwrap();
-        self.curl.http_headers(headers).unwrap();
-
-        let mut buf = Vec::new();
-
-        {
-            let mut t = self.curl.transfer();
-            t.write_function(|data| {
-                buf.extend_from_slice(data);
-                Ok(data.len())
-            }).unwrap();
-            t.perform().unwrap();
-        }
-
-        // TODO success codes can be more than just 200
-        if self.curl.response_code().unwrap() != 200 {
-            return Err(Error::Api(ApiError{ message: String::from_utf8(buf).unwrap()}));
-        } else {
-            return Ok(de::from_reader(&mut buf.as_slice())?);
-        }
+        Ok(self.request(Method::Delete, path, "")?.value)
     }
 
     pub fn get_accounts(&mut self) -> Result<Vec<Account>, Error> {
@@ -482,8 +732,25 @@ impl Client {
         Ok(self.post_and_decode::<NewOrderResult>("/orders", &body)?.id)
     }
 
+    pub fn convert(&mut self, from: &str, to: &str, amount: f64) -> Result<Conversion, Error> {
+        #[derive(Serialize)]
+        struct ConversionRequest<'a> {
+            from: &'a str,
+            to: &'a str,
+            amount: f64
+        }
+
+        let body = ser::to_string(&ConversionRequest { from, to, amount })?;
+        self.post_and_decode("/conversions", &body)
+    }
+
     pub fn cancel_order(&mut self, order_id: OrderId) -> Result<OrderId, Error> {
-        Ok(self.delete_and_decode::<Vec<OrderId>>(&format!("/orders/{}", order_id))?[0])
+        // An order that's already done comes back as an empty list
+        self.delete_and_decode::<Vec<OrderId>>(&format!("/orders/{}", order_id))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::Api(ApiError { message: format!("order {} was not canceled", order_id),
+                                                 status: None }))
     }
 
     pub fn cancel_all_orders(&mut self, product_id: Option<&str>) -> Result<Vec<OrderId>, Error> {
@@ -516,6 +783,21 @@ impl Client {
     pub fn get_order(&mut self, order_id: OrderId) -> Result<Order, Error> {
         self.get_and_decode(&format!("/orders/{}", order_id))
     }
+
+    /// Fills of the order `order_id`, or else of every order of
+    /// `product_id`. One of the two is required.
+    pub fn get_fills(&mut self, order_id: Option<OrderId>, product_id: Option<&str>) -> Result<Vec<Fill>, Error> {
+        match (order_id, product_id) {
+            (Some(order_id), _) => self.get_and_decode(&format!("/fills?order_id={}", order_id)),
+            (None, Some(product_id)) => self.get_and_decode(&format!("/fills?product_id={}", product_id)),
+            (None, None) => Err(Error::Api(ApiError { message: "order_id or product_id is required".to_owned(), status: None }))
+        }
+    }
+}
+
+fn is_timestamp_rejection(error: &ApiError) -> bool {
+    let message = error.message.to_lowercase();
+    message.contains("timestamp expired") || message.contains("invalid timestamp")
 }
 
 impl Deref for Client {
@@ -525,3 +807,9 @@ impl Deref for Client {
         &self.public_client
     }
 }
+
+impl DerefMut for Client {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.public_client
+    }
+}
@@ -1,17 +1,26 @@
-use chrono::{DateTime, Utc, SecondsFormat};
+use chrono::{DateTime, TimeZone, Utc, SecondsFormat};
 
-use futures::{Async, Poll, Stream};
-
-use curl::easy::Easy;
-use serde::Deserialize;
-use serde_json::{de, Number};
+use serde::{self, Deserialize, Serialize};
 use uuid::Uuid;
 
+use std::thread;
+use std::time::Duration;
+
 use super::Error;
-use super::ApiError;
+use super::Response;
 use super::Side;
+use super::transport::{CurlTransport, Method, Request, Transport};
+
+const PUBLIC_API_URL: &str = "https://api.gdax.com";
+
+// The most candles the exchange returns for one request
+const MAX_CANDLES: u64 = 300;
 
-const PUBLIC_API_URL: &'static str = "https://api.gdax.com";
+// Public endpoints allow three requests per second
+pub(crate) const RATE_LIMIT_INTERVAL: Duration = Duration::from_millis(334);
+
+// How often a request that hit the rate limit is retried before giving up
+const RATE_LIMIT_RETRIES: u32 = 5;
 
 pub enum Level {
     Best    = 1,
@@ -19,12 +28,51 @@ pub enum Level {
     Full    = 3
 }
 
+/// The candle widths accepted by `get_historic_rates`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Granularity {
+    OneMinute       = 60,
+    FiveMinutes     = 300,
+    FifteenMinutes  = 900,
+    OneHour         = 3600,
+    SixHours        = 21600,
+    OneDay          = 86400
+}
+
+impl Granularity {
+    pub fn seconds(&self) -> u64 {
+        *self as u64
+    }
+
+    pub fn from_seconds(seconds: u64) -> Option<Granularity> {
+        match seconds {
+            60 => Some(Granularity::OneMinute),
+            300 => Some(Granularity::FiveMinutes),
+            900 => Some(Granularity::FifteenMinutes),
+            3600 => Some(Granularity::OneHour),
+            21600 => Some(Granularity::SixHours),
+            86400 => Some(Granularity::OneDay),
+            _ => None
+        }
+    }
+}
+
+/// Candles for a time range, as returned by `get_historic_rates_range`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CandleRange {
+    /// The candles in ascending order of time, without duplicates.
+    pub candles: Vec<Candle>,
+    /// Half-open `[start, end)` intervals, in seconds since the epoch, for
+    /// which the exchange returned no candles because nothing traded.
+    pub gaps: Vec<(u64, u64)>
+}
+
 /** TODO
 Should reinstate the automatic conversion to fp64 that was in original to maintain compatibility.
 Can add a '_raw' api maybe? Or something else to more closely mirror types gdax uses.
 */
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Product {
     pub id: String,
     pub base_currency: String,
@@ -41,28 +89,28 @@ pub struct Product {
     pub cancel_only: bool,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Clone, PartialEq, Debug)]
 pub struct BookEntry {
     pub price: String,
     pub size: String,
     pub num_orders: u64
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Clone, PartialEq, Debug)]
 pub struct FullBookEntry {
     pub price: String,
     pub size: String,
     pub order_id: Uuid
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct OrderBook<T> {
     pub sequence: usize,
     pub bids: Vec<T>,
     pub asks: Vec<T>
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Tick {
     pub trade_id: u64,
     pub price: String,
@@ -73,7 +121,7 @@ pub struct Tick {
     pub time: DateTime<Utc>
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Trade {
     pub time: DateTime<Utc>,
     pub trade_id: u64,
@@ -82,7 +130,7 @@ pub struct Trade {
     pub side: Side,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Clone, PartialEq, Debug)]
 pub struct Candle {
     pub time: u64,
     pub low: f64,
@@ -92,7 +140,34 @@ pub struct Candle {
     pub volume: f64
 }
 
-#[derive(Deserialize, Debug)]
+// The exchange encodes book entries and candles as arrays rather than
+// objects. Derived Deserialize accepts both, but Serialize has to be written
+// by hand to produce the array form.
+impl Serialize for BookEntry {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        (&self.price, &self.size, self.num_orders).serialize(serializer)
+    }
+}
+
+impl Serialize for FullBookEntry {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        (&self.price, &self.size, &self.order_id).serialize(serializer)
+    }
+}
+
+impl Serialize for Candle {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: serde::Serializer
+    {
+        (self.time, self.low, self.high, self.open, self.close, self.volume).serialize(serializer)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Stats {
     pub open: String,
     pub high: String,
@@ -102,111 +177,231 @@ pub struct Stats {
     pub volume_30day: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Currency {
     pub id: String,
     pub name: String,
     pub min_size: String
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Time {
     pub iso: DateTime<Utc>,
     pub epoch: f64
 }
 
+fn candles_path(product: &str, start_time: DateTime<Utc>, end_time: DateTime<Utc>, granularity: Granularity)
+    -> String
+{
+    format!("/products/{}/candles?start={}&end={}&granularity={}",
+            product,
+            start_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            end_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            granularity.seconds())
+}
+
 pub struct Client {
-    curl: Easy,
+    transport: Box<dyn Transport + Send>,
+    url: String
+}
+
+impl Default for Client {
+    fn default() -> Client {
+        Client::new()
+    }
 }
 
 impl Client {
     pub fn new() -> Client {
+        Client::with_transport(CurlTransport::new())
+    }
+
+    /// Creates a client that sends its requests through `transport`, e.g.
+    /// to record them or to answer them from memory.
+    pub fn with_transport<T>(transport: T) -> Client
+        where T: Transport + Send + 'static
+    {
         Client {
-            curl: Easy::new(),
+            transport: Box::new(transport),
+            url: PUBLIC_API_URL.to_owned()
         }
     }
 
-    fn get_and_decode<T>(&mut self, url: &str) -> Result<T, Error>
+    /// Sends requests to the API at `url` rather than the exchange's, e.g.
+    /// a sandbox or a `mock::MockExchange`.
+    pub fn base_url(mut self, url: &str) -> Client {
+        self.url = url.trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Issues a request to `path` (relative to the API root, including any
+    /// query string) and returns the decoded value together with the raw
+    /// response. Useful for endpoints this crate doesn't model yet, or for
+    /// reading response headers such as pagination cursors.
+    pub fn request<T>(&mut self, method: Method, path: &str, body: &str) -> Result<Response<T>, Error>
         where for<'de> T: Deserialize<'de>
     {
-        self.curl.url(url).unwrap();
-        self.curl.useragent("rust-gdax-client/1.2.0").unwrap();
-
-        let mut buf = Vec::new();
-
-        {
-            let mut t = self.curl.transfer();
-            t.write_function(|data| {
-                buf.extend_from_slice(data);
-                Ok(data.len())
-            }).unwrap();
-            t.perform().unwrap();
-        }
+        self.execute(method, path, body)?.decode()
+    }
 
-        if self.curl.response_code().unwrap() != 200 {
-            return Err(Error::Api(ApiError{ message: String::from_utf8(buf).unwrap()}));
-        } else {
-            return Ok(de::from_reader(&mut buf.as_slice())?)
+    fn execute(&mut self, method: Method, path: &str, body: &str) -> Result<Response<()>, Error> {
+        let mut headers = vec![("Accept".to_owned(), "application/json".to_owned())];
+        if !body.is_empty() {
+            headers.push(("Content-Type".to_owned(), "application/json".to_owned()));
         }
+
+        let request = Request {
+            method,
+            url: format!("{}{}", self.url, path),
+            headers,
+            body: body.to_owned()
+        };
+
+        self.transport.execute(&request)
+    }
+
+    fn get_and_decode<T>(&mut self, path: &str) -> Result<T, Error>
+        where for<'de> T: Deserialize<'de>
+    {
+        Ok(self.request(Method::Get, path, "")?.value)
     }
 
     pub fn get_products(&mut self) -> Result<Vec<Product>, Error> {
-        self.get_and_decode(&format!("{}/products", PUBLIC_API_URL))
+        self.get_and_decode("/products")
     }
 
     pub fn get_best_order(&mut self, product: &str) -> Result<OrderBook<BookEntry>, Error> {
-        self.get_and_decode(&format!("{}/products/{}/book?level={}",
-                                     PUBLIC_API_URL,
+        self.get_and_decode(&format!("/products/{}/book?level={}",
                                      product,
                                      Level::Best as u8))
     }
 
     pub fn get_top50_orders(&mut self, product: &str) -> Result<OrderBook<BookEntry>, Error> {
-        self.get_and_decode(&format!("{}/products/{}/book?level={}",
-                                     PUBLIC_API_URL,
+        self.get_and_decode(&format!("/products/{}/book?level={}",
                                      product,
                                      Level::Top50 as u8))
     }
 
     pub fn get_full_book(&mut self, product: &str) -> Result<OrderBook<FullBookEntry>, Error> {
-        self.get_and_decode(&format!("{}/products/{}/book?level={}",
-                                     PUBLIC_API_URL,
+        self.get_and_decode(&format!("/products/{}/book?level={}",
                                      product,
                                      Level::Full as u8))
     }
 
     pub fn get_product_ticker(&mut self, product: &str) -> Result<Tick, Error> {
-        self.get_and_decode(&format!("{}/products/{}/ticker", PUBLIC_API_URL, product))
+        self.get_and_decode(&format!("/products/{}/ticker", product))
     }
 
     pub fn get_trades(&mut self, product: &str) -> Result<Vec<Trade>, Error> {
-        self.get_and_decode(&format!("{}/products/{}/trades", PUBLIC_API_URL, product))
+        self.get_and_decode(&format!("/products/{}/trades", product))
+    }
+
+    /// Fetches a page of trades, newest first. Without a cursor this is the
+    /// latest page; pass the previous page's `after()` cursor to continue
+    /// with older trades. Retried with backoff if rate limited.
+    pub fn get_trades_page(&mut self, product: &str, after: Option<&str>) -> Result<Response<Vec<Trade>>, Error> {
+        match after {
+            Some(after) => self.get_rate_limited(&format!("/products/{}/trades?after={}", product, after)),
+            None => self.get_rate_limited(&format!("/products/{}/trades", product))
+        }
+    }
+
+    /// Fetches the page of trades just newer than the `before` cursor,
+    /// still newest first. Pass a page's `before()` cursor, or a trade id,
+    /// to walk forward in time. Retried with backoff if rate limited.
+    pub fn get_newer_trades_page(&mut self, product: &str, before: &str) -> Result<Response<Vec<Trade>>, Error> {
+        self.get_rate_limited(&format!("/products/{}/trades?before={}", product, before))
     }
 
     pub fn get_historic_rates(&mut self,
                               product: &str,
                               start_time: DateTime<Utc>,
                               end_time: DateTime<Utc>,
-                              granularity: u64)
+                              granularity: Granularity)
         -> Result<Vec<Candle>, Error> {
 
-        self.get_and_decode(&format!("{}/products/{}/candles?start={}&end={}&granularity={}",
-                                     PUBLIC_API_URL,
-                                     product,
-                                     start_time.to_rfc3339_opts(SecondsFormat::Secs, true),
-                                     end_time.to_rfc3339_opts(SecondsFormat::Secs, true),
-                                     granularity))
+        self.get_and_decode(&candles_path(product, start_time, end_time, granularity))
+    }
+
+    /// Fetches the candles starting within `[start_time, end_time)`,
+    /// however long.
+    ///
+    /// The range is split into windows of at most 300 candles, the most the
+    /// exchange returns at once. Requests are spaced to stay within the
+    /// public rate limit, and retried with backoff if it's exceeded anyway.
+    pub fn get_historic_rates_range(&mut self,
+                                    product: &str,
+                                    start_time: DateTime<Utc>,
+                                    end_time: DateTime<Utc>,
+                                    granularity: Granularity)
+        -> Result<CandleRange, Error> {
+
+        let width = granularity.seconds();
+        // Round up, so the first candle doesn't start before `start_time`
+        let start = (start_time.timestamp().max(0) as u64).div_ceil(width) * width;
+        let end = end_time.timestamp().max(0) as u64;
+
+        let mut candles = Vec::new();
+        let mut window = start;
+        while window < end {
+            let window_end = end.min(window + MAX_CANDLES * width);
+            if window > start {
+                thread::sleep(RATE_LIMIT_INTERVAL);
+            }
+            // Both ends are inclusive, so ask up to the last candle start
+            // to stay within the per-request limit
+            let last = (window_end - 1) / width * width;
+            let path = candles_path(product,
+                                    Utc.timestamp_opt(window as i64, 0).unwrap(),
+                                    Utc.timestamp_opt(last as i64, 0).unwrap(),
+                                    granularity);
+            let batch: Vec<Candle> = self.get_rate_limited(&path)?.value;
+            candles.extend(batch.into_iter().filter(|c| c.time >= start && c.time < end));
+            window = window_end;
+        }
+
+        candles.sort_by_key(|c| c.time);
+        candles.dedup_by_key(|c| c.time);
+
+        let mut gaps = Vec::new();
+        let mut expected = start;
+        for candle in &candles {
+            if candle.time > expected {
+                gaps.push((expected, candle.time));
+            }
+            expected = candle.time + width;
+        }
+        if expected < end {
+            gaps.push((expected, end));
+        }
+
+        Ok(CandleRange { candles, gaps })
+    }
+
+    fn get_rate_limited<T>(&mut self, path: &str) -> Result<Response<T>, Error>
+        where for<'de> T: Deserialize<'de>
+    {
+        let mut delay = Duration::from_secs(1);
+        for _ in 0..RATE_LIMIT_RETRIES {
+            let response = self.execute(Method::Get, path, "")?;
+            if response.status != 429 {
+                return response.decode();
+            }
+            thread::sleep(delay);
+            delay *= 2;
+        }
+        self.execute(Method::Get, path, "")?.decode()
     }
 
     pub fn get_24hr_stats(&mut self, product: &str) -> Result<Stats, Error> {
-        self.get_and_decode(&format!("{}/products/{}/stats", PUBLIC_API_URL, product))
+        self.get_and_decode(&format!("/products/{}/stats", product))
     }
 
     pub fn get_currencies(&mut self) -> Result<Vec<Currency>, Error> {
-        self.get_and_decode(&format!("{}/currencies", PUBLIC_API_URL))
+        self.get_and_decode("/currencies")
     }
 
     pub fn get_time(&mut self) -> Result<Time, Error> {
-        self.get_and_decode(&format!("{}/time", PUBLIC_API_URL))
+        self.get_and_decode("/time")
     }
 }
@@ -0,0 +1,204 @@
+//! Recording and replaying of feed and REST traffic.
+//!
+//! A `Recorder` appends every feed message and REST response it sees to a
+//! newline delimited JSON file, one `Record` per line. A `Replay` reads
+//! such a file back, handing the feed messages out in their original order
+//! and serving the REST responses through a `MemoryTransport`, so that a
+//! session can be reproduced without a network.
+
+use chrono::{DateTime, Utc};
+use serde_json::{self, Value};
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use super::Error;
+use super::Response;
+use super::feed::Message;
+use super::transport::{MemoryTransport, Method, Request, Transport};
+
+/// One line of a recording.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Record {
+    /// A message received from the websocket feed, exactly as sent.
+    Feed {
+        time: DateTime<Utc>,
+        message: Value
+    },
+    /// A REST request and the response it got. Request headers aren't
+    /// recorded, so signed requests don't leak credentials.
+    Rest {
+        time: DateTime<Utc>,
+        method: Method,
+        path: String,
+        request_body: String,
+        status: u32,
+        headers: Vec<(String, String)>,
+        body: String
+    }
+}
+
+/// Appends records to a file or other writer.
+///
+/// Clones share the same writer, so one recorder can capture a feed and
+/// several clients into a single, interleaved file. Every record is flushed
+/// as it's written so nothing is lost if the process dies.
+#[derive(Clone)]
+pub struct Recorder {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>
+}
+
+impl Recorder {
+    pub fn new<W>(writer: W) -> Recorder
+        where W: Write + Send + 'static
+    {
+        Recorder {
+            writer: Arc::new(Mutex::new(Box::new(writer)))
+        }
+    }
+
+    /// Records to the file at `path`, appending if it already exists.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Recorder, Error> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Recorder::new(BufWriter::new(file)))
+    }
+
+    pub fn record(&self, record: &Record) -> Result<(), Error> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        writer.write_all(&line)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Records a raw feed message.
+    pub fn record_message(&self, text: &str) -> Result<(), Error> {
+        self.record(&Record::Feed {
+            time: Utc::now(),
+            message: serde_json::from_str(text)?
+        })
+    }
+
+    pub fn record_response(&self, request: &Request, response: &Response<()>) -> Result<(), Error> {
+        self.record(&Record::Rest {
+            time: Utc::now(),
+            method: request.method,
+            path: request.path().to_owned(),
+            request_body: request.body.clone(),
+            status: response.status,
+            headers: response.headers.clone(),
+            body: String::from_utf8_lossy(&response.body).into_owned()
+        })
+    }
+}
+
+/// A transport that records every response it passes on.
+pub struct RecordingTransport<T> {
+    inner: T,
+    recorder: Recorder
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    pub fn new(inner: T, recorder: Recorder) -> RecordingTransport<T> {
+        RecordingTransport { inner, recorder }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn execute(&mut self, request: &Request) -> Result<Response<()>, Error> {
+        let response = self.inner.execute(request)?;
+        self.recorder.record_response(request, &response)?;
+        Ok(response)
+    }
+}
+
+/// A recording read back into memory.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    records: Vec<Record>
+}
+
+impl Replay {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Replay, Error> {
+        Replay::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads a recording, skipping blank lines.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Replay, Error> {
+        let mut records = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                records.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(Replay { records })
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// The recorded feed messages, decoded in their original order.
+    pub fn feed(&self) -> ReplayFeed {
+        let messages = self.records
+                           .iter()
+                           .filter_map(|record| match *record {
+                               Record::Feed { ref message, .. } => Some(message.clone()),
+                               Record::Rest { .. } => None
+                           })
+                           .collect();
+        ReplayFeed { messages }
+    }
+
+    /// A transport that serves the recorded REST responses, for use with
+    /// `PublicClient::with_transport` and `PrivateClient::with_transport`.
+    /// Clones of it share the responses.
+    pub fn transport(&self) -> MemoryTransport {
+        let transport = MemoryTransport::new();
+        for record in &self.records {
+            if let Record::Rest { method, ref path, status, ref headers, ref body, .. } = *record {
+                transport.push_response(method, path, Response {
+                    value: (),
+                    status,
+                    headers: headers.clone(),
+                    body: body.as_bytes().to_vec()
+                });
+            }
+        }
+        transport
+    }
+}
+
+/// The feed side of a `Replay`, behaving like a `Feed` whose server closes
+/// the connection after the last recorded message.
+pub struct ReplayFeed {
+    messages: VecDeque<Value>
+}
+
+impl ReplayFeed {
+    pub fn next_message(&mut self) -> Result<Option<Message>, Error> {
+        match self.messages.pop_front() {
+            Some(message) => Ok(Some(serde_json::from_value(message)?)),
+            None => Ok(None)
+        }
+    }
+}
+
+impl Iterator for ReplayFeed {
+    type Item = Result<Message, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_message().transpose()
+    }
+}
@@ -0,0 +1,72 @@
+use serde::Deserialize;
+use serde_json::{de, Value};
+
+use super::ApiError;
+use super::Error;
+
+/// A decoded API response along with the raw bytes, status and headers it
+/// was decoded from.
+#[derive(Clone, Debug)]
+pub struct Response<T> {
+    pub value: T,
+    pub status: u32,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>
+}
+
+impl<T> Response<T> {
+    /// Looks up a response header, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+                    .find(|&(n, _)| n.eq_ignore_ascii_case(name))
+                    .map(|(_, v)| v.as_str())
+    }
+
+    /// Cursor for requesting the page of newer results.
+    pub fn before(&self) -> Option<&str> {
+        self.header("CB-BEFORE")
+    }
+
+    /// Cursor for requesting the page of older results.
+    pub fn after(&self) -> Option<&str> {
+        self.header("CB-AFTER")
+    }
+
+    pub fn map<U, F>(self, f: F) -> Response<U>
+        where F: FnOnce(T) -> U
+    {
+        Response {
+            value: f(self.value),
+            status: self.status,
+            headers: self.headers,
+            body: self.body
+        }
+    }
+
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl Response<()> {
+    /// Decodes the body as JSON, turning non-success statuses into
+    /// `Error::Api` with the status. An empty body, as sent with a `204`,
+    /// decodes as JSON `null`, so it suits `()`, `Option` or `Value`.
+    pub fn decode<T>(self) -> Result<Response<T>, Error>
+        where for<'de> T: Deserialize<'de>
+    {
+        if self.status < 200 || self.status >= 300 {
+            return Err(Error::Api(ApiError {
+                message: String::from_utf8_lossy(&self.body).into_owned(),
+                status: Some(self.status)
+            }));
+        }
+
+        let value = if self.body.iter().all(u8::is_ascii_whitespace) {
+            T::deserialize(Value::Null)?
+        } else {
+            de::from_slice(&self.body)?
+        };
+        Ok(self.map(|_| value))
+    }
+}
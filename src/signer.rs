@@ -0,0 +1,204 @@
+//! Authentication of private requests.
+//!
+//! `PrivateClient` asks a `RequestSigner` for the headers that authenticate
+//! each request. `HmacSigner` implements the exchange's API key scheme and
+//! is used by default; other signers can keep the key in an HSM or a remote
+//! signing service, or implement a different scheme altogether, such as a
+//! JWT signed with an ES256 key.
+
+use base64;
+use getrandom::getrandom;
+use hmac::{Hmac, Mac};
+use p256::ecdsa::{Signature, SigningKey};
+use p256::ecdsa::signature::Signer;
+use p256::pkcs8::DecodePrivateKey;
+use p256::SecretKey;
+use serde_json;
+use sha2::Sha256;
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use std::fmt;
+
+use super::Error;
+use super::transport::Method;
+
+/// What a signer may cover for a single request.
+#[derive(Clone, Copy, Debug)]
+pub struct SigningRequest<'a> {
+    pub method: Method,
+    /// The full URL, including the host.
+    pub url: &'a str,
+    /// The path and query string.
+    pub path: &'a str,
+    pub body: &'a str,
+    /// Seconds since the epoch, by the client's clock.
+    pub timestamp: &'a str
+}
+
+/// Produces the authentication headers for private requests.
+///
+/// Implementations must not include key material in their `Debug` output,
+/// which is used by the client's.
+pub trait RequestSigner: fmt::Debug + Send {
+    fn sign(&self, request: &SigningRequest) -> Result<Vec<(String, String)>, Error>;
+}
+
+/// Signs requests with an API key, secret and passphrase.
+///
+/// The signature is the base64 encoded HMAC-SHA256, keyed with the decoded
+/// secret, of the timestamp, method, path and body concatenated, as
+/// returned by `prehash`.
+pub struct HmacSigner {
+    key: String,
+    secret: Zeroizing<Vec<u8>>,
+    passphrase: Zeroizing<String>
+}
+
+impl HmacSigner {
+    /// Fails with `Error::InvalidSecretKey` if `secret` isn't base64.
+    pub fn new(key: &str, secret: &str, passphrase: &str) -> Result<HmacSigner, Error> {
+        Ok(HmacSigner::from_decoded(key, Zeroizing::new(base64::decode(secret)?), passphrase))
+    }
+
+    pub(crate) fn from_decoded(key: &str, secret: Zeroizing<Vec<u8>>, passphrase: &str) -> HmacSigner {
+        HmacSigner {
+            key: key.to_owned(),
+            secret,
+            passphrase: Zeroizing::new(passphrase.to_owned())
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The string that is signed for `request`.
+    pub fn prehash(request: &SigningRequest) -> String {
+        format!("{}{}{}{}", request.timestamp, request.method.as_str(), request.path, request.body)
+    }
+
+    /// The base64 encoded signature of `message`.
+    pub fn signature(&self, message: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(message.as_bytes());
+        base64::encode(&mac.finalize().into_bytes())
+    }
+}
+
+impl RequestSigner for HmacSigner {
+    fn sign(&self, request: &SigningRequest) -> Result<Vec<(String, String)>, Error> {
+        Ok(vec![
+            ("CB-ACCESS-KEY".to_owned(), self.key.clone()),
+            ("CB-ACCESS-SIGN".to_owned(), self.signature(&HmacSigner::prehash(request))),
+            ("CB-ACCESS-PASSPHRASE".to_owned(), self.passphrase.to_string()),
+            ("CB-ACCESS-TIMESTAMP".to_owned(), request.timestamp.to_owned()),
+        ])
+    }
+}
+
+impl fmt::Debug for HmacSigner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HmacSigner")
+         .field("key", &self.key)
+         .field("secret", &"<redacted>")
+         .field("passphrase", &"<redacted>")
+         .finish()
+    }
+}
+
+// How long a JWT stays valid, in seconds. Each request gets a fresh one.
+const JWT_LIFETIME: i64 = 120;
+
+/// Signs requests with a short-lived JWT, as required by the Advanced
+/// Trade API.
+///
+/// Each request gets its own token, signed with ES256 using an EC key in
+/// SEC1 or PKCS#8 PEM form and bound to the request's method, host and
+/// path.
+pub struct JwtSigner {
+    key_name: String,
+    key: SigningKey
+}
+
+impl JwtSigner {
+    /// `key_name` is the API key's name, such as
+    /// `organizations/{org_id}/apiKeys/{key_id}`. Fails with
+    /// `Error::InvalidSecretKey` if `pem` isn't a P-256 private key.
+    pub fn new(key_name: &str, pem: &str) -> Result<JwtSigner, Error> {
+        let pem = pem.trim().replace("\\n", "\n");
+        let secret = SecretKey::from_sec1_pem(&pem)
+            .or_else(|_| SecretKey::from_pkcs8_pem(&pem))
+            .map_err(|_| Error::InvalidSecretKey)?;
+
+        Ok(JwtSigner {
+            key_name: key_name.to_owned(),
+            key: SigningKey::from(secret)
+        })
+    }
+
+    pub fn key_name(&self) -> &str {
+        &self.key_name
+    }
+
+    /// A token for `request`, valid from its timestamp for two minutes.
+    pub fn token(&self, request: &SigningRequest) -> Result<String, Error> {
+        let now: i64 = request.timestamp.parse().map_err(|_| Error::InvalidNumber(request.timestamp.to_owned()))?;
+
+        let host = request.url.find("://").map_or(request.url, |idx| &request.url[idx + 3..]);
+        let host = host.find('/').map_or(host, |idx| &host[..idx]);
+        let path = request.path.find('?').map_or(request.path, |idx| &request.path[..idx]);
+
+        let nonce = random_hex(16)?;
+
+        let header = json!({
+            "alg": "ES256",
+            "typ": "JWT",
+            "kid": self.key_name,
+            "nonce": nonce
+        });
+        let claims = json!({
+            "sub": self.key_name,
+            "iss": "cdp",
+            "nbf": now,
+            "exp": now + JWT_LIFETIME,
+            "uri": format!("{} {}{}", request.method.as_str(), host, path)
+        });
+
+        let encode = |data: &[u8]| base64::encode_config(data, base64::URL_SAFE_NO_PAD);
+        let message = format!("{}.{}",
+                              encode(&serde_json::to_vec(&header)?),
+                              encode(&serde_json::to_vec(&claims)?));
+        let signature: Signature = self.key.sign(message.as_bytes());
+
+        Ok(format!("{}.{}", message, encode(&signature.to_bytes())))
+    }
+}
+
+/// `len` random bytes from the operating system, hex encoded.
+pub(crate) fn random_hex(len: usize) -> Result<String, Error> {
+    let mut bytes = vec![0u8; len];
+    getrandom(&mut bytes).map_err(|e| Error::Io(e.into()))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// A random identifier, e.g. for a client order id.
+pub(crate) fn random_uuid() -> Result<Uuid, Error> {
+    let hex = random_hex(16)?;
+    Ok(Uuid::parse_str(&hex).expect("32 hex digits are a UUID"))
+}
+
+impl RequestSigner for JwtSigner {
+    fn sign(&self, request: &SigningRequest) -> Result<Vec<(String, String)>, Error> {
+        Ok(vec![("Authorization".to_owned(), format!("Bearer {}", self.token(request)?))])
+    }
+}
+
+impl fmt::Debug for JwtSigner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("JwtSigner")
+         .field("key_name", &self.key_name)
+         .field("key", &"<redacted>")
+         .finish()
+    }
+}
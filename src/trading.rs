@@ -0,0 +1,234 @@
+//! Traits over the clients, for code that shouldn't depend on which backend
+//! it runs against.
+//!
+//! `MarketData` covers the public endpoints and `Trading` the private ones.
+//! `PublicClient` implements `MarketData`, `PrivateClient` implements both,
+//! and `PaperTrader` implements `Trading`, so a strategy written against the
+//! traits runs unchanged against the exchange, a `MockExchange` or a paper
+//! account:
+//!
+//! ```no_run
+//! # use gdax_client::{Error, MarketData, NewOrder, PublicClient, Side, Trading};
+//! # use gdax_client::paper::PaperTrader;
+//! fn buy_the_dip<M: MarketData, T: Trading>(market: &mut M, trader: &mut T) -> Result<(), Error> {
+//!     let price: f64 = market.get_product_ticker("BTC-USD")?.price.parse().unwrap();
+//!     trader.post_order(&NewOrder::limit(Side::Buy, "BTC-USD", 0.01, price * 0.95))?;
+//!     Ok(())
+//! }
+//!
+//! let mut trader = PaperTrader::new();
+//! trader.deposit("USD", 1000.0);
+//! buy_the_dip(&mut PublicClient::new(), &mut trader).unwrap();
+//! ```
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::Error;
+use super::NewOrder;
+use super::Response;
+use super::paper::PaperTrader;
+use super::private::{self, Account, Conversion, Fill, Hold, Ledger, OpenOrder, Order, OrderId};
+use super::public::{self, BookEntry, Candle, CandleRange, Currency, FullBookEntry, Granularity, OrderBook, Product, Stats, Tick,
+                    Time, Trade};
+
+/// The public market data endpoints.
+pub trait MarketData {
+    fn get_products(&mut self) -> Result<Vec<Product>, Error>;
+
+    fn get_best_order(&mut self, product: &str) -> Result<OrderBook<BookEntry>, Error>;
+
+    fn get_top50_orders(&mut self, product: &str) -> Result<OrderBook<BookEntry>, Error>;
+
+    fn get_full_book(&mut self, product: &str) -> Result<OrderBook<FullBookEntry>, Error>;
+
+    fn get_product_ticker(&mut self, product: &str) -> Result<Tick, Error>;
+
+    fn get_trades(&mut self, product: &str) -> Result<Vec<Trade>, Error>;
+
+    fn get_trades_page(&mut self, product: &str, after: Option<&str>) -> Result<Response<Vec<Trade>>, Error>;
+
+    fn get_newer_trades_page(&mut self, product: &str, before: &str) -> Result<Response<Vec<Trade>>, Error>;
+
+    fn get_historic_rates(&mut self,
+                          product: &str,
+                          start_time: DateTime<Utc>,
+                          end_time: DateTime<Utc>,
+                          granularity: Granularity)
+        -> Result<Vec<Candle>, Error>;
+
+    fn get_historic_rates_range(&mut self,
+                                product: &str,
+                                start_time: DateTime<Utc>,
+                                end_time: DateTime<Utc>,
+                                granularity: Granularity)
+        -> Result<CandleRange, Error>;
+
+    fn get_24hr_stats(&mut self, product: &str) -> Result<Stats, Error>;
+
+    fn get_currencies(&mut self) -> Result<Vec<Currency>, Error>;
+
+    fn get_time(&mut self) -> Result<Time, Error>;
+}
+
+/// The private account and order endpoints.
+pub trait Trading {
+    fn get_accounts(&mut self) -> Result<Vec<Account>, Error>;
+
+    fn get_account(&mut self, id: Uuid) -> Result<Account, Error>;
+
+    fn get_account_history(&mut self, id: Uuid) -> Result<Ledger, Error>;
+
+    fn get_account_holds(&mut self, id: Uuid) -> Result<Vec<Hold>, Error>;
+
+    fn post_order(&mut self, order: &NewOrder) -> Result<OrderId, Error>;
+
+    fn convert(&mut self, from: &str, to: &str, amount: f64) -> Result<Conversion, Error>;
+
+    fn cancel_order(&mut self, order_id: OrderId) -> Result<OrderId, Error>;
+
+    fn cancel_all_orders(&mut self, product_id: Option<&str>) -> Result<Vec<OrderId>, Error>;
+
+    fn get_orders_with_status(&mut self, open: bool, pending: bool, active: bool) -> Result<Vec<OpenOrder>, Error>;
+
+    fn get_orders(&mut self) -> Result<Vec<OpenOrder>, Error>;
+
+    fn get_order(&mut self, order_id: OrderId) -> Result<Order, Error>;
+
+    fn get_fills(&mut self, order_id: Option<OrderId>, product_id: Option<&str>) -> Result<Vec<Fill>, Error>;
+}
+
+// Implements `MarketData` for `$client` by calling the public client it
+// dereferences to
+macro_rules! impl_market_data {
+    ($client:ty) => {
+        impl MarketData for $client {
+            fn get_products(&mut self) -> Result<Vec<Product>, Error> {
+                public::Client::get_products(self)
+            }
+
+            fn get_best_order(&mut self, product: &str) -> Result<OrderBook<BookEntry>, Error> {
+                public::Client::get_best_order(self, product)
+            }
+
+            fn get_top50_orders(&mut self, product: &str) -> Result<OrderBook<BookEntry>, Error> {
+                public::Client::get_top50_orders(self, product)
+            }
+
+            fn get_full_book(&mut self, product: &str) -> Result<OrderBook<FullBookEntry>, Error> {
+                public::Client::get_full_book(self, product)
+            }
+
+            fn get_product_ticker(&mut self, product: &str) -> Result<Tick, Error> {
+                public::Client::get_product_ticker(self, product)
+            }
+
+            fn get_trades(&mut self, product: &str) -> Result<Vec<Trade>, Error> {
+                public::Client::get_trades(self, product)
+            }
+
+            fn get_trades_page(&mut self, product: &str, after: Option<&str>) -> Result<Response<Vec<Trade>>, Error> {
+                public::Client::get_trades_page(self, product, after)
+            }
+
+            fn get_newer_trades_page(&mut self, product: &str, before: &str) -> Result<Response<Vec<Trade>>, Error> {
+                public::Client::get_newer_trades_page(self, product, before)
+            }
+
+            fn get_historic_rates(&mut self,
+                                  product: &str,
+                                  start_time: DateTime<Utc>,
+                                  end_time: DateTime<Utc>,
+                                  granularity: Granularity)
+                -> Result<Vec<Candle>, Error>
+            {
+                public::Client::get_historic_rates(self, product, start_time, end_time, granularity)
+            }
+
+            fn get_historic_rates_range(&mut self,
+                                        product: &str,
+                                        start_time: DateTime<Utc>,
+                                        end_time: DateTime<Utc>,
+                                        granularity: Granularity)
+                -> Result<CandleRange, Error>
+            {
+                public::Client::get_historic_rates_range(self, product, start_time, end_time, granularity)
+            }
+
+            fn get_24hr_stats(&mut self, product: &str) -> Result<Stats, Error> {
+                public::Client::get_24hr_stats(self, product)
+            }
+
+            fn get_currencies(&mut self) -> Result<Vec<Currency>, Error> {
+                public::Client::get_currencies(self)
+            }
+
+            fn get_time(&mut self) -> Result<Time, Error> {
+                public::Client::get_time(self)
+            }
+        }
+    }
+}
+
+// Implements `Trading` for `$client` with its inherent methods of the same
+// names
+macro_rules! impl_trading {
+    ($client:ty) => {
+        impl Trading for $client {
+            fn get_accounts(&mut self) -> Result<Vec<Account>, Error> {
+                <$client>::get_accounts(self)
+            }
+
+            fn get_account(&mut self, id: Uuid) -> Result<Account, Error> {
+                <$client>::get_account(self, id)
+            }
+
+            fn get_account_history(&mut self, id: Uuid) -> Result<Ledger, Error> {
+                <$client>::get_account_history(self, id)
+            }
+
+            fn get_account_holds(&mut self, id: Uuid) -> Result<Vec<Hold>, Error> {
+                <$client>::get_account_holds(self, id)
+            }
+
+            fn post_order(&mut self, order: &NewOrder) -> Result<OrderId, Error> {
+                <$client>::post_order(self, order)
+            }
+
+            fn convert(&mut self, from: &str, to: &str, amount: f64) -> Result<Conversion, Error> {
+                <$client>::convert(self, from, to, amount)
+            }
+
+            fn cancel_order(&mut self, order_id: OrderId) -> Result<OrderId, Error> {
+                <$client>::cancel_order(self, order_id)
+            }
+
+            fn cancel_all_orders(&mut self, product_id: Option<&str>) -> Result<Vec<OrderId>, Error> {
+                <$client>::cancel_all_orders(self, product_id)
+            }
+
+            fn get_orders_with_status(&mut self, open: bool, pending: bool, active: bool)
+                -> Result<Vec<OpenOrder>, Error>
+            {
+                <$client>::get_orders_with_status(self, open, pending, active)
+            }
+
+            fn get_orders(&mut self) -> Result<Vec<OpenOrder>, Error> {
+                <$client>::get_orders(self)
+            }
+
+            fn get_order(&mut self, order_id: OrderId) -> Result<Order, Error> {
+                <$client>::get_order(self, order_id)
+            }
+
+            fn get_fills(&mut self, order_id: Option<OrderId>, product_id: Option<&str>) -> Result<Vec<Fill>, Error> {
+                <$client>::get_fills(self, order_id, product_id)
+            }
+        }
+    }
+}
+
+impl_market_data!(public::Client);
+impl_market_data!(private::Client);
+impl_trading!(private::Client);
+impl_trading!(PaperTrader);
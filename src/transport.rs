@@ -0,0 +1,273 @@
+use curl::easy::{Easy, List};
+use zeroize::Zeroize;
+
+use std::collections::VecDeque;
+use std::io;
+use std::str;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::Error;
+use super::Response;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Method {
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete
+}
+
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Method::Get => "GET",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Patch => "PATCH",
+            Method::Delete => "DELETE"
+        }
+    }
+}
+
+/// An HTTP request as built by the clients, before it is sent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    pub method: Method,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String
+}
+
+impl Request {
+    /// The path and query string, without the scheme and host.
+    pub fn path(&self) -> &str {
+        path_of(&self.url)
+    }
+}
+
+// The headers of signed requests carry credentials, such as the
+// passphrase, that shouldn't outlive the request
+impl Drop for Request {
+    fn drop(&mut self) {
+        for (_, value) in &mut self.headers {
+            value.zeroize();
+        }
+    }
+}
+
+fn path_of(url: &str) -> &str {
+    let rest = url.find("://").map_or(url, |idx| &url[idx + 3..]);
+    rest.find('/').map_or("/", |idx| &rest[idx..])
+}
+
+/// Sends requests on behalf of `PublicClient` and `PrivateClient`.
+///
+/// The clients use curl by default; other transports can be supplied to
+/// record traffic or to answer requests without a network.
+pub trait Transport {
+    fn execute(&mut self, request: &Request) -> Result<Response<()>, Error>;
+}
+
+pub struct CurlTransport {
+    curl: Easy
+}
+
+impl Default for CurlTransport {
+    fn default() -> CurlTransport {
+        CurlTransport::new()
+    }
+}
+
+impl CurlTransport {
+    pub fn new() -> CurlTransport {
+        CurlTransport {
+            curl: Easy::new()
+        }
+    }
+}
+
+impl Transport for CurlTransport {
+    fn execute(&mut self, request: &Request) -> Result<Response<()>, Error> {
+        // Reset so options from the previous request (method, body) don't
+        // leak into this one; the connection cache is kept.
+        self.curl.reset();
+        self.curl.url(&request.url)?;
+        self.curl.useragent("rust-gdax-client/1.2.0")?;
+
+        let mut headers = List::new();
+        for (name, value) in &request.headers {
+            // curl copies the line, so ours can be wiped straight away
+            let mut line = format!("{}: {}", name, value);
+            let appended = headers.append(&line);
+            line.zeroize();
+            appended?;
+        }
+        self.curl.http_headers(headers)?;
+
+        match request.method {
+            Method::Get => self.curl.get(true)?,
+            Method::Post => {
+                self.curl.post(true)?;
+                self.curl.post_fields_copy(request.body.as_bytes())?;
+            }
+            Method::Put | Method::Patch => {
+                // curl's own PUT uploads through a read callback; sending
+                // the body as fields with a custom verb is simpler
+                self.curl.custom_request(request.method.as_str())?;
+                self.curl.post_fields_copy(request.body.as_bytes())?;
+            }
+            Method::Delete => self.curl.custom_request("DELETE")?
+        }
+
+        let mut body = Vec::new();
+        let mut response_headers = Vec::new();
+
+        {
+            let mut t = self.curl.transfer();
+            t.write_function(|data| {
+                body.extend_from_slice(data);
+                Ok(data.len())
+            })?;
+            t.header_function(|line| {
+                let line = str::from_utf8(line).unwrap_or("").trim_end();
+                if line.starts_with("HTTP/") {
+                    // A new status line starts a new header block, e.g.
+                    // after a `100 Continue`
+                    response_headers.clear();
+                } else if let Some(idx) = line.find(':') {
+                    response_headers.push((line[..idx].trim().to_owned(),
+                                           line[idx + 1..].trim().to_owned()));
+                }
+                true
+            })?;
+            t.perform()?;
+        }
+
+        Ok(Response {
+            value: (),
+            status: self.curl.response_code()?,
+            headers: response_headers,
+            body
+        })
+    }
+}
+
+/// A transport shared by several clients, so that a `PrivateClient` and
+/// the `PublicClient` inside it send through the same one.
+#[derive(Clone)]
+pub(crate) struct SharedTransport {
+    inner: Arc<Mutex<Box<dyn Transport + Send>>>
+}
+
+impl SharedTransport {
+    pub(crate) fn new<T: Transport + Send + 'static>(transport: T) -> SharedTransport {
+        SharedTransport {
+            inner: Arc::new(Mutex::new(Box::new(transport)))
+        }
+    }
+}
+
+impl Transport for SharedTransport {
+    fn execute(&mut self, request: &Request) -> Result<Response<()>, Error> {
+        // A panic in another client's request can't leave the transport
+        // half updated, so carry on with it
+        self.inner.lock().unwrap_or_else(|e| e.into_inner()).execute(request)
+    }
+}
+
+// Headers whose values `MemoryTransport` doesn't keep
+const REDACTED_HEADERS: &[&str] = &["CB-ACCESS-PASSPHRASE"];
+
+struct Exchange {
+    method: Method,
+    path: String,
+    response: Response<()>
+}
+
+#[derive(Default)]
+struct MemoryState {
+    exchanges: VecDeque<Exchange>,
+    requests: Vec<Request>
+}
+
+/// A transport that answers requests from canned responses instead of the
+/// network.
+///
+/// Each response is used once, and requests are matched against them by
+/// method and path in the order the responses were added, so the same
+/// endpoint can return different values on successive calls. Clones share
+/// the same responses, which lets one set of canned data back several
+/// clients.
+#[derive(Clone, Default)]
+pub struct MemoryTransport {
+    state: Arc<Mutex<MemoryState>>
+}
+
+impl MemoryTransport {
+    pub fn new() -> MemoryTransport {
+        MemoryTransport::default()
+    }
+
+    /// Adds a response for the next `method` request to `path`, which is
+    /// relative to the API root and includes any query string.
+    pub fn push_response(&self, method: Method, path: &str, response: Response<()>) {
+        self.lock().exchanges.push_back(Exchange {
+            method,
+            path: path_of(path).to_owned(),
+            response
+        });
+    }
+
+    /// Like `push_response`, for a `200 OK` with a JSON `body`.
+    pub fn push_json(&self, method: Method, path: &str, body: &str) {
+        self.push_response(method, path, Response {
+            value: (),
+            status: 200,
+            headers: vec![("Content-Type".to_owned(), "application/json".to_owned())],
+            body: body.as_bytes().to_vec()
+        });
+    }
+
+    /// The requests executed so far, in order, with the passphrase
+    /// redacted.
+    pub fn requests(&self) -> Vec<Request> {
+        self.lock().requests.clone()
+    }
+
+    /// The number of responses that haven't been used yet.
+    pub fn remaining(&self) -> usize {
+        self.lock().exchanges.len()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemoryState> {
+        // A panic while holding the lock can't leave the queue half updated
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn execute(&mut self, request: &Request) -> Result<Response<()>, Error> {
+        let mut state = self.lock();
+        let mut kept = request.clone();
+        for (name, value) in &mut kept.headers {
+            if REDACTED_HEADERS.iter().any(|h| name.eq_ignore_ascii_case(h)) {
+                value.zeroize();
+                value.push_str("<redacted>");
+            }
+        }
+        state.requests.push(kept);
+
+        let position = state.exchanges
+                            .iter()
+                            .position(|e| e.method == request.method && e.path == request.path());
+        match position.and_then(|idx| state.exchanges.remove(idx)) {
+            Some(exchange) => Ok(exchange.response),
+            None => Err(Error::Io(io::Error::new(io::ErrorKind::NotFound,
+                                                 format!("no response for {} {}",
+                                                         request.method.as_str(),
+                                                         request.path()))))
+        }
+    }
+}
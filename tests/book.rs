@@ -0,0 +1,190 @@
+extern crate gdax_client;
+extern crate serde_json;
+extern crate uuid;
+
+use gdax_client::Side;
+use gdax_client::book::{BookEvent, FullBook, L2Book};
+use gdax_client::feed::Message;
+use gdax_client::public::{BookEntry, FullBookEntry, OrderBook};
+use uuid::Uuid;
+
+const A: &str = "00000000-0000-0000-0000-00000000000a";
+const B: &str = "00000000-0000-0000-0000-00000000000b";
+const C: &str = "00000000-0000-0000-0000-00000000000c";
+const D: &str = "00000000-0000-0000-0000-00000000000d";
+
+fn id(s: &str) -> Uuid {
+    s.parse().unwrap()
+}
+
+fn snapshot() -> OrderBook<FullBookEntry> {
+    serde_json::from_str(&format!(r#"{{
+        "sequence": 100,
+        "bids": [["99.00", "1.0", "{}"], ["98.00", "2.0", "{}"]],
+        "asks": [["101.00", "1.5", "{}"]]
+    }}"#, A, B, C)).unwrap()
+}
+
+fn open(sequence: u64, order_id: &str, side: &str, price: &str, size: &str) -> Message {
+    serde_json::from_str(&format!(r#"{{"type": "open", "time": "2018-01-01T00:00:00Z",
+        "product_id": "BTC-USD", "sequence": {}, "order_id": "{}", "price": "{}",
+        "remaining_size": "{}", "side": "{}"}}"#, sequence, order_id, price, size, side)).unwrap()
+}
+
+fn done(sequence: u64, order_id: &str, side: &str) -> Message {
+    serde_json::from_str(&format!(r#"{{"type": "done", "time": "2018-01-01T00:00:00Z",
+        "product_id": "BTC-USD", "sequence": {}, "order_id": "{}", "reason": "canceled",
+        "side": "{}"}}"#, sequence, order_id, side)).unwrap()
+}
+
+fn fill(sequence: u64, maker: &str, side: &str, price: &str, size: &str) -> Message {
+    serde_json::from_str(&format!(r#"{{"type": "match", "trade_id": 1, "sequence": {},
+        "maker_order_id": "{}", "taker_order_id": "{}", "time": "2018-01-01T00:00:00Z",
+        "product_id": "BTC-USD", "size": "{}", "price": "{}", "side": "{}"}}"#,
+        sequence, maker, D, size, price, side)).unwrap()
+}
+
+#[test]
+fn loads_snapshot() {
+    let mut book = FullBook::new("BTC-USD");
+    book.load_snapshot(&snapshot()).unwrap();
+
+    assert_eq!(book.sequence(), Some(100));
+    assert_eq!(book.len(), 3);
+    assert_eq!(book.best_bid().unwrap().price, 99.0);
+    assert_eq!(book.best_ask().unwrap().size, 1.5);
+    assert_eq!(book.depth_at(Side::Buy, 98.0).unwrap().num_orders, 1);
+    assert_eq!(book.order(&id(B)).unwrap().size, 2.0);
+}
+
+#[test]
+fn applies_messages_in_order() {
+    let mut book = FullBook::new("BTC-USD");
+    book.load_snapshot(&snapshot()).unwrap();
+
+    book.apply(&open(101, D, "buy", "99.00", "0.5")).unwrap();
+    assert_eq!(book.best_bid().unwrap().size, 1.5);
+    assert_eq!(book.best_bid().unwrap().num_orders, 2);
+
+    book.apply(&fill(102, A, "buy", "99.00", "0.25")).unwrap();
+    assert_eq!(book.order(&id(A)).unwrap().size, 0.75);
+    assert_eq!(book.best_bid().unwrap().size, 1.25);
+
+    book.apply(&done(103, C, "sell")).unwrap();
+    assert!(book.best_ask().is_none());
+    assert!(book.order(&id(C)).is_none());
+
+    // Stale messages are ignored
+    book.apply(&done(103, A, "buy")).unwrap();
+    assert!(book.order(&id(A)).is_some());
+    assert_eq!(book.sequence(), Some(103));
+
+    let exported = book.to_order_book();
+    assert_eq!(exported.bids.len(), 3);
+    assert_eq!(exported.bids[0].order_id, id(A));
+    assert_eq!(exported.bids[1].order_id, id(D));
+}
+
+#[test]
+fn buffers_messages_until_snapshot() {
+    let mut book = FullBook::new("BTC-USD");
+
+    // Older than the snapshot, so dropped on replay
+    book.apply(&open(99, D, "sell", "100.00", "9.0")).unwrap();
+    // Newer than the snapshot, replayed in sequence order
+    book.apply(&done(102, D, "sell")).unwrap();
+    book.apply(&open(101, D, "sell", "100.50", "3.0")).unwrap();
+    assert_eq!(book.sequence(), None);
+    assert!(book.is_empty());
+
+    book.load_snapshot(&snapshot()).unwrap();
+    assert_eq!(book.sequence(), Some(102));
+    assert!(book.order(&id(D)).is_none());
+    assert_eq!(book.best_ask().unwrap().price, 101.0);
+}
+
+#[test]
+fn bad_snapshots_and_messages_leave_the_book_recoverable() {
+    let mut book = FullBook::new("BTC-USD");
+    book.load_snapshot(&snapshot()).unwrap();
+
+    // A snapshot that can't be read leaves the book out of sync rather
+    // than half loaded
+    let bad: OrderBook<FullBookEntry> = serde_json::from_str(&format!(r#"{{
+        "sequence": 200, "bids": [["99.00", "1.0", "{}"], ["98.00", "lots", "{}"]], "asks": []
+    }}"#, A, B)).unwrap();
+    assert!(book.load_snapshot(&bad).is_err());
+    assert!(!book.is_synced());
+
+    // A bad message in the replay doesn't lose the ones buffered after it
+    book.apply(&open(101, D, "buy", "97.00", "lots")).unwrap();
+    book.apply(&open(102, D, "buy", "97.00", "3.0")).unwrap();
+    assert!(book.load_snapshot(&snapshot()).is_err());
+    let mut newer = snapshot();
+    newer.sequence = 101;
+    assert_eq!(book.load_snapshot(&newer).unwrap(), BookEvent::Applied);
+    assert_eq!(book.sequence(), Some(102));
+    assert_eq!(book.order(&id(D)).unwrap().size, 3.0);
+}
+
+#[test]
+fn ignores_other_products() {
+    let mut book = FullBook::new("ETH-USD");
+    book.load_snapshot(&snapshot()).unwrap();
+    book.apply(&done(101, A, "buy")).unwrap();
+    assert!(book.order(&id(A)).is_some());
+    assert_eq!(book.sequence(), Some(100));
+}
+
+fn l2_snapshot() -> Message {
+    serde_json::from_str(r#"{"type": "snapshot", "product_id": "BTC-USD",
+        "bids": [["99.00", "1.0"], ["98.00", "2.0"], ["97.00", "4.0"]],
+        "asks": [["101.00", "3.0"], ["102.00", "1.0"]]}"#).unwrap()
+}
+
+#[test]
+fn l2_book_from_snapshot_and_updates() {
+    let mut book = L2Book::new("BTC-USD");
+    book.apply(&l2_snapshot()).unwrap();
+
+    assert_eq!(book.spread(), Some(2.0));
+    assert_eq!(book.mid_price(), Some(100.0));
+    // (99 * 3 + 101 * 1) / 4
+    assert_eq!(book.microprice(), Some(99.5));
+
+    let update: Message = serde_json::from_str(r#"{"type": "l2update", "product_id": "BTC-USD",
+        "time": "2019-08-14T20:42:27.265Z",
+        "changes": [["buy", "99.00", "0"], ["buy", "99.50", "0.5"], ["sell", "102.00", "2.5"]]}"#).unwrap();
+    book.apply(&update).unwrap();
+
+    assert_eq!(book.best_bid().unwrap().price, 99.5);
+    assert!(book.depth_at(Side::Buy, 99.0).is_none());
+    assert_eq!(book.depth_at(Side::Sell, 102.0).unwrap().size, 2.5);
+    let bid_prices: Vec<f64> = book.bids().iter().map(|l| l.price).collect();
+    assert_eq!(bid_prices, vec![99.5, 98.0, 97.0]);
+}
+
+#[test]
+fn l2_book_depth_queries() {
+    let mut book = L2Book::new("BTC-USD");
+    book.apply(&l2_snapshot()).unwrap();
+
+    assert_eq!(book.depth_to(Side::Buy, 98.0), 3.0);
+    assert_eq!(book.depth_to(Side::Sell, 101.5), 3.0);
+    assert_eq!(book.depth_levels(Side::Buy, 2), 3.0);
+    let cumulative: Vec<f64> = book.cumulative_depth(Side::Buy).iter().map(|l| l.size).collect();
+    assert_eq!(cumulative, vec![1.0, 3.0, 7.0]);
+}
+
+#[test]
+fn l2_book_round_trips_rest_snapshot() {
+    let top50: OrderBook<BookEntry> = serde_json::from_str(r#"{
+        "sequence": 42,
+        "bids": [["99.00", "1.0", 3], ["98.00", "2.0", 1]],
+        "asks": [["101.00", "3.0", 2]]
+    }"#).unwrap();
+
+    let book = L2Book::from_order_book("BTC-USD", &top50).unwrap();
+    assert_eq!(book.best_bid().unwrap().num_orders, 3);
+    assert_eq!(book.to_order_book(), top50);
+}
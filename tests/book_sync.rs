@@ -0,0 +1,172 @@
+extern crate gdax_client;
+extern crate serde_json;
+extern crate uuid;
+
+use gdax_client::Error;
+use gdax_client::Side;
+use gdax_client::book::{BookEvent, FullBook, ResyncEvent, SyncedBook};
+use gdax_client::feed::Message;
+use gdax_client::public::{FullBookEntry, OrderBook};
+use std::collections::VecDeque;
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+const A: &str = "00000000-0000-0000-0000-00000000000a";
+const B: &str = "00000000-0000-0000-0000-00000000000b";
+const C: &str = "00000000-0000-0000-0000-00000000000c";
+const D: &str = "00000000-0000-0000-0000-00000000000d";
+const F: &str = "00000000-0000-0000-0000-00000000000f";
+const G: &str = "00000000-0000-0000-0000-000000000010";
+
+// Recorded full channel messages for BTC-USD, with sequences 103 and 104
+// missing.
+fn recorded() -> Vec<Message> {
+    include_str!("data/full_channel_gap.jsonl")
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+fn id(s: &str) -> Uuid {
+    s.parse().unwrap()
+}
+
+fn snapshot(json: &str) -> OrderBook<FullBookEntry> {
+    serde_json::from_str(json).unwrap()
+}
+
+fn snapshot_100() -> OrderBook<FullBookEntry> {
+    snapshot(&format!(r#"{{"sequence": 100,
+        "bids": [["99.00", "1.0", "{}"], ["98.00", "2.0", "{}"]],
+        "asks": [["101.00", "1.5", "{}"]]}}"#, A, B, C))
+}
+
+// The book as of sequence 104, including the order opened by one of the
+// missed messages.
+fn snapshot_104() -> OrderBook<FullBookEntry> {
+    snapshot(&format!(r#"{{"sequence": 104,
+        "bids": [["99.00", "1.0", "{}"], ["99.00", "0.5", "{}"], ["98.00", "2.0", "{}"],
+                 ["97.00", "3.0", "{}"]],
+        "asks": [["101.00", "1.5", "{}"]]}}"#, A, D, B, G, C))
+}
+
+fn source(snapshots: Vec<OrderBook<FullBookEntry>>)
+    -> impl FnMut(&str) -> Result<OrderBook<FullBookEntry>, Error>
+{
+    let mut snapshots: VecDeque<_> = snapshots.into_iter().collect();
+    move |product_id: &str| {
+        assert_eq!(product_id, "BTC-USD");
+        Ok(snapshots.pop_front().expect("unexpected snapshot request"))
+    }
+}
+
+#[test]
+fn detects_gap_and_buffers_until_resync() {
+    let messages = recorded();
+    let mut book = FullBook::new("BTC-USD");
+    book.load_snapshot(&snapshot_100()).unwrap();
+
+    assert_eq!(book.apply(&messages[0]).unwrap(), BookEvent::Applied);
+    assert_eq!(book.apply(&messages[1]).unwrap(), BookEvent::Applied);
+    assert_eq!(book.apply(&messages[2]).unwrap(), BookEvent::Gap { expected: 103, received: 105 });
+    assert!(!book.is_synced());
+    assert_eq!(book.apply(&messages[3]).unwrap(), BookEvent::Buffered);
+
+    assert_eq!(book.load_snapshot(&snapshot_104()).unwrap(), BookEvent::Applied);
+    assert_eq!(book.sequence(), Some(106));
+    assert_eq!(book.order(&id(A)).unwrap().size, 0.75);
+    assert!(book.order(&id(C)).is_none());
+    assert!(book.order(&id(G)).is_some());
+}
+
+#[test]
+fn synced_book_resyncs_through_recorded_gap() {
+    let mut book = SyncedBook::new("BTC-USD", source(vec![snapshot_100(), snapshot_104()]));
+    let events: Vec<Option<ResyncEvent>> = recorded().iter()
+                                                     .map(|m| book.process(m).unwrap())
+                                                     .collect();
+
+    assert_eq!(events[0], Some(ResyncEvent {
+        product_id: "BTC-USD".to_owned(),
+        gap: None,
+        snapshot_sequence: 100,
+        synced: true
+    }));
+    assert_eq!(events[1], None);
+    assert_eq!(events[2], Some(ResyncEvent {
+        product_id: "BTC-USD".to_owned(),
+        gap: Some((103, 105)),
+        snapshot_sequence: 104,
+        synced: true
+    }));
+    assert!(events[3..].iter().all(Option::is_none));
+
+    let book = book.book();
+    assert_eq!(book.sequence(), Some(107));
+    assert_eq!(book.best_ask().unwrap().price, 100.5);
+    assert_eq!(book.order(&id(F)).unwrap().side, Side::Sell);
+    assert_eq!(book.depth_at(Side::Buy, 99.0).unwrap().size, 1.25);
+    assert_eq!(book.depth_at(Side::Buy, 97.0).unwrap().num_orders, 1);
+}
+
+#[test]
+fn out_of_order_messages_are_replayed_in_sequence() {
+    let messages = recorded();
+    let mut book = SyncedBook::new("BTC-USD", source(vec![snapshot_100(), snapshot_100()]))
+        .retry_delay(Duration::from_millis(0));
+
+    // 102 overtakes 101, so it doesn't follow on from the snapshot
+    let event = book.process(&messages[1]).unwrap().unwrap();
+    assert_eq!(event.gap, Some((101, 102)));
+    assert!(!event.synced);
+    assert!(!book.book().is_synced());
+
+    let event = book.process(&messages[0]).unwrap().unwrap();
+    assert_eq!(event.gap, Some((101, 102)));
+    assert!(event.synced);
+    assert_eq!(book.book().sequence(), Some(102));
+    assert_eq!(book.book().order(&id(D)).unwrap().size, 0.5);
+}
+
+#[test]
+fn stale_snapshot_is_refetched_after_the_retry_delay() {
+    let messages = recorded();
+    let stale = snapshot_100();
+    let mut book = SyncedBook::new("BTC-USD", source(vec![stale.clone(), stale, snapshot_104()]))
+        .retry_delay(Duration::from_millis(50));
+
+    book.process(&messages[0]).unwrap();
+    book.process(&messages[1]).unwrap();
+
+    // The gap can't be filled from a snapshot older than the gap
+    let event = book.process(&messages[2]).unwrap().unwrap();
+    assert_eq!(event.snapshot_sequence, 100);
+    assert!(!event.synced);
+
+    // Nothing more is fetched until the delay has passed
+    assert_eq!(book.process(&messages[3]).unwrap(), None);
+    thread::sleep(Duration::from_millis(60));
+
+    let event = book.process(&messages[4]).unwrap().unwrap();
+    assert_eq!(event.gap, Some((103, 105)));
+    assert_eq!(event.snapshot_sequence, 104);
+    assert!(event.synced);
+    assert_eq!(book.book().sequence(), Some(106));
+    assert_eq!(book.process(&messages[5]).unwrap(), None);
+    assert_eq!(book.book().sequence(), Some(107));
+}
+
+#[test]
+fn heartbeat_reveals_gap() {
+    let mut book = FullBook::new("BTC-USD");
+    book.load_snapshot(&snapshot_100()).unwrap();
+
+    let heartbeat: Message = serde_json::from_str(r#"{"type": "heartbeat", "sequence": 100,
+        "last_trade_id": 6, "product_id": "BTC-USD", "time": "2018-06-01T12:00:00Z"}"#).unwrap();
+    assert_eq!(book.apply(&heartbeat).unwrap(), BookEvent::Ignored);
+
+    let heartbeat: Message = serde_json::from_str(r#"{"type": "heartbeat", "sequence": 102,
+        "last_trade_id": 6, "product_id": "BTC-USD", "time": "2018-06-01T12:00:01Z"}"#).unwrap();
+    assert_eq!(book.apply(&heartbeat).unwrap(), BookEvent::Gap { expected: 101, received: 102 });
+}
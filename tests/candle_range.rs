@@ -0,0 +1,98 @@
+extern crate chrono;
+extern crate gdax_client;
+
+use chrono::{DateTime, Duration, SecondsFormat, TimeZone, Utc};
+use gdax_client::{Method, PublicClient, Response};
+use gdax_client::public::{Candle, Granularity};
+use gdax_client::transport::MemoryTransport;
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2018, 6, 1, 0, 0, 0).unwrap()
+}
+
+fn path(from: DateTime<Utc>, to: DateTime<Utc>) -> String {
+    format!("/products/BTC-USD/candles?start={}&end={}&granularity=60",
+            from.to_rfc3339_opts(SecondsFormat::Secs, true),
+            to.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+// Candles for the given minutes after the start, newest first like the
+// exchange returns them.
+fn candles(minutes: &[i64]) -> String {
+    let rows: Vec<String> = minutes.iter()
+                                   .rev()
+                                   .map(|m| format!("[{}, 1.0, 2.0, 1.5, 1.5, 10.0]",
+                                                    (start() + Duration::minutes(*m)).timestamp()))
+                                   .collect();
+    format!("[{}]", rows.join(", "))
+}
+
+fn minute(m: i64) -> u64 {
+    (start() + Duration::minutes(m)).timestamp() as u64
+}
+
+#[test]
+fn granularity_seconds() {
+    assert_eq!(Granularity::FifteenMinutes.seconds(), 900);
+    assert_eq!(Granularity::from_seconds(21600), Some(Granularity::SixHours));
+    assert_eq!(Granularity::from_seconds(120), None);
+}
+
+#[test]
+fn splits_long_ranges_into_windows() {
+    let transport = MemoryTransport::new();
+    let minutes = |from: i64, to: i64| (from..to).collect::<Vec<_>>();
+    let at = |m: i64| start() + Duration::minutes(m);
+
+    // Three windows of at most 300 candles; the second repeats the last
+    // candle of the first and is missing ten minutes with no trades
+    transport.push_json(Method::Get, &path(at(0), at(299)), &candles(&minutes(0, 300)));
+    let mut second = minutes(299, 400);
+    second.extend(410..600);
+    transport.push_json(Method::Get, &path(at(300), at(599)), &candles(&second));
+    transport.push_json(Method::Get, &path(at(600), at(699)), &candles(&minutes(600, 690)));
+
+    let mut client = PublicClient::with_transport(transport.clone());
+    let range = client.get_historic_rates_range("BTC-USD", at(0), at(700), Granularity::OneMinute).unwrap();
+
+    assert_eq!(transport.remaining(), 0);
+    assert_eq!(range.candles.len(), 300 + 100 + 190 + 90);
+    assert!(range.candles.windows(2).all(|w| w[0].time < w[1].time));
+    assert_eq!(range.candles[0], Candle { time: minute(0), low: 1.0, high: 2.0, open: 1.5, close: 1.5, volume: 10.0 });
+    assert_eq!(range.gaps, vec![(minute(400), minute(410)), (minute(690), minute(700))]);
+}
+
+#[test]
+fn retries_when_rate_limited() {
+    let transport = MemoryTransport::new();
+    let from = start();
+    let to = start() + Duration::minutes(1);
+    transport.push_response(Method::Get, &path(from, from), Response {
+        value: (),
+        status: 429,
+        headers: vec![],
+        body: br#"{"message": "Slow down"}"#.to_vec()
+    });
+    transport.push_json(Method::Get, &path(from, from), &candles(&[0]));
+
+    let mut client = PublicClient::with_transport(transport.clone());
+    let range = client.get_historic_rates_range("BTC-USD", from, to, Granularity::OneMinute).unwrap();
+
+    assert_eq!(transport.requests().len(), 2);
+    assert_eq!(range.candles.len(), 1);
+    assert!(range.gaps.is_empty());
+}
+
+#[test]
+fn unaligned_ranges_start_at_the_next_candle() {
+    let transport = MemoryTransport::new();
+    let at = |m: i64| start() + Duration::minutes(m);
+    transport.push_json(Method::Get, &path(at(1), at(2)), &candles(&[0, 1, 2]));
+
+    let mut client = PublicClient::with_transport(transport.clone());
+    let from = start() + Duration::seconds(30);
+    let range = client.get_historic_rates_range("BTC-USD", from, at(3), Granularity::OneMinute).unwrap();
+
+    assert_eq!(range.candles.iter().map(|c| c.time).collect::<Vec<_>>(), vec![minute(1), minute(2)]);
+    assert!(range.gaps.is_empty());
+}
@@ -0,0 +1,112 @@
+extern crate chrono;
+extern crate gdax_client;
+extern crate serde_json;
+
+use chrono::{DateTime, TimeZone, Utc};
+use gdax_client::Side;
+use gdax_client::candles::{CandleBuilder, CandleEvent};
+use gdax_client::feed::Message;
+use gdax_client::public::{Candle, Trade};
+
+fn at(secs: i64) -> DateTime<Utc> {
+    Utc.timestamp_opt(1_527_854_400 + secs, 0).unwrap()
+}
+
+fn trade(trade_id: u64, secs: i64, price: &str, size: &str) -> Trade {
+    Trade {
+        time: at(secs),
+        trade_id,
+        price: price.to_owned(),
+        size: size.to_owned(),
+        side: Side::Buy
+    }
+}
+
+fn candle(time: u64, low: f64, high: f64, open: f64, close: f64, volume: f64) -> Candle {
+    Candle { time, low, high, open, close, volume }
+}
+
+const START: u64 = 1_527_854_400;
+
+#[test]
+fn aggregates_trades_into_bars() {
+    let mut builder = CandleBuilder::new("BTC-USD", 60);
+
+    assert_eq!(builder.push_trade(&trade(1, 5, "100.00", "1.0")).unwrap(),
+               vec![CandleEvent::Updated(candle(START, 100.0, 100.0, 100.0, 100.0, 1.0))]);
+    builder.push_trade(&trade(2, 20, "102.50", "0.5")).unwrap();
+    assert_eq!(builder.push_trade(&trade(3, 59, "99.00", "0.25")).unwrap(),
+               vec![CandleEvent::Updated(candle(START, 99.0, 102.5, 100.0, 99.0, 1.75))]);
+
+    // The first trade of the next minute closes the bar
+    assert_eq!(builder.push_trade(&trade(4, 60, "98.00", "2.0")).unwrap(), vec![
+        CandleEvent::Closed(candle(START, 99.0, 102.5, 100.0, 99.0, 1.75)),
+        CandleEvent::Updated(candle(START + 60, 98.0, 98.0, 98.0, 98.0, 2.0)),
+    ]);
+}
+
+#[test]
+fn supports_sub_minute_and_multi_hour_granularities() {
+    let mut seconds = CandleBuilder::new("BTC-USD", 5);
+    let mut hours = CandleBuilder::new("BTC-USD", 4 * 3600);
+    let trades = [trade(1, 3, "100.00", "1.0"), trade(2, 7, "101.00", "1.0"), trade(3, 3 * 3600, "103.00", "1.0")];
+
+    let closed: Vec<Candle> = trades.iter()
+                                    .flat_map(|t| seconds.push_trade(t).unwrap())
+                                    .filter_map(|e| match e {
+                                        CandleEvent::Closed(c) => Some(c),
+                                        CandleEvent::Updated(_) => None
+                                    })
+                                    .collect();
+    // No bars for the empty intervals in between
+    assert_eq!(closed, vec![candle(START, 100.0, 100.0, 100.0, 100.0, 1.0),
+                            candle(START + 5, 101.0, 101.0, 101.0, 101.0, 1.0)]);
+
+    for t in &trades {
+        hours.push_trade(t).unwrap();
+    }
+    // 1527854400 is a multiple of four hours, so everything is one bar
+    assert_eq!(hours.current(), Some(&candle(START, 100.0, 103.0, 100.0, 103.0, 3.0)));
+}
+
+#[test]
+fn ignores_duplicate_and_late_trades() {
+    let mut builder = CandleBuilder::new("BTC-USD", 60);
+    builder.push_trade(&trade(1, 5, "100.00", "1.0")).unwrap();
+    builder.push_trade(&trade(2, 65, "101.00", "1.0")).unwrap();
+
+    assert!(builder.push_trade(&trade(2, 65, "101.00", "1.0")).unwrap().is_empty());
+    assert!(builder.push(at(30), 90.0, 1.0).is_empty());
+    assert_eq!(builder.current(), Some(&candle(START + 60, 101.0, 101.0, 101.0, 101.0, 1.0)));
+}
+
+#[test]
+fn closes_bars_through_quiet_periods() {
+    let mut builder = CandleBuilder::new("BTC-USD", 60);
+    builder.push_trade(&trade(1, 5, "100.00", "1.0")).unwrap();
+
+    assert_eq!(builder.close_until(at(59)), None);
+    assert_eq!(builder.close_until(at(61)), Some(candle(START, 100.0, 100.0, 100.0, 100.0, 1.0)));
+    assert_eq!(builder.current(), None);
+
+    // A straggler for the closed bar doesn't reopen it
+    assert!(builder.push_trade(&trade(2, 50, "100.00", "1.0")).unwrap().is_empty());
+}
+
+#[test]
+fn builds_from_match_messages() {
+    let mut builder = CandleBuilder::new("BTC-USD", 60);
+    let message = |product_id: &str, kind: &str, trade_id: u64| -> Message {
+        serde_json::from_str(&format!(r#"{{"type": "{}", "product_id": "{}", "sequence": 50,
+            "time": "2018-06-01T12:00:10Z", "trade_id": {},
+            "maker_order_id": "00000000-0000-0000-0000-00000000000a",
+            "taker_order_id": "00000000-0000-0000-0000-00000000000b",
+            "side": "sell", "size": "0.5", "price": "7500.00"}}"#, kind, product_id, trade_id)).unwrap()
+    };
+
+    builder.apply(&message("BTC-USD", "last_match", 10)).unwrap();
+    assert!(builder.apply(&message("ETH-USD", "match", 11)).unwrap().is_empty());
+    let events = builder.apply(&message("BTC-USD", "match", 12)).unwrap();
+
+    assert_eq!(events, vec![CandleEvent::Updated(candle(START, 7500.0, 7500.0, 7500.0, 7500.0, 1.0))]);
+}
@@ -0,0 +1,159 @@
+extern crate gdax_client;
+extern crate serde_json;
+
+use gdax_client::{NewOrder, Side};
+use gdax_client::mock::MockExchange;
+use serde_json::Value;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn command(args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_gdax"));
+    command.args(args)
+           .env_remove("CB_KEY")
+           .env_remove("CB_SECRET")
+           .env_remove("CB_PASSPHRASE")
+           .env_remove("GDAX_CREDENTIALS")
+           .env_remove("GDAX_API_URL")
+           .env("HOME", "/nonexistent");
+    command
+}
+
+fn gdax(args: &[&str]) -> Output {
+    command(args).output().unwrap()
+}
+
+// Runs against `exchange` with its credentials, answering any prompt with
+// `input`
+fn gdax_against(exchange: &MockExchange, args: &[&str], input: &str) -> Output {
+    let credentials = exchange.credentials();
+    let mut child = command(args).env("GDAX_API_URL", exchange.url())
+                                 .env("CB_KEY", credentials.key())
+                                 .env("CB_SECRET", credentials.secret())
+                                 .env("CB_PASSPHRASE", credentials.passphrase())
+                                 .stdin(Stdio::piped())
+                                 .stdout(Stdio::piped())
+                                 .stderr(Stdio::piped())
+                                 .spawn()
+                                 .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn json(output: &Output) -> Value {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+#[test]
+fn help_lists_every_command() {
+    let output = gdax(&["--help"]);
+    assert!(output.status.success());
+
+    let usage = String::from_utf8(output.stdout).unwrap();
+    for command in &["products", "book", "ticker", "trades", "candles", "accounts", "ledger", "holds",
+                     "orders", "place", "cancel"] {
+        assert!(usage.contains(&format!("\n    {} ", command)), "{} missing from usage", command);
+    }
+}
+
+#[test]
+fn private_commands_need_credentials() {
+    let output = gdax(&["accounts"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no credentials"));
+}
+
+#[test]
+fn bad_arguments_are_rejected_before_any_request() {
+    for args in &[&["frobnicate"][..], &["place", "hold", "BTC-USD", "1"], &["book", "BTC-USD", "--level", "4"],
+                  &["candles", "BTC-USD", "yesterday", "today"]] {
+        let output = gdax(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("gdax: "));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn credentials_fd_must_be_an_open_inherited_descriptor() {
+    for fd in &["0", "1", "2", "-1"] {
+        let output = gdax(&["accounts", "--credentials-fd", fd]);
+        assert_eq!(output.status.code(), Some(2), "{}", fd);
+    }
+    let output = gdax(&["accounts", "--credentials-fd", "0"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("can't be stdin, stdout or stderr"));
+
+    let output = gdax(&["accounts", "--credentials-fd", "987"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("can't load credentials"));
+}
+
+#[test]
+fn accounts_lists_balances() {
+    let exchange = MockExchange::start().unwrap();
+    exchange.deposit("USD", 1000.0);
+
+    let accounts = json(&gdax_against(&exchange, &["--json", "accounts"], ""));
+    let usd = accounts.as_array().unwrap().iter().find(|a| a["currency"] == "USD").unwrap();
+    assert_eq!(usd["balance"].as_f64(), Some(1000.0));
+
+    let table = gdax_against(&exchange, &["accounts"], "");
+    let table = String::from_utf8(table.stdout).unwrap();
+    assert!(table.lines().next().unwrap().contains("currency"));
+    assert!(table.lines().any(|line| line.contains("USD") && line.contains("1000")));
+}
+
+#[test]
+fn place_asks_for_confirmation() {
+    let exchange = MockExchange::start().unwrap();
+    exchange.deposit("USD", 1000.0);
+    let place = ["--json", "place", "buy", "BTC-USD", "2", "--price", "100"];
+
+    let declined = gdax_against(&exchange, &place, "n\n");
+    assert_eq!(declined.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&declined.stderr).contains("order not placed"));
+    assert_eq!(exchange.account("USD").unwrap().hold, 0.0);
+
+    let placed = json(&gdax_against(&exchange, &place, "y\n"));
+    assert!(placed[0].is_string());
+    assert_eq!(exchange.account("USD").unwrap().hold, 200.0);
+
+    // --yes places without asking
+    let mut unattended = place.to_vec();
+    unattended.push("--yes");
+    json(&gdax_against(&exchange, &unattended, ""));
+    assert_eq!(exchange.account("USD").unwrap().hold, 400.0);
+
+    // Rejections are reported in words
+    let rejected = gdax_against(&exchange, &["place", "buy", "BTC-USD", "20", "--price", "100", "--yes"], "");
+    assert_eq!(rejected.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&rejected.stderr).lines().last(), Some("gdax: Insufficient funds (HTTP 400)"));
+}
+
+#[test]
+fn cancel_one_or_all_orders() {
+    let exchange = MockExchange::start().unwrap();
+    exchange.deposit("BTC", 3.0);
+    exchange.place_order(&NewOrder::limit(Side::Buy, "BTC-USD", 1.0, 90.0)).unwrap();
+
+    let place = |price: &str| {
+        let placed = json(&gdax_against(&exchange, &["--json", "place", "sell", "BTC-USD", "1", "--price", price,
+                                                     "--yes"], ""));
+        placed[0].as_str().unwrap().to_owned()
+    };
+    let first = place("110");
+    let second = place("120");
+    assert_eq!(exchange.account("BTC").unwrap().hold, 2.0);
+
+    let canceled = json(&gdax_against(&exchange, &["--json", "cancel", &first], ""));
+    assert_eq!(canceled, serde_json::json!([first]));
+    assert_eq!(exchange.account("BTC").unwrap().hold, 1.0);
+
+    let canceled = json(&gdax_against(&exchange, &["--json", "cancel", "--all", "--product", "BTC-USD"], ""));
+    assert_eq!(canceled, serde_json::json!([second]));
+    assert_eq!(exchange.account("BTC").unwrap().hold, 0.0);
+
+    let missing = gdax_against(&exchange, &["cancel", &first], "");
+    assert_eq!(missing.status.code(), Some(1));
+}
@@ -0,0 +1,45 @@
+extern crate gdax_client;
+extern crate serde_json;
+
+use gdax_client::{Method, PrivateClient};
+use gdax_client::private::{EntryDetails, EntryType, LedgerEntry};
+use gdax_client::transport::MemoryTransport;
+use serde_json::Value;
+
+#[test]
+fn convert_posts_the_conversion_and_decodes_the_result() {
+    let transport = MemoryTransport::new();
+    transport.push_json(Method::Post, "/conversions", r#"{
+        "id": "8942caee-f9d5-4600-a894-4811268545db",
+        "amount": "10000.00",
+        "from_account_id": "7849cc79-8b01-4793-9345-bc6b5f08acce",
+        "to_account_id": "105c3e58-0898-4106-8283-dc5781cda07b",
+        "from": "USD",
+        "to": "USDC"
+    }"#);
+    let mut client = PrivateClient::with_transport("key", "c2VjcmV0", "passphrase", transport.clone()).unwrap();
+
+    let conversion = client.convert("USD", "USDC", 10000.0).unwrap();
+    assert_eq!(conversion.id.to_string(), "8942caee-f9d5-4600-a894-4811268545db");
+    assert_eq!((conversion.amount, conversion.from.as_str(), conversion.to.as_str()), (10000.0, "USD", "USDC"));
+    assert_eq!(conversion.to_account_id.to_string(), "105c3e58-0898-4106-8283-dc5781cda07b");
+
+    let request = &transport.requests()[0];
+    assert_eq!((request.method, request.path()), (Method::Post, "/conversions"));
+    let body: Value = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(body, serde_json::json!({"from": "USD", "to": "USDC", "amount": 10000.0}));
+}
+
+#[test]
+fn conversion_ledger_entries_carry_the_conversion_id() {
+    let entry: LedgerEntry = serde_json::from_str(r#"{
+        "id": 7, "created_at": "2018-06-01T10:00:00Z", "amount": -10000.00, "balance": 0.00,
+        "type": "conversion", "details": {"conversion_id": "8942caee-f9d5-4600-a894-4811268545db"}
+    }"#).unwrap();
+    assert_eq!(entry.entry_type, EntryType::Conversion);
+    match entry.details {
+        Some(EntryDetails::Conversion(ref details)) =>
+            assert_eq!(details.conversion_id.to_string(), "8942caee-f9d5-4600-a894-4811268545db"),
+        ref other => panic!("unexpected details {:?}", other)
+    }
+}
@@ -0,0 +1,103 @@
+extern crate gdax_client;
+
+use gdax_client::{Credentials, Error, PrivateClient};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const SECRET: &str = "c2VjcmV0";
+
+fn write(name: &str, contents: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("gdax-credentials-{}-{}", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn secret_is_validated_eagerly() {
+    match Credentials::new("key", "not base64!", "passphrase") {
+        Err(Error::InvalidSecretKey) => {}
+        other => panic!("expected an invalid secret, got {:?}", other)
+    }
+}
+
+#[test]
+fn debug_leaves_out_secrets() {
+    let credentials = Credentials::new("key", SECRET, "hunter2").unwrap();
+    let debug = format!("{:?}", credentials);
+    assert!(debug.contains("key"));
+    assert!(!debug.contains(SECRET));
+    assert!(!debug.contains("hunter2"));
+}
+
+#[test]
+fn reads_profiles_from_toml() {
+    let path = write("profiles.toml", &format!(r#"
+        [default]
+        key = "live-key"
+        secret = "{0}"
+        passphrase = "live"
+
+        [sandbox]
+        key = "sandbox-key"
+        secret = "{0}"
+        passphrase = "sandbox"
+    "#, SECRET));
+
+    assert_eq!(Credentials::from_file(&path, None).unwrap().key(), "live-key");
+    let sandbox = Credentials::from_file(&path, Some("sandbox")).unwrap();
+    assert_eq!(sandbox.key(), "sandbox-key");
+    assert_eq!(sandbox.passphrase(), "sandbox");
+    match Credentials::from_file(&path, Some("staging")) {
+        Err(Error::MissingCredentials(why)) => assert_eq!(why, r#"no profile named "staging""#),
+        other => panic!("expected a missing profile, got {:?}", other)
+    }
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn reads_json_with_or_without_profiles() {
+    let single = write("single.json", &format!(r#"{{"key": "k", "secret": "{}", "passphrase": "p"}}"#, SECRET));
+    assert_eq!(Credentials::from_file(&single, None).unwrap().key(), "k");
+    assert!(Credentials::from_file(&single, Some("sandbox")).is_err());
+
+    let profiles = write("profiles.json", &format!(r#"{{"sandbox": {{"key": "s", "secret": "{}", "passphrase": "p"}}}}"#,
+                                                    SECRET));
+    assert_eq!(Credentials::from_file(&profiles, Some("sandbox")).unwrap().key(), "s");
+
+    fs::remove_file(single).unwrap();
+    fs::remove_file(profiles).unwrap();
+}
+
+#[test]
+fn reads_from_a_pipe() {
+    let toml = format!("key = \"k\"\nsecret = \"{}\"\npassphrase = \"p\"\n", SECRET);
+    let credentials = Credentials::from_reader(toml.as_bytes(), None).unwrap();
+    assert_eq!((credentials.key(), credentials.secret(), credentials.passphrase()), ("k", SECRET, "p"));
+
+    // A bad secret in a file is caught just the same
+    assert!(Credentials::from_reader(&b"key = \"k\"\nsecret = \"!\"\npassphrase = \"p\"\n"[..], None).is_err());
+}
+
+#[test]
+fn client_rejects_bad_secret_at_construction() {
+    match PrivateClient::new("key", "not base64!", "passphrase") {
+        Err(Error::InvalidSecretKey) => {}
+        Err(e) => panic!("expected an invalid secret, got {:?}", e),
+        Ok(_) => panic!("expected an invalid secret")
+    }
+}
+
+#[test]
+fn client_debug_leaves_out_secrets() {
+    let client = PrivateClient::new("key", SECRET, "hunter2").unwrap();
+    let debug = format!("{:?}", client);
+    assert!(debug.contains("key"));
+    assert!(!debug.contains("hunter2"));
+    // Neither encoded nor decoded
+    assert!(!debug.contains(SECRET));
+    assert!(!debug.contains("115, 101, 99"));
+
+    let debug = format!("{:?}", Credentials::new("key", SECRET, "hunter2").unwrap().client());
+    assert!(!debug.contains("hunter2"));
+}
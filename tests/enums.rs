@@ -0,0 +1,90 @@
+extern crate gdax_client;
+extern crate serde_json;
+
+use gdax_client::Side;
+use gdax_client::private::{EntryDetails, EntryType, HoldType, Ledger};
+
+#[test]
+fn side_accepts_known_values() {
+    assert_eq!(serde_json::from_str::<Side>("\"buy\"").unwrap(), Side::Buy);
+    assert_eq!(serde_json::from_str::<Side>("\"SELL\"").unwrap(), Side::Sell);
+}
+
+#[test]
+fn side_rejects_unknown_values() {
+    let err = serde_json::from_str::<Side>("\"short\"").unwrap_err();
+    let message = err.to_string();
+    assert!(message.contains("short"), "{}", message);
+    assert!(message.contains("\"buy\" or \"sell\""), "{}", message);
+}
+
+#[test]
+fn side_rejects_non_strings() {
+    let err = serde_json::from_str::<Side>("1").unwrap_err();
+    assert!(err.to_string().contains("\"buy\" or \"sell\""), "{}", err);
+}
+
+#[test]
+fn entry_type_keeps_unknown_values() {
+    match serde_json::from_str::<EntryType>("\"rebate\"").unwrap() {
+        EntryType::Rebate => {}
+        other => panic!("unexpected entry type {:?}", other)
+    }
+    match serde_json::from_str::<EntryType>("\"margin_interest\"").unwrap() {
+        EntryType::Unknown(ref t) if t == "margin_interest" => {}
+        other => panic!("unexpected entry type {:?}", other)
+    }
+}
+
+#[test]
+fn entry_type_rejects_non_strings() {
+    let err = serde_json::from_str::<EntryType>("null").unwrap_err();
+    assert!(err.to_string().contains("ledger entry type"), "{}", err);
+}
+
+#[test]
+fn hold_type_keeps_unknown_values() {
+    match serde_json::from_str::<HoldType>("\"order\"").unwrap() {
+        HoldType::Order => {}
+        other => panic!("unexpected hold type {:?}", other)
+    }
+    match serde_json::from_str::<HoldType>("\"withdrawal\"").unwrap() {
+        HoldType::Unknown(ref t) if t == "withdrawal" => {}
+        other => panic!("unexpected hold type {:?}", other)
+    }
+}
+
+#[test]
+fn hold_type_rejects_non_strings() {
+    let err = serde_json::from_str::<HoldType>("[]").unwrap_err();
+    assert!(err.to_string().contains("hold type"), "{}", err);
+}
+
+#[test]
+fn ledger_page_survives_unknown_entries() {
+    let ledger: Ledger = serde_json::from_str(r#"[
+        {"id": 1, "created_at": "2018-06-01T10:00:00Z", "amount": 1.5, "balance": 1.5,
+         "type": "transfer",
+         "details": {"transfer_id": "1b44d4f6-b6d4-4b2c-a5bb-6b21c1b80a1b", "transfer_type": "deposit"}},
+        {"id": 2, "created_at": "2018-06-01T10:05:00Z", "amount": -0.1, "balance": 1.4,
+         "type": "margin_interest",
+         "details": {"loan_id": 7}},
+        {"id": 3, "created_at": "2018-06-01T10:10:00Z", "amount": -0.01, "balance": 1.39,
+         "type": "fee",
+         "details": {"unexpected": true}}
+    ]"#).unwrap();
+
+    assert_eq!(ledger.len(), 3);
+    match ledger[0].details {
+        Some(EntryDetails::Transfer(ref details)) => assert_eq!(details.transfer_type, "deposit"),
+        ref other => panic!("unexpected details {:?}", other)
+    }
+    match ledger[1].details {
+        Some(EntryDetails::Unknown(_)) => {}
+        ref other => panic!("unexpected details {:?}", other)
+    }
+    match ledger[2].details {
+        Some(EntryDetails::Unknown(_)) => {}
+        ref other => panic!("unexpected details {:?}", other)
+    }
+}
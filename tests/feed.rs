@@ -0,0 +1,191 @@
+extern crate futures;
+extern crate gdax_client;
+extern crate serde_json;
+extern crate tungstenite;
+
+use futures::{executor, Async};
+use futures::executor::Notify;
+use gdax_client::Side;
+use gdax_client::feed::{Channel, DoneReason, Feed, Message, OrderEvent, OrderType};
+use std::net::TcpListener;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+fn decode(json: &str) -> Message {
+    serde_json::from_str(json).unwrap()
+}
+
+#[test]
+fn decodes_market_data_messages() {
+    match decode(r#"{"type": "heartbeat", "sequence": 90, "last_trade_id": 20,
+                     "product_id": "BTC-USD", "time": "2014-11-07T08:19:28.464459Z"}"#) {
+        Message::Heartbeat(h) => assert_eq!(h.last_trade_id, 20),
+        other => panic!("unexpected message {:?}", other)
+    }
+
+    match decode(r#"{"type": "ticker", "trade_id": 20153558, "sequence": 3262786978,
+                     "time": "2017-09-02T17:05:49.250000Z", "product_id": "BTC-USD",
+                     "price": "4388.01000000", "side": "buy", "last_size": "0.03000000",
+                     "best_bid": "4388", "best_ask": "4388.01", "volume_24h": "1000.0"}"#) {
+        Message::Ticker(t) => {
+            let tick = t.to_tick().unwrap();
+            assert_eq!(tick.trade_id, 20153558);
+            assert_eq!(tick.size, "0.03000000");
+        }
+        other => panic!("unexpected message {:?}", other)
+    }
+
+    match decode(r#"{"type": "snapshot", "product_id": "BTC-EUR",
+                     "bids": [["6500.11", "0.45054140"]], "asks": [["6500.15", "0.57753524"]]}"#) {
+        Message::Snapshot(s) => assert_eq!(s.asks[0].price, "6500.15"),
+        other => panic!("unexpected message {:?}", other)
+    }
+
+    match decode(r#"{"type": "l2update", "product_id": "BTC-EUR",
+                     "time": "2019-08-14T20:42:27.265Z", "changes": [["buy", "6500.09", "0.84702376"]]}"#) {
+        Message::L2Update(u) => assert_eq!(u.changes[0].side, Side::Buy),
+        other => panic!("unexpected message {:?}", other)
+    }
+}
+
+#[test]
+fn decodes_full_channel_messages() {
+    match decode(r#"{"type": "received", "time": "2014-11-07T08:19:27.028459Z",
+                     "product_id": "BTC-USD", "sequence": 10,
+                     "order_id": "d50ec984-77a8-460a-b958-66f114b0de9b",
+                     "size": "1.34", "price": "502.1", "side": "buy", "order_type": "limit"}"#) {
+        Message::Received(r) => assert_eq!(r.order_type, OrderType::Limit),
+        other => panic!("unexpected message {:?}", other)
+    }
+
+    match decode(r#"{"type": "done", "time": "2014-11-07T08:19:27.028459Z",
+                     "product_id": "BTC-USD", "sequence": 10, "price": "200.2",
+                     "order_id": "d50ec984-77a8-460a-b958-66f114b0de9b",
+                     "reason": "filled", "side": "sell", "remaining_size": "0"}"#) {
+        Message::Done(d) => assert_eq!(d.reason, DoneReason::Filled),
+        other => panic!("unexpected message {:?}", other)
+    }
+
+    match decode(r#"{"type": "match", "trade_id": 10, "sequence": 50,
+                     "maker_order_id": "ac928c66-ca53-498f-9c13-a110027a60e8",
+                     "taker_order_id": "132fb6ae-456b-4654-b4e0-d681ac05cea1",
+                     "time": "2014-11-07T08:19:27.028459Z", "product_id": "BTC-USD",
+                     "size": "5.23512", "price": "400.23", "side": "sell"}"#) {
+        Message::Match(m) => {
+            let trade = m.to_trade();
+            assert_eq!(trade.side, Side::Sell);
+            assert_eq!(trade.price, "400.23");
+        }
+        other => panic!("unexpected message {:?}", other)
+    }
+}
+
+#[test]
+fn keeps_unknown_message_types() {
+    let message = decode(r#"{"type": "auction", "product_id": "BTC-USD"}"#);
+    match message {
+        Message::Unknown(ref v) => assert_eq!(v["product_id"], "BTC-USD"),
+        ref other => panic!("unexpected message {:?}", other)
+    }
+    assert_eq!(message.message_type(), "auction");
+}
+
+#[test]
+fn rejects_malformed_known_messages() {
+    assert!(serde_json::from_str::<Message>(r#"{"type": "match", "trade_id": "x"}"#).is_err());
+    assert!(serde_json::from_str::<Message>(r#"{"product_id": "BTC-USD"}"#).is_err());
+}
+
+#[test]
+fn messages_round_trip() {
+    let message = decode(r#"{"type": "open", "time": "2014-11-07T08:19:27.028459Z",
+                             "product_id": "BTC-USD", "sequence": 10,
+                             "order_id": "d50ec984-77a8-460a-b958-66f114b0de9b",
+                             "price": "200.2", "remaining_size": "1.00", "side": "sell"}"#);
+    let encoded = serde_json::to_string(&message).unwrap();
+    assert_eq!(decode(&encoded), message);
+}
+
+#[test]
+fn only_user_channel_messages_are_order_events() {
+    let public = decode(r#"{"type": "open", "time": "2014-11-07T08:19:27.028459Z",
+                            "product_id": "BTC-USD", "sequence": 10,
+                            "order_id": "d50ec984-77a8-460a-b958-66f114b0de9b",
+                            "price": "200.2", "remaining_size": "1.00", "side": "sell"}"#);
+    assert!(public.into_order_event().is_none());
+
+    let ours = decode(r#"{"type": "open", "time": "2014-11-07T08:19:27.028459Z",
+                          "product_id": "BTC-USD", "sequence": 10,
+                          "order_id": "d50ec984-77a8-460a-b958-66f114b0de9b",
+                          "price": "200.2", "remaining_size": "1.00", "side": "sell",
+                          "user_id": "5844eceecf7e803e259d0365",
+                          "profile_id": "765d1549-9660-4be2-97d4-fa2d65fa3352"}"#);
+    match ours.into_order_event() {
+        Some(OrderEvent::Open(ref open)) => assert_eq!(open.remaining_size, "1.00"),
+        other => panic!("unexpected event {:?}", other)
+    }
+
+    let taker = decode(r#"{"type": "match", "trade_id": 10, "sequence": 50,
+                           "maker_order_id": "ac928c66-ca53-498f-9c13-a110027a60e8",
+                           "taker_order_id": "132fb6ae-456b-4654-b4e0-d681ac05cea1",
+                           "time": "2014-11-07T08:19:27.028459Z", "product_id": "BTC-USD",
+                           "size": "5.23512", "price": "400.23", "side": "sell",
+                           "taker_user_id": "5844eceecf7e803e259d0365",
+                           "taker_profile_id": "765d1549-9660-4be2-97d4-fa2d65fa3352"}"#);
+    let event = taker.into_order_event().unwrap();
+    assert_eq!(event.order_id().to_string(), "132fb6ae-456b-4654-b4e0-d681ac05cea1");
+
+    let heartbeat = decode(r#"{"type": "heartbeat", "sequence": 90, "last_trade_id": 20,
+                               "product_id": "BTC-USD", "time": "2014-11-07T08:19:28.464459Z"}"#);
+    assert!(heartbeat.into_order_event().is_none());
+}
+
+// Counts the times a task is woken
+struct Wakes(Mutex<mpsc::Sender<()>>);
+
+impl Notify for Wakes {
+    fn notify(&self, _: usize) {
+        let _ = self.0.lock().unwrap().send(());
+    }
+}
+
+#[test]
+fn streams_without_blocking() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (go, wait) = mpsc::channel::<()>();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut socket = tungstenite::accept(stream).unwrap();
+        socket.read().unwrap();
+        wait.recv().unwrap();
+        socket.send(tungstenite::Message::Text(r#"{"type": "heartbeat", "sequence": 1, "last_trade_id": 1,
+            "product_id": "BTC-USD", "time": "2018-06-01T12:00:00Z"}"#.to_owned())).unwrap();
+        socket.close(None).unwrap();
+        let _ = socket.flush();
+    });
+
+    let (woken_tx, woken) = mpsc::channel();
+    let wakes = Arc::new(Wakes(Mutex::new(woken_tx)));
+    let feed = Feed::connect_to(&url, &["BTC-USD"], &[Channel::Heartbeat]).unwrap();
+    let mut stream = executor::spawn(feed.into_stream());
+
+    // Nothing has been sent yet, so polling returns straight away
+    assert!(stream.poll_stream_notify(&wakes, 0).unwrap().is_not_ready());
+    go.send(()).unwrap();
+
+    woken.recv_timeout(Duration::from_secs(5)).unwrap();
+    match stream.poll_stream_notify(&wakes, 0).unwrap() {
+        Async::Ready(Some(Message::Heartbeat(ref h))) => assert_eq!(h.sequence, 1),
+        other => panic!("expected the heartbeat, got {:?}", other)
+    }
+    loop {
+        match stream.poll_stream_notify(&wakes, 0).unwrap() {
+            Async::Ready(None) => break,
+            Async::NotReady => woken.recv_timeout(Duration::from_secs(5)).unwrap(),
+            other => panic!("expected the end of the stream, got {:?}", other)
+        }
+    }
+    server.join().unwrap();
+}
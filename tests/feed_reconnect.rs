@@ -0,0 +1,179 @@
+extern crate futures;
+extern crate gdax_client;
+extern crate serde_json;
+extern crate tungstenite;
+
+use futures::Stream;
+use gdax_client::Error;
+use gdax_client::feed::{Channel, FeedEvent, Message, ReconnectingFeed};
+use serde_json::Value;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tungstenite::WebSocket;
+
+fn heartbeat(sequence: u64) -> tungstenite::Message {
+    tungstenite::Message::Text(format!(r#"{{"type": "heartbeat", "sequence": {}, "last_trade_id": 1,
+        "product_id": "BTC-USD", "time": "2018-06-01T12:00:00Z"}}"#, sequence))
+}
+
+// Accepts a connection and returns it along with the subscribe request.
+fn accept(listener: &TcpListener) -> (WebSocket<TcpStream>, Value) {
+    let (stream, _) = listener.accept().unwrap();
+    let mut socket = tungstenite::accept(stream).unwrap();
+    let request = socket.read().unwrap();
+    let request = serde_json::from_str(request.to_text().unwrap()).unwrap();
+    (socket, request)
+}
+
+// A local stand-in for the feed server that drops the first connection,
+// goes silent on the second, and behaves on the third.
+fn serve(listener: TcpListener, requests: mpsc::Sender<Value>) {
+    let (mut socket, request) = accept(&listener);
+    requests.send(request).unwrap();
+    socket.send(heartbeat(1)).unwrap();
+    socket.close(None).unwrap();
+    let _ = socket.flush();
+    drop(socket);
+
+    let (mut socket, request) = accept(&listener);
+    requests.send(request).unwrap();
+    socket.send(heartbeat(2)).unwrap();
+    // Stop sending heartbeats but keep the connection open
+    thread::sleep(Duration::from_millis(500));
+    drop(socket);
+
+    let (mut socket, request) = accept(&listener);
+    requests.send(request).unwrap();
+    socket.send(heartbeat(3)).unwrap();
+    thread::sleep(Duration::from_millis(100));
+}
+
+fn without_message_contents(event: &FeedEvent) -> String {
+    match *event {
+        FeedEvent::Message(Message::Heartbeat(ref h)) => format!("heartbeat {}", h.sequence),
+        FeedEvent::Message(ref m) => m.message_type().to_owned(),
+        FeedEvent::Connected => "connected".to_owned(),
+        FeedEvent::Disconnected { ref reason } => format!("disconnected: {}", reason),
+        FeedEvent::Reconnecting { attempt, .. } => format!("reconnecting {}", attempt)
+    }
+}
+
+#[test]
+fn reconnects_and_resubscribes() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (tx, requests) = mpsc::channel();
+    let server = thread::spawn(move || serve(listener, tx));
+
+    let mut feed = ReconnectingFeed::with_url(&url, &["BTC-USD"], &[Channel::Ticker])
+        .heartbeat_timeout(Duration::from_millis(200))
+        .backoff(Duration::from_millis(10), Duration::from_millis(50));
+
+    let events: Vec<String> = feed.by_ref()
+                                  .take(10)
+                                  .map(|e| without_message_contents(&e.unwrap()))
+                                  .collect();
+    assert_eq!(events, vec![
+        "connected",
+        "heartbeat 1",
+        "disconnected: connection closed",
+        "reconnecting 1",
+        "connected",
+        "heartbeat 2",
+        "disconnected: heartbeat timeout",
+        "reconnecting 1",
+        "connected",
+        "heartbeat 3",
+    ]);
+    assert!(feed.is_connected());
+    assert!(feed.last_heartbeat().is_some());
+    feed.close().unwrap();
+    server.join().unwrap();
+
+    // Every connection restored the same subscription, plus heartbeats
+    let requests: Vec<Value> = requests.iter().collect();
+    assert_eq!(requests.len(), 3);
+    for request in &requests {
+        assert_eq!(request["type"], "subscribe");
+        assert_eq!(request["product_ids"], serde_json::json!(["BTC-USD"]));
+        assert_eq!(request["channels"], serde_json::json!(["ticker", "heartbeat"]));
+    }
+}
+
+#[test]
+fn streams_across_reconnects() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (tx, _requests) = mpsc::channel();
+    let server = thread::spawn(move || serve(listener, tx));
+
+    let stream = ReconnectingFeed::with_url(&url, &["BTC-USD"], &[Channel::Ticker])
+        .heartbeat_timeout(Duration::from_millis(200))
+        .backoff(Duration::from_millis(10), Duration::from_millis(50))
+        .into_stream();
+
+    let events: Vec<String> = stream.wait()
+                                    .take(10)
+                                    .map(|e| without_message_contents(&e.unwrap()))
+                                    .collect();
+    assert_eq!(events, vec![
+        "connected",
+        "heartbeat 1",
+        "disconnected: connection closed",
+        "reconnecting 1",
+        "connected",
+        "heartbeat 2",
+        "disconnected: heartbeat timeout",
+        "reconnecting 1",
+        "connected",
+        "heartbeat 3",
+    ]);
+    server.join().unwrap();
+}
+
+#[test]
+fn gives_up_after_max_attempts() {
+    // Bind and drop a listener to find a port nothing is listening on
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let url = format!("ws://127.0.0.1:{}", port);
+
+    let mut feed = ReconnectingFeed::with_url(&url, &["BTC-USD"], &[Channel::Ticker])
+        .backoff(Duration::from_millis(1), Duration::from_millis(5))
+        .max_attempts(2);
+
+    let mut reconnects = 0;
+    loop {
+        match feed.next_event() {
+            Ok(Some(FeedEvent::Reconnecting { .. })) => reconnects += 1,
+            Ok(other) => panic!("unexpected event {:?}", other),
+            Err(_) => break
+        }
+    }
+    assert_eq!(reconnects, 2);
+    assert!(feed.next_event().unwrap().is_none());
+}
+
+#[test]
+fn server_errors_close_the_feed() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let server = thread::spawn(move || {
+        let (mut socket, _) = accept(&listener);
+        socket.send(tungstenite::Message::Text(r#"{"type": "error", "message": "Failed to subscribe",
+            "reason": "BTC-XYZ is not a valid product"}"#.to_owned())).unwrap();
+        let _ = socket.read();
+    });
+
+    let mut feed = ReconnectingFeed::with_url(&url, &["BTC-XYZ"], &[Channel::Ticker])
+        .backoff(Duration::from_millis(1), Duration::from_millis(5));
+    assert_eq!(feed.next_event().unwrap(), Some(FeedEvent::Connected));
+    match feed.next_event() {
+        Err(Error::Api(e)) => assert_eq!(e.message, "Failed to subscribe"),
+        other => panic!("expected an API error, got {:?}", other)
+    }
+    assert!(!feed.is_connected());
+    assert!(feed.next_event().unwrap().is_none());
+    server.join().unwrap();
+}
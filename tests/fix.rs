@@ -0,0 +1,358 @@
+extern crate gdax_client;
+extern crate uuid;
+
+use gdax_client::{Credentials, NewOrder, Side, Size};
+use gdax_client::fix::{msg_type, tag, Event, ExecType, Message, Session};
+use gdax_client::signer::HmacSigner;
+use uuid::Uuid;
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const ORDER_ID: &str = "00000000-0000-0000-0000-00000000000a";
+
+// A stand-in for the gateway's side of the session
+struct Acceptor {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    next_out: u64
+}
+
+impl Acceptor {
+    fn read(&mut self) -> Message {
+        loop {
+            if let Some((message, len)) = Message::decode(&self.buffer).unwrap() {
+                self.buffer.drain(..len);
+                return message;
+            }
+            let mut chunk = [0u8; 1024];
+            let n = self.stream.read(&mut chunk).unwrap();
+            assert!(n > 0, "the session closed the connection");
+            self.buffer.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    fn stamp(seq: u64, message: Message) -> Vec<u8> {
+        let mut stamped = Message::new(message.msg_type()).with(tag::SENDER_COMP_ID, "Coinbase")
+                                                          .with(tag::TARGET_COMP_ID, "key")
+                                                          .with(tag::MSG_SEQ_NUM, seq)
+                                                          .with(tag::SENDING_TIME, "20180601-12:00:00.000");
+        for (t, value) in message.fields().iter().skip(1) {
+            stamped = stamped.with(*t, value);
+        }
+        stamped.encode()
+    }
+
+    fn send_as(&mut self, seq: u64, message: Message) {
+        self.stream.write_all(&Acceptor::stamp(seq, message)).unwrap();
+    }
+
+    fn send(&mut self, message: Message) {
+        let seq = self.next_out;
+        self.next_out += 1;
+        self.send_as(seq, message);
+    }
+
+    fn accept_logon(&mut self) {
+        let logon = self.read();
+        assert_eq!(logon.msg_type(), msg_type::LOGON);
+        assert_eq!(logon.get(tag::MSG_SEQ_NUM), Some("1"));
+        assert_eq!(logon.get(tag::PASSWORD), Some("passphrase"));
+        assert_eq!(logon.get(tag::HEART_BT_INT), Some("30"));
+
+        let prehash = [logon.get(tag::SENDING_TIME).unwrap(), "A", "1", "key", "Coinbase", "passphrase"].join("\x01");
+        let signer = HmacSigner::new("key", "c2VjcmV0", "passphrase").unwrap();
+        assert_eq!(logon.get(tag::RAW_DATA), Some(signer.signature(&prehash).as_str()));
+
+        self.send(Message::new(msg_type::LOGON).with(tag::ENCRYPT_METHOD, 0).with(tag::HEART_BT_INT, 30));
+    }
+}
+
+// Runs `gateway` against a logged on session, polling the session until
+// the gateway is done, and returns the session with the events it reported
+fn run<F>(gateway: F, client: fn(&mut Session<TcpStream>)) -> (Session<TcpStream>, Vec<Event>)
+    where F: FnOnce(&mut Acceptor) + Send + 'static
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (done_tx, done_rx) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut acceptor = Acceptor { stream, buffer: Vec::new(), next_out: 1 };
+        acceptor.accept_logon();
+        gateway(&mut acceptor);
+        done_tx.send(()).unwrap();
+        // Keep the connection open until the session has read everything
+        thread::sleep(Duration::from_millis(200));
+    });
+
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_millis(20))).unwrap();
+    let credentials = Credentials::new("key", "c2VjcmV0", "passphrase").unwrap();
+    let mut session = Session::new(stream, &credentials);
+    session.logon().unwrap();
+    assert!(session.is_logged_on());
+    client(&mut session);
+
+    let mut events = Vec::new();
+    loop {
+        let finished = done_rx.try_recv().is_ok() || handle.is_finished();
+        // Drain what was sent before the gateway finished
+        while let Some(event) = session.poll().unwrap() {
+            events.push(event);
+        }
+        if finished {
+            break;
+        }
+    }
+    handle.join().unwrap();
+    (session, events)
+}
+
+fn execution_report(exec_type: &str) -> Message {
+    Message::new(msg_type::EXECUTION_REPORT).with(tag::ORDER_ID, ORDER_ID)
+                                            .with(tag::SYMBOL, "BTC-USD")
+                                            .with(tag::SIDE, 1)
+                                            .with(tag::EXEC_TYPE, exec_type)
+                                            .with(tag::TRANSACT_TIME, "20180601-12:00:01.250")
+}
+
+#[test]
+fn message_framing() {
+    let message = Message::new(msg_type::HEARTBEAT).with(tag::MSG_SEQ_NUM, 7);
+    let bytes = message.encode();
+    assert_eq!(String::from_utf8_lossy(&bytes), "8=FIX.4.2\x019=10\x0135=0\x0134=7\x0110=169\x01");
+
+    assert_eq!(Message::decode(&bytes).unwrap(), Some((message, bytes.len())));
+    assert_eq!(Message::decode(&bytes[..bytes.len() - 1]).unwrap(), None);
+
+    let mut corrupt = bytes.clone();
+    corrupt[15] = b'1';
+    assert!(Message::decode(&corrupt).is_err());
+
+    // A BodyLength too long to be real is refused rather than waited for
+    assert!(Message::decode(b"8=FIX.4.2\x019=18446744073709551615\x0135=0\x01").is_err());
+    assert!(Message::decode(b"8=FIX.4.2\x019=1000000\x0135=0\x01").is_err());
+}
+
+#[test]
+fn orders_and_execution_reports() {
+    let (session, events) = run(|gateway| {
+        let order = gateway.read();
+        assert_eq!(order.msg_type(), msg_type::NEW_ORDER_SINGLE);
+        assert_eq!(order.get(tag::MSG_SEQ_NUM), Some("2"));
+        assert_eq!(order.get(tag::SYMBOL), Some("BTC-USD"));
+        assert_eq!(order.get(tag::SIDE), Some("1"));
+        assert_eq!(order.get(tag::ORD_TYPE), Some("2"));
+        assert_eq!(order.get(tag::PRICE), Some("100"));
+        assert_eq!(order.get(tag::ORDER_QTY), Some("1.5"));
+        let client_order_id = order.get(tag::CL_ORD_ID).unwrap().to_owned();
+
+        let market = gateway.read();
+        assert_eq!((market.get(tag::ORD_TYPE), market.get(tag::SIDE)), (Some("1"), Some("2")));
+        assert_eq!(market.get(tag::ORDER_QTY), Some("0.25"));
+
+        gateway.send(execution_report("0").with(tag::CL_ORD_ID, &client_order_id)
+                                          .with(tag::PRICE, 100)
+                                          .with(tag::ORDER_QTY, 1.5));
+        gateway.send(execution_report("1").with(tag::PRICE, 99.5)
+                                          .with(tag::LAST_SHARES, 1)
+                                          .with(tag::TRADE_ID, 42)
+                                          .with(tag::MISC_FEE_AMT, 0.25));
+
+        let cancel = gateway.read();
+        assert_eq!(cancel.msg_type(), msg_type::ORDER_CANCEL_REQUEST);
+        assert_eq!((cancel.get(tag::ORDER_ID), cancel.get(tag::SYMBOL)), (Some(ORDER_ID), Some("BTC-USD")));
+        gateway.send(execution_report("4"));
+    }, |session| {
+        session.place_order(&NewOrder::limit(Side::Buy, "BTC-USD", 1.5, 100.0)).unwrap();
+        session.place_order(&NewOrder::market(Side::Sell, "BTC-USD", Size(0.25))).unwrap();
+        session.cancel_order("BTC-USD", Uuid::parse_str(ORDER_ID).unwrap()).unwrap();
+    });
+
+    let reports: Vec<_> = events.into_iter()
+                                .map(|event| match event {
+                                    Event::Execution(report) => report,
+                                    other => panic!("expected an execution report, got {:?}", other)
+                                })
+                                .collect();
+    assert_eq!(reports.iter().map(|r| r.exec_type.clone()).collect::<Vec<_>>(),
+               vec![ExecType::New, ExecType::Fill, ExecType::Canceled]);
+    assert_eq!(reports[1].trade_id, Some(42));
+
+    let mut order = reports[0].to_order();
+    assert_eq!((order.size, order.price, order.status.as_str()), (1.5, 100.0, "open"));
+    reports[1].update(&mut order);
+    assert_eq!((order.filled_size, order.executed_value, order.fill_fees), (1.0, 99.5, 0.25));
+    reports[2].update(&mut order);
+    assert_eq!((order.status.as_str(), order.done_reason.as_deref()), ("done", Some("canceled")));
+    assert_eq!(order.done_at.unwrap().to_rfc3339(), "2018-06-01T12:00:01.250+00:00");
+
+    assert_eq!(session.next_sender_seq_num(), 5);
+    assert_eq!(session.next_target_seq_num(), 5);
+}
+
+#[test]
+fn heartbeats_and_resends() {
+    let (session, events) = run(|gateway| {
+        let order = gateway.read();
+        let sending_time = order.get(tag::SENDING_TIME).unwrap().to_owned();
+
+        gateway.send(Message::new(msg_type::TEST_REQUEST).with(tag::TEST_REQ_ID, "ping"));
+        let heartbeat = gateway.read();
+        assert_eq!(heartbeat.msg_type(), msg_type::HEARTBEAT);
+        assert_eq!((heartbeat.get(tag::MSG_SEQ_NUM), heartbeat.get(tag::TEST_REQ_ID)), (Some("3"), Some("ping")));
+
+        // The logon and heartbeat are skipped, the order sent again as is
+        gateway.send(Message::new(msg_type::RESEND_REQUEST).with(tag::BEGIN_SEQ_NO, 1).with(tag::END_SEQ_NO, 0));
+        let gap_fill = gateway.read();
+        assert_eq!(gap_fill.msg_type(), msg_type::SEQUENCE_RESET);
+        assert_eq!((gap_fill.get(tag::MSG_SEQ_NUM), gap_fill.get(tag::NEW_SEQ_NO)), (Some("1"), Some("2")));
+        assert_eq!(gap_fill.get(tag::GAP_FILL_FLAG), Some("Y"));
+        let resent = gateway.read();
+        assert_eq!(resent.msg_type(), msg_type::NEW_ORDER_SINGLE);
+        assert_eq!((resent.get(tag::MSG_SEQ_NUM), resent.get(tag::POSS_DUP_FLAG)), (Some("2"), Some("Y")));
+        assert_eq!(resent.get(tag::ORIG_SENDING_TIME), Some(sending_time.as_str()));
+        assert_eq!(resent.get(tag::CL_ORD_ID), order.get(tag::CL_ORD_ID));
+        let gap_fill = gateway.read();
+        assert_eq!((gap_fill.get(tag::MSG_SEQ_NUM), gap_fill.get(tag::NEW_SEQ_NO)), (Some("3"), Some("4")));
+
+        // Skip number 4; the session asks for it and drops number 5
+        gateway.send_as(5, execution_report("0"));
+        let resend_request = gateway.read();
+        assert_eq!(resend_request.msg_type(), msg_type::RESEND_REQUEST);
+        assert_eq!(resend_request.get(tag::BEGIN_SEQ_NO), Some("4"));
+        assert_eq!(resend_request.get(tag::END_SEQ_NO), Some("0"));
+        gateway.send_as(4, Message::new(msg_type::SEQUENCE_RESET).with(tag::POSS_DUP_FLAG, "Y")
+                                                                 .with(tag::GAP_FILL_FLAG, "Y")
+                                                                 .with(tag::NEW_SEQ_NO, 5));
+        gateway.send_as(5, execution_report("0").with(tag::POSS_DUP_FLAG, "Y"));
+        gateway.send_as(6, Message::new(msg_type::LOGOUT).with(tag::TEXT, "bye"));
+        let logout = gateway.read();
+        assert_eq!(logout.msg_type(), msg_type::LOGOUT);
+    }, |session| {
+        session.place_order(&NewOrder::limit(Side::Buy, "BTC-USD", 1.0, 100.0)).unwrap();
+    });
+
+    assert_eq!(events.len(), 2);
+    match events[0] {
+        Event::Execution(ref report) => assert_eq!(report.exec_type, ExecType::New),
+        ref other => panic!("expected an execution report, got {:?}", other)
+    }
+    assert_eq!(events[1], Event::LoggedOut(Some("bye".to_owned())));
+    assert!(!session.is_logged_on());
+    assert_eq!(session.next_target_seq_num(), 7);
+}
+
+#[test]
+fn logon_and_logout_ahead_of_sequence() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (logged_on_tx, logged_on) = mpsc::channel();
+    let gateway = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut gateway = Acceptor { stream, buffer: Vec::new(), next_out: 1 };
+        assert_eq!(gateway.read().msg_type(), msg_type::LOGON);
+
+        // The logon still counts, and the messages before it are asked for
+        gateway.send_as(3, Message::new(msg_type::LOGON).with(tag::ENCRYPT_METHOD, 0).with(tag::HEART_BT_INT, 30));
+        let resend_request = gateway.read();
+        assert_eq!(resend_request.msg_type(), msg_type::RESEND_REQUEST);
+        assert_eq!(resend_request.get(tag::BEGIN_SEQ_NO), Some("1"));
+
+        // A logout is confirmed even while the gap is open
+        logged_on.recv().unwrap();
+        gateway.send_as(4, Message::new(msg_type::LOGOUT).with(tag::TEXT, "bye"));
+        assert_eq!(gateway.read().msg_type(), msg_type::LOGOUT);
+    });
+
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_millis(20))).unwrap();
+    let credentials = Credentials::new("key", "c2VjcmV0", "passphrase").unwrap();
+    let mut session = Session::new(stream, &credentials);
+    session.logon().unwrap();
+    assert!(session.is_logged_on());
+    logged_on_tx.send(()).unwrap();
+
+    let event = loop {
+        if let Some(event) = session.poll().unwrap() {
+            break event;
+        }
+    };
+    assert_eq!(event, Event::LoggedOut(Some("bye".to_owned())));
+    assert!(!session.is_logged_on());
+    assert_eq!(session.next_target_seq_num(), 1);
+    gateway.join().unwrap();
+}
+
+#[test]
+fn events_during_logon_are_kept() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let gateway = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut gateway = Acceptor { stream, buffer: Vec::new(), next_out: 1 };
+        assert_eq!(gateway.read().msg_type(), msg_type::LOGON);
+
+        // A report arrives ahead of the logon
+        let mut bytes = Acceptor::stamp(1, execution_report("0"));
+        bytes.extend(Acceptor::stamp(2, Message::new(msg_type::LOGON).with(tag::ENCRYPT_METHOD, 0)
+                                                                     .with(tag::HEART_BT_INT, 30)));
+        gateway.stream.write_all(&bytes).unwrap();
+        thread::sleep(Duration::from_millis(200));
+    });
+
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_millis(20))).unwrap();
+    let credentials = Credentials::new("key", "c2VjcmV0", "passphrase").unwrap();
+    let mut session = Session::new(stream, &credentials);
+    session.logon().unwrap();
+
+    match session.poll().unwrap() {
+        Some(Event::Execution(ref report)) => assert_eq!(report.exec_type, ExecType::New),
+        other => panic!("expected the execution report, got {:?}", other)
+    }
+    assert_eq!(session.next_target_seq_num(), 3);
+    gateway.join().unwrap();
+}
+
+#[test]
+fn heartbeats_go_out_while_the_gateway_is_busy() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let gateway = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut gateway = Acceptor { stream, buffer: Vec::new(), next_out: 1 };
+        assert_eq!(gateway.read().msg_type(), msg_type::LOGON);
+        gateway.send(Message::new(msg_type::LOGON).with(tag::ENCRYPT_METHOD, 0).with(tag::HEART_BT_INT, 1));
+
+        // Never quiet for long enough for the session's reads to time out
+        for _ in 0..8 {
+            thread::sleep(Duration::from_millis(200));
+            gateway.send(Message::new(msg_type::HEARTBEAT));
+        }
+        gateway.send(Message::new(msg_type::LOGOUT));
+
+        let mut sent = Vec::new();
+        loop {
+            let message = gateway.read();
+            sent.push(message.msg_type().to_owned());
+            if message.msg_type() == msg_type::LOGOUT {
+                break;
+            }
+        }
+        sent
+    });
+
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let credentials = Credentials::new("key", "c2VjcmV0", "passphrase").unwrap();
+    let mut session = Session::new(stream, &credentials).heartbeat_interval(Duration::from_secs(1));
+    session.logon().unwrap();
+    assert_eq!(session.poll().unwrap(), Some(Event::LoggedOut(None)));
+
+    assert_eq!(gateway.join().unwrap(), vec![msg_type::HEARTBEAT, msg_type::LOGOUT]);
+}
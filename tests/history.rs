@@ -0,0 +1,192 @@
+extern crate chrono;
+extern crate gdax_client;
+
+use chrono::{DateTime, Duration, SecondsFormat, TimeZone, Utc};
+use gdax_client::{Method, PublicClient, Response, Side};
+use gdax_client::history::{self, Format};
+use gdax_client::public::{Candle, Granularity, Trade};
+use gdax_client::transport::MemoryTransport;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn output(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("gdax-history-{}-{}", std::process::id(), name));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2018, 6, 1, 0, 0, 0).unwrap()
+}
+
+fn trade(trade_id: u64, minutes: i64) -> Trade {
+    Trade {
+        time: start() + Duration::minutes(minutes),
+        trade_id,
+        price: "7500.00".to_owned(),
+        size: "0.25".to_owned(),
+        side: if trade_id.is_multiple_of(2) { Side::Buy } else { Side::Sell }
+    }
+}
+
+fn candle(minutes: i64) -> Candle {
+    let time = (start() + Duration::minutes(minutes)).timestamp() as u64;
+    Candle { time, low: 1.0, high: 2.5, open: 1.5, close: 2.0, volume: 12.25 }
+}
+
+fn candles_json(minutes: &[i64]) -> String {
+    let rows: Vec<String> = minutes.iter()
+                                   .rev()
+                                   .map(|m| {
+                                       let c = candle(*m);
+                                       format!("[{}, {}, {}, {}, {}, {}]", c.time, c.low, c.high, c.open, c.close, c.volume)
+                                   })
+                                   .collect();
+    format!("[{}]", rows.join(","))
+}
+
+fn candles_path(from: i64, to: i64) -> String {
+    format!("/products/BTC-USD/candles?start={}&end={}&granularity=60",
+            (start() + Duration::minutes(from)).to_rfc3339_opts(SecondsFormat::Secs, true),
+            (start() + Duration::minutes(to)).to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+#[test]
+fn round_trips_both_formats() {
+    let trades = vec![trade(1, 0), trade(2, 1), trade(3, 1)];
+    let candles = vec![candle(0), candle(1)];
+
+    for format in &[Format::Csv, Format::Columnar] {
+        let trades_path = output(&format!("trades-{:?}", format));
+        history::append(&trades_path, *format, &trades[..2]).unwrap();
+        history::append(&trades_path, *format, &trades[2..]).unwrap();
+        assert_eq!(history::read::<Trade, _>(&trades_path, *format).unwrap(), trades);
+        assert_eq!(history::last_key::<Trade, _>(&trades_path, *format).unwrap(), Some(3));
+
+        let candles_path = output(&format!("candles-{:?}", format));
+        history::append(&candles_path, *format, &candles).unwrap();
+        assert_eq!(history::read::<Candle, _>(&candles_path, *format).unwrap(), candles);
+        assert_eq!(history::last_key::<Candle, _>(&candles_path, *format).unwrap(), Some(candles[1].time));
+
+        fs::remove_file(trades_path).unwrap();
+        fs::remove_file(candles_path).unwrap();
+    }
+}
+
+#[test]
+fn csv_schema_is_stable() {
+    let path = output("schema.csv");
+    history::append(&path, Format::Csv, &[trade(7, 0)]).unwrap();
+
+    let contents = fs::read_to_string(&path).unwrap();
+    let mut lines = contents.lines();
+    assert_eq!(lines.next(), Some("time,trade_id,price,size,side"));
+    assert_eq!(lines.next(), Some(r#""2018-06-01T00:00:00Z",7,"7500.00","0.25","sell""#));
+
+    // Reading trades back as candles is rejected rather than misread
+    assert!(history::read::<Candle, _>(&path, Format::Csv).is_err());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn columnar_schema_is_stable() {
+    let path = output("schema");
+    history::append(&path, Format::Columnar, &[trade(7, 0), trade(8, 1)]).unwrap();
+
+    let contents = fs::read_to_string(&path).unwrap();
+    assert_eq!(contents, concat!(r#"{"schema":"trade","columns":["time","trade_id","price","size","side"],"rows":2,"#,
+                                 r#""data":[["2018-06-01T00:00:00Z","2018-06-01T00:01:00Z"],[7,8],"#,
+                                 r#"["7500.00","7500.00"],["0.25","0.25"],["sell","buy"]]}"#, "\n"));
+
+    assert!(history::read::<Candle, _>(&path, Format::Columnar).is_err());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn missing_files_have_no_last_key() {
+    assert_eq!(history::last_key::<Trade, _>(output("missing"), Format::Columnar).unwrap(), None);
+}
+
+#[test]
+fn candle_downloads_resume_after_the_last_stored_candle() {
+    let path = output("candles.csv");
+    let transport = MemoryTransport::new();
+    transport.push_json(Method::Get, &candles_path(0, 2), &candles_json(&[0, 1, 2]));
+    transport.push_json(Method::Get, &candles_path(3, 5), &candles_json(&[3, 5]));
+    let mut client = PublicClient::with_transport(transport.clone());
+
+    let at = |m: i64| start() + Duration::minutes(m);
+    let first = history::download_candles(&mut client, "BTC-USD", at(0), at(3), Granularity::OneMinute,
+                                          &path, Format::Csv).unwrap();
+    assert_eq!(first.resumed_from, None);
+    assert_eq!(first.written, 3);
+
+    let second = history::download_candles(&mut client, "BTC-USD", at(0), at(6), Granularity::OneMinute,
+                                           &path, Format::Csv).unwrap();
+    assert_eq!(second.resumed_from, Some(candle(2).time));
+    assert_eq!(second.written, 2);
+    assert_eq!(second.gaps, vec![(candle(4).time, candle(5).time)]);
+    assert_eq!(transport.remaining(), 0);
+
+    let stored: Vec<u64> = history::read::<Candle, _>(&path, Format::Csv).unwrap().iter().map(|c| c.time).collect();
+    assert_eq!(stored, [0, 1, 2, 3, 5].iter().map(|m| candle(*m).time).collect::<Vec<_>>());
+    fs::remove_file(path).unwrap();
+}
+
+// A page of trades, with `cursor` as the page's `header` cursor.
+fn trades_page(trades: &[Trade], header: &str, cursor: Option<&str>) -> Response<()> {
+    let body: Vec<String> = trades.iter()
+                                  .map(|t| format!(r#"{{"time": "{}", "trade_id": {}, "price": "{}", "size": "{}", "side": "{}"}}"#,
+                                                   t.time.to_rfc3339_opts(SecondsFormat::Secs, true),
+                                                   t.trade_id, t.price, t.size,
+                                                   if t.side == Side::Buy { "buy" } else { "sell" }))
+                                  .collect();
+    Response {
+        value: (),
+        status: 200,
+        headers: cursor.map(|c| vec![(header.to_owned(), c.to_owned())]).unwrap_or_default(),
+        body: format!("[{}]", body.join(",")).into_bytes()
+    }
+}
+
+#[test]
+fn trade_downloads_append_each_page_and_resume_after_the_last_stored_trade() {
+    let path = output("trades");
+    let at = |m: i64| start() + Duration::minutes(m);
+    let older = |trades: &[Trade], cursor| trades_page(trades, "CB-AFTER", Some(cursor));
+    let newer = |trades: &[Trade], cursor| trades_page(trades, "CB-BEFORE", cursor);
+
+    // Paging back finds trade 1, the last before the range; paging forward
+    // from it fails after the first page
+    let transport = MemoryTransport::new();
+    transport.push_response(Method::Get, "/products/BTC-USD/trades",
+                            older(&[trade(6, 20), trade(5, 9), trade(4, 8)], "4"));
+    transport.push_response(Method::Get, "/products/BTC-USD/trades?after=4",
+                            older(&[trade(3, 5), trade(2, 1), trade(1, -1)], "1"));
+    transport.push_response(Method::Get, "/products/BTC-USD/trades?before=1",
+                            newer(&[trade(3, 5), trade(2, 1)], Some("3")));
+    let mut client = PublicClient::with_transport(transport.clone());
+
+    assert!(history::download_trades(&mut client, "BTC-USD", at(0), at(10), &path, Format::Columnar).is_err());
+    assert_eq!(history::last_key::<Trade, _>(&path, Format::Columnar).unwrap(), Some(3));
+
+    // Retrying continues from the stored trades, up to trade 6 past the end
+    transport.push_response(Method::Get, "/products/BTC-USD/trades?before=3",
+                            newer(&[trade(6, 20), trade(5, 9), trade(4, 8)], Some("6")));
+    let download = history::download_trades(&mut client, "BTC-USD", at(0), at(10), &path, Format::Columnar).unwrap();
+    assert_eq!((download.resumed_from, download.written), (Some(3), 2));
+    assert_eq!(transport.remaining(), 0);
+
+    // Later, only the trades after the last stored one are added
+    transport.push_response(Method::Get, "/products/BTC-USD/trades?before=5",
+                            newer(&[trade(8, 9), trade(7, 9)], Some("8")));
+    transport.push_response(Method::Get, "/products/BTC-USD/trades?before=8", newer(&[], None));
+    let download = history::download_trades(&mut client, "BTC-USD", at(0), at(10), &path, Format::Columnar).unwrap();
+    assert_eq!((download.resumed_from, download.written), (Some(5), 2));
+    assert_eq!(transport.remaining(), 0);
+
+    let ids: Vec<u64> = history::read::<Trade, _>(&path, Format::Columnar).unwrap().iter().map(|t| t.trade_id).collect();
+    assert_eq!(ids, vec![2, 3, 4, 5, 7, 8]);
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,189 @@
+extern crate chrono;
+extern crate gdax_client;
+
+use chrono::{Duration, Utc};
+use gdax_client::{Error, Funds, NewOrder, PrivateClient, Side, Size};
+use gdax_client::public::Granularity;
+use gdax_client::mock::{MockExchange, MOCK_KEY, MOCK_PASSPHRASE};
+use gdax_client::history::{self, Format};
+use gdax_client::private::{EntryType, HoldType};
+
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+
+#[test]
+fn public_endpoints() {
+    let exchange = MockExchange::start().unwrap();
+    exchange.place_order(&NewOrder::limit(Side::Buy, "BTC-USD", 1.0, 99.0)).unwrap();
+    exchange.place_order(&NewOrder::limit(Side::Buy, "BTC-USD", 2.0, 99.0)).unwrap();
+    exchange.place_order(&NewOrder::limit(Side::Sell, "BTC-USD", 1.5, 101.0)).unwrap();
+    exchange.place_order(&NewOrder::market(Side::Sell, "BTC-USD", Size(0.5))).unwrap();
+
+    let mut client = exchange.public_client();
+    let products = client.get_products().unwrap();
+    assert_eq!((products[0].id.as_str(), products[0].quote_currency.as_str()), ("BTC-USD", "USD"));
+
+    let book = client.get_top50_orders("BTC-USD").unwrap();
+    assert_eq!((book.bids[0].price.as_str(), book.bids[0].size.as_str(), book.bids[0].num_orders), ("99", "2.5", 2));
+    assert_eq!(book.asks[0].price, "101");
+    let full = client.get_full_book("BTC-USD").unwrap();
+    assert_eq!(full.bids.len(), 2);
+    assert_eq!(full.bids[0].size, "0.5");
+
+    let trades = client.get_trades("BTC-USD").unwrap();
+    assert_eq!(trades.len(), 1);
+    assert_eq!((trades[0].price.as_str(), trades[0].size.as_str(), trades[0].side), ("99", "0.5", Side::Buy));
+
+    let ticker = client.get_product_ticker("BTC-USD").unwrap();
+    assert_eq!((ticker.price.as_str(), ticker.bid.as_str(), ticker.ask.as_str()), ("99", "99", "101"));
+    assert!(client.get_time().unwrap().epoch > 0.0);
+    match client.get_product_ticker("ETH-USD") {
+        Err(Error::Api(e)) => assert!(e.message.contains("NotFound")),
+        other => panic!("expected NotFound, got {:?}", other)
+    }
+}
+
+#[test]
+fn limit_orders_fill_and_cancel() {
+    let exchange = MockExchange::start().unwrap();
+    exchange.deposit("USD", 1000.0);
+    let mut client = exchange.private_client();
+
+    let id = client.post_order(&NewOrder::limit(Side::Buy, "BTC-USD", 1.0, 100.0)).unwrap();
+    let open = client.get_orders().unwrap();
+    assert_eq!((open.len(), open[0].id, open[0].status.as_str()), (1, id, "open"));
+
+    let usd = client.get_accounts().unwrap().into_iter().find(|a| a.currency == "USD").unwrap();
+    assert_eq!((usd.balance, usd.hold, usd.available), (1000.0, 100.0, 900.0));
+    let holds = client.get_account_holds(usd.id).unwrap();
+    assert_eq!((holds[0].amount, &holds[0].hold_type, holds[0].ref_id), (100.0, &HoldType::Order, id));
+
+    // Another trader sells into the bid
+    exchange.place_order(&NewOrder::market(Side::Sell, "BTC-USD", Size(0.4))).unwrap();
+    let order = client.get_order(id).unwrap();
+    assert_eq!((order.filled_size, order.executed_value, order.status.as_str()), (0.4, 40.0, "open"));
+    assert_eq!(exchange.account("BTC").unwrap().balance, 0.4);
+    let usd = client.get_account(usd.id).unwrap();
+    assert_eq!((usd.balance, usd.hold), (960.0, 60.0));
+
+    let ledger = client.get_account_history(usd.id).unwrap();
+    assert_eq!(ledger.iter().map(|e| e.entry_type.clone()).collect::<Vec<_>>(),
+               vec![EntryType::Match, EntryType::Transfer]);
+    assert_eq!((ledger[0].amount, ledger[0].balance), (-40.0, 960.0));
+
+    assert_eq!(client.cancel_order(id).unwrap(), id);
+    let order = client.get_order(id).unwrap();
+    assert_eq!((order.status.as_str(), order.done_reason.as_deref()), ("done", Some("canceled")));
+    assert_eq!(exchange.account("USD").unwrap().hold, 0.0);
+    assert!(client.get_orders().unwrap().is_empty());
+    assert!(client.cancel_order(id).is_err());
+}
+
+#[test]
+fn market_and_stop_orders() {
+    let exchange = MockExchange::start().unwrap();
+    exchange.deposit("USD", 1000.0);
+    exchange.deposit("BTC", 1.0);
+    exchange.place_order(&NewOrder::limit(Side::Sell, "BTC-USD", 1.0, 100.0)).unwrap();
+    exchange.place_order(&NewOrder::limit(Side::Sell, "BTC-USD", 1.0, 110.0)).unwrap();
+    let mut client = exchange.private_client();
+
+    // 100 buys one at 100, the other 55 half of one at 110
+    let id = client.post_order(&NewOrder::market(Side::Buy, "BTC-USD", Funds(155.0))).unwrap();
+    let order = client.get_order(id).unwrap();
+    assert_eq!((order.filled_size, order.executed_value, order.status.as_str()), (1.5, 155.0, "done"));
+    let usd = exchange.account("USD").unwrap();
+    assert_eq!((usd.balance, usd.hold), (845.0, 0.0));
+
+    let stop = client.post_order(&NewOrder::stop(Side::Sell, "BTC-USD", Size(0.5), 95.0)).unwrap();
+    assert_eq!(client.get_orders().unwrap()[0].status, "active");
+    assert_eq!(exchange.account("BTC").unwrap().hold, 0.5);
+
+    // A trade at 95 triggers the stop, which sells into the remaining bid
+    exchange.place_order(&NewOrder::limit(Side::Buy, "BTC-USD", 1.0, 95.0)).unwrap();
+    exchange.place_order(&NewOrder::market(Side::Sell, "BTC-USD", Size(0.2))).unwrap();
+    let order = client.get_order(stop).unwrap();
+    assert_eq!((order.filled_size, order.executed_value, order.status.as_str()), (0.5, 47.5, "done"));
+    let btc = exchange.account("BTC").unwrap();
+    assert_eq!((btc.balance, btc.hold), (2.0, 0.0));
+}
+
+#[test]
+fn requests_are_authenticated() {
+    let exchange = MockExchange::start().unwrap();
+    let mut client = exchange.private_client();
+    match client.post_order(&NewOrder::limit(Side::Buy, "BTC-USD", 1.0, 100.0)) {
+        Err(Error::Api(e)) => assert!(e.message.contains("Insufficient funds")),
+        other => panic!("expected insufficient funds, got {:?}", other)
+    }
+
+    let mut forged = PrivateClient::new(MOCK_KEY, "d3Jvbmc=", MOCK_PASSPHRASE).unwrap().base_url(&exchange.url());
+    match forged.get_accounts() {
+        Err(Error::Api(e)) => assert!(e.message.contains("invalid signature")),
+        other => panic!("expected an invalid signature, got {:?}", other)
+    }
+}
+
+#[test]
+fn unsupported_granularities_are_rejected() {
+    let exchange = MockExchange::start().unwrap();
+    exchange.place_order(&NewOrder::limit(Side::Buy, "BTC-USD", 1.0, 99.0)).unwrap();
+    exchange.place_order(&NewOrder::market(Side::Sell, "BTC-USD", Size(0.5))).unwrap();
+
+    // The client only asks for supported widths, so go around it
+    for granularity in &["0", "61", "-60"] {
+        let mut stream = TcpStream::connect(exchange.url().trim_start_matches("http://")).unwrap();
+        write!(stream, "GET /products/BTC-USD/candles?granularity={} HTTP/1.1\r\nHost: localhost\r\n\r\n",
+               granularity).unwrap();
+        stream.shutdown(Shutdown::Write).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+        assert!(response.contains("Unsupported granularity"), "{}", response);
+    }
+
+    // and the exchange carries on serving
+    let now = Utc::now();
+    let candles = exchange.public_client()
+                          .get_historic_rates("BTC-USD", now - Duration::hours(1), now + Duration::minutes(1),
+                                              Granularity::OneMinute)
+                          .unwrap();
+    assert_eq!(candles.len(), 1);
+}
+
+#[test]
+fn trades_page_back_and_forward() {
+    let exchange = MockExchange::start().unwrap();
+    exchange.place_order(&NewOrder::limit(Side::Sell, "BTC-USD", 1000.0, 100.0)).unwrap();
+    for _ in 0..150 {
+        exchange.place_order(&NewOrder::market(Side::Buy, "BTC-USD", Size(1.0))).unwrap();
+    }
+    let mut client = exchange.public_client();
+    let ids = |page: &[gdax_client::public::Trade]| page.iter().map(|t| t.trade_id).collect::<Vec<_>>();
+
+    let newest = client.get_trades_page("BTC-USD", None).unwrap();
+    assert_eq!(ids(&newest.value), (51..151).rev().collect::<Vec<_>>());
+    assert_eq!((newest.before(), newest.after()), (Some("150"), Some("51")));
+
+    let oldest = client.get_newer_trades_page("BTC-USD", "0").unwrap();
+    assert_eq!(ids(&oldest.value), (1..101).rev().collect::<Vec<_>>());
+    assert_eq!(oldest.before(), Some("100"));
+    let next = client.get_newer_trades_page("BTC-USD", "100").unwrap();
+    assert_eq!(ids(&next.value), (101..151).rev().collect::<Vec<_>>());
+    assert!(client.get_newer_trades_page("BTC-USD", "150").unwrap().value.is_empty());
+
+    // So a download walks forward through every page
+    let path = env::temp_dir().join(format!("gdax-mock-trades-{}", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let now = Utc::now();
+    let download = history::download_trades(&mut client, "BTC-USD", now - Duration::hours(1),
+                                            now + Duration::hours(1), &path, Format::Csv).unwrap();
+    assert_eq!(download.written, 150);
+    let stored: Vec<u64> = fs::read_to_string(&path).unwrap().lines().skip(1)
+                              .map(|line| line.split(',').nth(1).unwrap().parse().unwrap())
+                              .collect();
+    assert_eq!(stored, (1..151).collect::<Vec<_>>());
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,52 @@
+extern crate gdax_client;
+#[macro_use]
+extern crate serde_json;
+
+use gdax_client::{Error, Funds, Method, NewOrder, PrivateClient, Side, Size};
+use gdax_client::private::{Hold, HoldType};
+use gdax_client::transport::MemoryTransport;
+
+#[test]
+fn new_orders_are_sent_with_their_type() {
+    let body = |order: NewOrder| serde_json::to_value(&order).unwrap();
+
+    assert_eq!(body(NewOrder::limit(Side::Sell, "BTC-USD", 0.5, 6500.0)),
+               json!({"type": "limit", "side": "sell", "product_id": "BTC-USD", "price": 6500.0, "size": 0.5}));
+    assert_eq!(body(NewOrder::market(Side::Buy, "BTC-USD", Size(0.25))),
+               json!({"type": "market", "side": "buy", "product_id": "BTC-USD", "size": 0.25}));
+    assert_eq!(body(NewOrder::market(Side::Buy, "BTC-USD", Funds(100.0))),
+               json!({"type": "market", "side": "buy", "product_id": "BTC-USD", "funds": 100.0}));
+    assert_eq!(body(NewOrder::stop(Side::Sell, "BTC-USD", Size(1.0), 90.0)),
+               json!({"type": "stop", "side": "sell", "product_id": "BTC-USD", "price": 90.0, "size": 1.0}));
+    assert_eq!(body(NewOrder::stop(Side::Buy, "BTC-USD", Funds(50.0), 110.0)),
+               json!({"type": "stop", "side": "buy", "product_id": "BTC-USD", "price": 110.0, "funds": 50.0}));
+}
+
+#[test]
+fn holds_decode_type_and_ref() {
+    let hold: Hold = serde_json::from_str(r#"{
+        "id": "82dcd140-c3c7-4507-8de4-2c529cd1a28f",
+        "account_id": "e0b3f39a-183d-453e-b754-0c13e5bab0b3",
+        "created_at": "2014-11-06T10:34:47.123456Z",
+        "updated_at": null,
+        "amount": 4.23, "type": "transfer",
+        "ref": "0a205de4-dd35-4370-a285-fe8fc375a273"
+    }"#).unwrap();
+    match hold.hold_type {
+        HoldType::Transfer => {}
+        other => panic!("unexpected hold type {:?}", other)
+    }
+    assert_eq!(hold.ref_id.to_string(), "0a205de4-dd35-4370-a285-fe8fc375a273");
+}
+
+#[test]
+fn cancelling_a_done_order_is_an_error() {
+    let transport = MemoryTransport::new();
+    transport.push_json(Method::Delete, "/orders/d50ec984-77a8-460a-b958-66f114b0de9b", "[]");
+    let mut client = PrivateClient::with_transport("key", "c2VjcmV0", "passphrase", transport).unwrap();
+
+    match client.cancel_order("d50ec984-77a8-460a-b958-66f114b0de9b".parse().unwrap()) {
+        Err(Error::Api(e)) => assert!(e.message.contains("was not canceled")),
+        other => panic!("expected an API error, got {:?}", other)
+    }
+}
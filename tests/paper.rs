@@ -0,0 +1,161 @@
+extern crate gdax_client;
+extern crate serde_json;
+
+use gdax_client::{Error, Funds, NewOrder, Side, Size};
+use gdax_client::feed::Message;
+use gdax_client::paper::PaperTrader;
+use gdax_client::private::{Account, EntryType, HoldType};
+use gdax_client::public::{BookEntry, OrderBook};
+
+fn book(bids: &[(&str, &str)], asks: &[(&str, &str)]) -> OrderBook<BookEntry> {
+    let entries = |levels: &[(&str, &str)]| {
+        levels.iter()
+              .map(|&(price, size)| BookEntry { price: price.to_owned(), size: size.to_owned(), num_orders: 1 })
+              .collect()
+    };
+    OrderBook { sequence: 1, bids: entries(bids), asks: entries(asks) }
+}
+
+fn trade(price: &str, size: &str) -> Message {
+    serde_json::from_str(&format!(r#"{{"type": "match", "trade_id": 10, "sequence": 50,
+        "maker_order_id": "00000000-0000-0000-0000-00000000000a",
+        "taker_order_id": "00000000-0000-0000-0000-00000000000b",
+        "time": "2018-06-01T12:00:00.000000Z", "product_id": "BTC-USD",
+        "size": "{}", "price": "{}", "side": "sell"}}"#, size, price)).unwrap()
+}
+
+fn account(trader: &mut PaperTrader, currency: &str) -> Account {
+    trader.get_accounts().unwrap().into_iter().find(|a| a.currency == currency).unwrap()
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+}
+
+#[test]
+fn limit_orders_take_and_make_with_fees() {
+    let mut trader = PaperTrader::new().fees(0.001, 0.002);
+    trader.deposit("USD", 1000.0);
+    trader.load_book("BTC-USD", &book(&[("99", "1")], &[("100", "1"), ("101", "2")])).unwrap();
+
+    // Takes all of 100 and half a coin at 101
+    let buy = trader.post_order(&NewOrder::limit(Side::Buy, "BTC-USD", 1.5, 101.0)).unwrap();
+    let order = trader.get_order(buy).unwrap();
+    assert_eq!((order.filled_size, order.executed_value, order.status.as_str()), (1.5, 150.5, "done"));
+    assert_close(order.fill_fees, 0.301);
+    let fills = trader.get_fills(Some(buy), None).unwrap();
+    assert_eq!(fills.iter().map(|f| (f.price, f.size, f.liquidity.as_str())).collect::<Vec<_>>(),
+               vec![(101.0, 0.5, "T"), (100.0, 1.0, "T")]);
+
+    let usd = account(&mut trader, "USD");
+    assert_close(usd.balance, 849.199);
+    assert_eq!(usd.hold, 0.0);
+    let ledger = trader.get_account_history(usd.id).unwrap();
+    assert_eq!(ledger.iter().map(|e| e.entry_type.clone()).collect::<Vec<_>>(),
+               vec![EntryType::Fee, EntryType::Match, EntryType::Fee, EntryType::Match, EntryType::Transfer]);
+    assert_eq!((ledger[1].amount, ledger[3].amount), (-50.5, -100.0));
+
+    // Rests above the bid, then fills as maker
+    let sell = trader.post_order(&NewOrder::limit(Side::Sell, "BTC-USD", 1.0, 102.0)).unwrap();
+    let btc = account(&mut trader, "BTC");
+    assert_eq!((btc.balance, btc.hold), (1.5, 1.0));
+    let holds = trader.get_account_holds(btc.id).unwrap();
+    assert_eq!((holds[0].amount, &holds[0].hold_type, holds[0].ref_id), (1.0, &HoldType::Order, sell));
+    assert_eq!(trader.get_orders().unwrap()[0].id, sell);
+
+    let fills = trader.apply(&trade("102.50", "0.4")).unwrap();
+    assert_eq!(fills.iter().map(|f| (f.price, f.size, f.liquidity.as_str())).collect::<Vec<_>>(),
+               vec![(102.0, 0.4, "M")]);
+    assert_close(fills[0].fee, 0.0408);
+    assert_eq!(trader.get_order(sell).unwrap().status, "open");
+
+    // The bids move through the order
+    let snapshot: Message = serde_json::from_str(r#"{"type": "snapshot", "product_id": "BTC-USD",
+        "bids": [["103.00", "5.0"]], "asks": [["104.00", "1.0"]]}"#).unwrap();
+    let fills = trader.apply(&snapshot).unwrap();
+    assert_close(fills[0].size, 0.6);
+    let order = trader.get_order(sell).unwrap();
+    assert_eq!((order.status.as_str(), order.done_reason.as_deref()), ("done", Some("filled")));
+    let btc = account(&mut trader, "BTC");
+    assert_close(btc.balance, 0.5);
+    assert_eq!(btc.hold, 0.0);
+    assert_eq!(trader.get_fills(None, Some("BTC-USD")).unwrap().len(), 4);
+    assert!(trader.get_orders().unwrap().is_empty());
+}
+
+#[test]
+fn market_and_stop_orders() {
+    let mut trader = PaperTrader::new();
+    trader.deposit("USD", 100.0);
+    match trader.post_order(&NewOrder::market(Side::Buy, "BTC-USD", Funds(60.0))) {
+        Err(Error::Api(e)) => assert!(e.message.contains("No market data")),
+        other => panic!("expected no market data, got {:?}", other)
+    }
+
+    // Without a book, market orders execute at the last trade's price
+    trader.apply(&trade("50.00", "1.0")).unwrap();
+    let buy = trader.post_order(&NewOrder::market(Side::Buy, "BTC-USD", Funds(60.0))).unwrap();
+    let order = trader.get_order(buy).unwrap();
+    assert_eq!((order.filled_size, order.executed_value, order.status.as_str()), (1.2, 60.0, "done"));
+    assert_eq!(account(&mut trader, "USD").balance, 40.0);
+
+    match trader.post_order(&NewOrder::limit(Side::Buy, "BTC-USD", 1.0, 45.0)) {
+        Err(Error::Api(e)) => assert_eq!(e.message, "Insufficient funds"),
+        other => panic!("expected insufficient funds, got {:?}", other)
+    }
+    let bid = trader.post_order(&NewOrder::limit(Side::Buy, "BTC-USD", 0.5, 45.0)).unwrap();
+    assert_eq!(account(&mut trader, "USD").available, 17.5);
+    assert_eq!(trader.cancel_order(bid).unwrap(), bid);
+    assert_eq!(account(&mut trader, "USD").hold, 0.0);
+    assert_eq!(trader.get_order(bid).unwrap().done_reason.as_deref(), Some("canceled"));
+    assert!(trader.cancel_order(bid).is_err());
+
+    let stop = trader.post_order(&NewOrder::stop(Side::Sell, "BTC-USD", Size(1.0), 48.0)).unwrap();
+    assert_eq!(trader.get_orders().unwrap()[0].status, "active");
+    assert_eq!(account(&mut trader, "BTC").hold, 1.0);
+
+    // A trade at 48 triggers the stop, which sells into the bids
+    trader.load_book("BTC-USD", &book(&[("47", "0.4"), ("46", "2")], &[("49", "1")])).unwrap();
+    let fills = trader.apply(&trade("48.00", "0.1")).unwrap();
+    assert_eq!(fills.iter().map(|f| (f.price, f.size, f.liquidity.as_str())).collect::<Vec<_>>(),
+               vec![(47.0, 0.4, "T"), (46.0, 0.6, "T")]);
+    let order = trader.get_order(stop).unwrap();
+    assert_eq!((order.filled_size, order.status.as_str()), (1.0, "done"));
+    assert_close(order.executed_value, 46.4);
+    assert_close(account(&mut trader, "USD").balance, 86.4);
+    let btc = account(&mut trader, "BTC");
+    assert_close(btc.balance, 0.2);
+    assert_eq!(btc.hold, 0.0);
+
+    let conversion = trader.convert("USD", "USDC", 80.0).unwrap();
+    let usdc = account(&mut trader, "USDC");
+    assert_eq!((usdc.id, usdc.balance), (conversion.to_account_id, 80.0));
+    assert_eq!(trader.get_account_history(usdc.id).unwrap()[0].entry_type, EntryType::Conversion);
+    assert!(trader.convert("USD", "USDC", 80.0).is_err());
+}
+
+#[test]
+fn crossing_limits_do_not_refill_against_the_liquidity_they_took() {
+    let mut trader = PaperTrader::new();
+    trader.deposit("USD", 1000.0);
+    trader.load_book("BTC-USD", &book(&[("99", "1")], &[("100", "1")])).unwrap();
+
+    // Takes the whole ask, then rests for the other coin
+    let buy = trader.post_order(&NewOrder::limit(Side::Buy, "BTC-USD", 2.0, 105.0)).unwrap();
+    assert_eq!(trader.get_order(buy).unwrap().filled_size, 1.0);
+
+    let update = |changes: &str| -> Message {
+        serde_json::from_str(&format!(r#"{{"type": "l2update", "product_id": "BTC-USD",
+            "time": "2018-06-01T12:00:00.000000Z", "changes": {}}}"#, changes)).unwrap()
+    };
+    assert!(trader.apply(&update(r#"[["buy", "98.00", "3.0"]]"#)).unwrap().is_empty());
+    assert_eq!(trader.get_order(buy).unwrap().filled_size, 1.0);
+
+    // Once the level is traded away and refilled, the new size fills
+    assert!(trader.apply(&update(r#"[["sell", "100.00", "0"]]"#)).unwrap().is_empty());
+    let fills = trader.apply(&update(r#"[["sell", "100.00", "0.4"]]"#)).unwrap();
+    assert_eq!(fills.iter().map(|f| (f.price, f.size, f.liquidity.as_str())).collect::<Vec<_>>(),
+               vec![(105.0, 0.4, "M")]);
+    assert!(trader.apply(&update(r#"[["buy", "97.00", "1.0"]]"#)).unwrap().is_empty());
+    assert_close(trader.get_order(buy).unwrap().filled_size, 1.4);
+}
@@ -0,0 +1,146 @@
+extern crate gdax_client;
+extern crate serde_json;
+
+use gdax_client::{Error, Method, PrivateClient, PublicClient};
+use gdax_client::book::SyncedBook;
+use gdax_client::replay::{Record, Recorder, RecordingTransport, Replay};
+use gdax_client::transport::MemoryTransport;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const A: &str = "00000000-0000-0000-0000-00000000000a";
+const B: &str = "00000000-0000-0000-0000-00000000000b";
+const C: &str = "00000000-0000-0000-0000-00000000000c";
+const D: &str = "00000000-0000-0000-0000-00000000000d";
+const G: &str = "00000000-0000-0000-0000-000000000010";
+
+fn recording_path(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("gdax-replay-{}-{}.jsonl", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+// The exchange as seen during the original session: a snapshot when the
+// book is first loaded, and another after the gap in the recorded feed.
+fn exchange() -> MemoryTransport {
+    let transport = MemoryTransport::new();
+    transport.push_json(Method::Get, "/products/BTC-USD/book?level=3", &format!(r#"{{"sequence": 100,
+        "bids": [["99.00", "1.0", "{}"], ["98.00", "2.0", "{}"]],
+        "asks": [["101.00", "1.5", "{}"]]}}"#, A, B, C));
+    transport.push_json(Method::Get, "/products/BTC-USD/book?level=3", &format!(r#"{{"sequence": 104,
+        "bids": [["99.00", "1.0", "{}"], ["99.00", "0.5", "{}"], ["98.00", "2.0", "{}"],
+                 ["97.00", "3.0", "{}"]],
+        "asks": [["101.00", "1.5", "{}"]]}}"#, A, D, B, G, C));
+    transport
+}
+
+#[test]
+fn replayed_session_rebuilds_the_same_book() {
+    let path = recording_path("book");
+    let recorder = Recorder::create(&path).unwrap();
+
+    let client = PublicClient::with_transport(RecordingTransport::new(exchange(), recorder.clone()));
+    let mut live = SyncedBook::new("BTC-USD", client);
+    for line in include_str!("data/full_channel_gap.jsonl").lines() {
+        recorder.record_message(line).unwrap();
+        live.process(&serde_json::from_str(line).unwrap()).unwrap();
+    }
+
+    let replay = Replay::open(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    // Six feed messages, with the two snapshots interleaved where they
+    // were fetched
+    let kinds: Vec<&str> = replay.records()
+                                 .iter()
+                                 .map(|r| match *r {
+                                     Record::Feed { .. } => "feed",
+                                     Record::Rest { .. } => "rest"
+                                 })
+                                 .collect();
+    assert_eq!(kinds, ["feed", "rest", "feed", "feed", "rest", "feed", "feed", "feed"]);
+
+    let mut replayed = SyncedBook::new("BTC-USD", PublicClient::with_transport(replay.transport()));
+    for message in replay.feed() {
+        replayed.process(&message.unwrap()).unwrap();
+    }
+
+    assert_eq!(replayed.book().sequence(), Some(107));
+    assert_eq!(replayed.book().to_order_book(), live.book().to_order_book());
+}
+
+#[test]
+fn rest_records_keep_status_and_headers_but_not_credentials() {
+    let path = recording_path("private");
+    let exchange = {
+        let transport = MemoryTransport::new();
+        let mut response = gdax_client::Response {
+            value: (),
+            status: 400,
+            headers: vec![("CB-AFTER".to_owned(), "42".to_owned())],
+            body: br#"{"message": "Insufficient funds"}"#.to_vec()
+        };
+        transport.push_response(Method::Delete, "/orders", response.clone());
+        response.status = 200;
+        response.body = b"[]".to_vec();
+        transport.push_response(Method::Delete, "/orders", response);
+        transport
+    };
+
+    let recorder = Recorder::create(&path).unwrap();
+    let transport = RecordingTransport::new(exchange, recorder);
+    let mut client = PrivateClient::with_transport("key", "c2VjcmV0", "passphrase", transport).unwrap();
+    match client.cancel_all_orders(None) {
+        Err(Error::Api(e)) => assert_eq!(e.message, r#"{"message": "Insufficient funds"}"#),
+        other => panic!("expected an API error, got {:?}", other)
+    }
+    assert!(client.cancel_all_orders(None).unwrap().is_empty());
+
+    let contents = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(!contents.contains("passphrase"));
+
+    let replay = Replay::from_reader(contents.as_bytes()).unwrap();
+    match replay.records()[0] {
+        Record::Rest { method, ref path, status, ref headers, .. } => {
+            assert_eq!(method, Method::Delete);
+            assert_eq!(path, "/orders");
+            assert_eq!(status, 400);
+            assert_eq!(headers[0], ("CB-AFTER".to_owned(), "42".to_owned()));
+        }
+        ref other => panic!("expected a REST record, got {:?}", other)
+    }
+
+    // The replayed client sees the same sequence of responses
+    let transport = replay.transport();
+    let mut client = PrivateClient::with_transport("key", "c2VjcmV0", "passphrase", transport.clone()).unwrap();
+    assert!(client.cancel_all_orders(None).is_err());
+    assert!(client.cancel_all_orders(None).unwrap().is_empty());
+    assert_eq!(transport.remaining(), 0);
+    let requests = transport.requests();
+    assert!(requests[0].headers.contains(&("CB-ACCESS-KEY".to_owned(), "key".to_owned())));
+}
+
+#[test]
+fn private_clients_send_public_requests_through_their_transport() {
+    let transport = MemoryTransport::new();
+    transport.push_json(Method::Get, "/time", r#"{"iso": "2018-06-01T12:00:00Z", "epoch": 1527854400.0}"#);
+    transport.push_json(Method::Get, "/orders?status=open&status=pending&status=active", "[]");
+    let mut client = PrivateClient::with_transport("key", "c2VjcmV0", "passphrase", transport.clone()).unwrap();
+
+    assert_eq!(client.get_time().unwrap().epoch, 1527854400.0);
+    assert!(client.get_orders().unwrap().is_empty());
+    let requests = transport.requests();
+    assert_eq!(requests.iter().map(|r| r.path()).collect::<Vec<_>>(),
+               vec!["/time", "/orders?status=open&status=pending&status=active"]);
+    assert!(requests[0].headers.iter().all(|(name, _)| name != "CB-ACCESS-KEY"));
+}
+
+#[test]
+fn unrecorded_requests_fail() {
+    let mut client = PublicClient::with_transport(MemoryTransport::new());
+    match client.get_products() {
+        Err(Error::Io(e)) => assert_eq!(e.to_string(), "no response for GET /products"),
+        other => panic!("expected an IO error, got {:?}", other)
+    }
+}
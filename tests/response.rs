@@ -0,0 +1,71 @@
+extern crate gdax_client;
+extern crate serde_json;
+
+use gdax_client::{Error, Method, PublicClient, Response};
+use gdax_client::public::Time;
+use gdax_client::transport::MemoryTransport;
+use serde_json::Value;
+
+fn response(status: u32, headers: &[(&str, &str)], body: &str) -> Response<()> {
+    Response {
+        value: (),
+        status,
+        headers: headers.iter().map(|&(n, v)| (n.to_owned(), v.to_owned())).collect(),
+        body: body.as_bytes().to_vec()
+    }
+}
+
+#[test]
+fn decodes_success_and_keeps_the_raw_response() {
+    let raw = response(200, &[("Content-Type", "application/json"), ("cb-after", "41")],
+                       r#"{"iso": "2015-01-07T23:47:25.201Z", "epoch": 1420674445.201}"#);
+    let decoded = raw.decode::<Time>().unwrap();
+
+    assert_eq!(decoded.value.epoch, 1420674445.201);
+    assert_eq!(decoded.status, 200);
+    assert_eq!(decoded.header("content-type"), Some("application/json"));
+    assert_eq!((decoded.after(), decoded.before()), (Some("41"), None));
+    assert!(String::from_utf8_lossy(&decoded.body).contains("epoch"));
+    assert_eq!(decoded.map(|time| time.iso.timestamp()).into_value(), 1420674445);
+}
+
+#[test]
+fn non_success_statuses_are_errors() {
+    match response(400, &[], r#"{"message": "Invalid size"}"#).decode::<Time>() {
+        Err(Error::Api(e)) => {
+            assert!(e.message.contains("Invalid size"));
+            assert_eq!(e.status, Some(400));
+        }
+        other => panic!("expected an API error, got {:?}", other)
+    }
+    match response(429, &[], r#"{"message": "Rate limit exceeded"}"#).decode::<Time>() {
+        Err(Error::Api(e)) => assert_eq!(e.status, Some(429)),
+        other => panic!("expected an API error, got {:?}", other)
+    }
+    assert!(response(200, &[], "not json").decode::<Time>().is_err());
+}
+
+#[test]
+fn empty_bodies_decode_as_null() {
+    let decoded = response(204, &[], "").decode::<()>().unwrap();
+    assert_eq!(decoded.status, 204);
+    assert_eq!(response(200, &[], " \n").decode::<Option<Time>>().unwrap().value, None);
+    assert!(response(204, &[], "").decode::<Time>().is_err());
+}
+
+#[test]
+fn requests_can_use_any_method() {
+    let transport = MemoryTransport::new();
+    transport.push_json(Method::Put, "/profiles/p1", r#"{"ok": true}"#);
+    transport.push_json(Method::Patch, "/profiles/p1", r#"{"ok": true}"#);
+    let mut client = PublicClient::with_transport(transport.clone());
+
+    let put = client.request::<Value>(Method::Put, "/profiles/p1", r#"{"name": "a"}"#).unwrap();
+    assert_eq!(put.value["ok"], true);
+    client.request::<Value>(Method::Patch, "/profiles/p1", r#"{"name": "b"}"#).unwrap();
+
+    let requests = transport.requests();
+    assert_eq!((requests[0].method, requests[0].body.as_str()), (Method::Put, r#"{"name": "a"}"#));
+    assert_eq!(requests[1].method, Method::Patch);
+    assert_eq!(Method::Patch.as_str(), "PATCH");
+}
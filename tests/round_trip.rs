@@ -0,0 +1,106 @@
+extern crate gdax_client;
+extern crate serde;
+extern crate serde_json;
+
+use gdax_client::Side;
+use gdax_client::private::{Account, Hold, HoldType, Ledger};
+use gdax_client::public::{BookEntry, Candle, FullBookEntry, OrderBook, Product, Trade};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Decodes `json`, re-encodes the result and checks that both the value and
+// the JSON survive the round trip.
+fn round_trip<T>(json: &str) -> T
+    where for<'de> T: Deserialize<'de> + Serialize + PartialEq + ::std::fmt::Debug
+{
+    let value: T = serde_json::from_str(json).unwrap();
+    let encoded = serde_json::to_string(&value).unwrap();
+    let decoded: T = serde_json::from_str(&encoded).unwrap();
+    assert_eq!(value, decoded);
+
+    let original: Value = serde_json::from_str(json).unwrap();
+    let reencoded: Value = serde_json::from_str(&encoded).unwrap();
+    assert_eq!(original, reencoded);
+
+    value
+}
+
+#[test]
+fn product() {
+    round_trip::<Product>(r#"{
+        "id": "BTC-USD", "base_currency": "BTC", "quote_currency": "USD",
+        "base_min_size": "0.001", "base_max_size": "70", "quote_increment": "0.01",
+        "status": "online", "margin_enabled": false,
+        "min_market_funds": "10", "max_market_funds": "1000000",
+        "post_only": false, "limit_only": false, "cancel_only": false
+    }"#);
+}
+
+#[test]
+fn books_keep_array_entries() {
+    let book = round_trip::<OrderBook<BookEntry>>(r#"{
+        "sequence": 3,
+        "bids": [["6500.11", "0.45", 2]],
+        "asks": [["6500.15", "0.57", 1]]
+    }"#);
+    assert_eq!(book.bids[0].num_orders, 2);
+
+    round_trip::<OrderBook<FullBookEntry>>(r#"{
+        "sequence": 7,
+        "bids": [["6500.11", "0.45", "da863862-25f4-4868-ac41-005d11ab0a5f"]],
+        "asks": []
+    }"#);
+}
+
+#[test]
+fn candles_keep_array_form() {
+    let candles = round_trip::<Vec<Candle>>("[[1415398768, 0.32, 4.2, 0.35, 4.2, 12.3]]");
+    assert_eq!(candles[0].high, 4.2);
+}
+
+#[test]
+fn trade() {
+    let trades = round_trip::<Vec<Trade>>(r#"[{
+        "time": "2014-11-07T22:19:28.578544Z", "trade_id": 74,
+        "price": "10.00000000", "size": "0.01000000", "side": "buy"
+    }]"#);
+    assert_eq!(trades[0].side, Side::Buy);
+}
+
+#[test]
+fn account() {
+    round_trip::<Account>(r#"{
+        "id": "71452118-efc7-4cc4-8780-a5e22d4baa53", "balance": 1.1,
+        "hold": 0.1, "available": 1.0, "currency": "BTC"
+    }"#);
+}
+
+#[test]
+fn ledger() {
+    round_trip::<Ledger>(r#"[
+        {"id": 100, "created_at": "2014-11-07T08:19:27.028459Z", "amount": 0.001,
+         "balance": 239.669, "type": "fee",
+         "details": {"order_id": "d50ec984-77a8-460a-b958-66f114b0de9b",
+                     "trade_id": 74, "product_id": "BTC-USD"}},
+        {"id": 101, "created_at": "2014-11-07T08:19:27.028459Z", "amount": 10.0,
+         "balance": 249.669, "type": "conversion",
+         "details": {"conversion_id": "8942caee-f9d5-4600-a894-4811268545db"}},
+        {"id": 102, "created_at": "2014-11-07T08:19:27.028459Z", "amount": 1.0,
+         "balance": 250.669, "type": "margin_interest", "details": {"loan": 1}},
+        {"id": 103, "created_at": "2014-11-07T08:19:27.028459Z", "amount": 1.0,
+         "balance": 251.669, "type": "rebate", "details": null}
+    ]"#);
+}
+
+#[test]
+fn hold() {
+    let hold = round_trip::<Hold>(r#"{
+        "id": "82dcd140-c3c7-4507-8de4-2c529cd1a28f",
+        "account_id": "e0b3f39a-183d-453e-b754-0c13e5bab0b3",
+        "created_at": "2014-11-06T10:34:47.123456Z",
+        "updated_at": "2014-11-06T10:40:47.123456Z",
+        "amount": 4.23, "type": "order",
+        "ref": "0a205de4-dd35-4370-a285-fe8fc375a273"
+    }"#);
+    assert_eq!(hold.hold_type, HoldType::Order);
+}
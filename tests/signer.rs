@@ -0,0 +1,91 @@
+extern crate gdax_client;
+
+use gdax_client::{Error, Method, NewOrder, PrivateClient, Side};
+use gdax_client::signer::{HmacSigner, RequestSigner, SigningRequest};
+use gdax_client::transport::MemoryTransport;
+
+const ORDER: &str = r#"{"type":"limit","side":"buy","product_id":"BTC-USD","price":100.0,"size":1.0}"#;
+
+fn request<'a>(method: Method, path: &'a str, body: &'a str) -> SigningRequest<'a> {
+    SigningRequest {
+        method,
+        url: "https://api.gdax.com",
+        path,
+        body,
+        timestamp: "1527854400"
+    }
+}
+
+#[test]
+fn prehash_format() {
+    assert_eq!(HmacSigner::prehash(&request(Method::Get, "/orders?status=open", "")),
+               "1527854400GET/orders?status=open");
+    assert_eq!(HmacSigner::prehash(&request(Method::Delete, "/orders/42", "")),
+               "1527854400DELETE/orders/42");
+    assert_eq!(HmacSigner::prehash(&request(Method::Post, "/orders", ORDER)),
+               format!("1527854400POST/orders{}", ORDER));
+}
+
+#[test]
+fn hmac_sha256_known_answers() {
+    // RFC 4231, test case 2, with the key base64 encoded as the API
+    // hands out secrets
+    let signer = HmacSigner::new("key", "SmVmZQ==", "passphrase").unwrap();
+    assert_eq!(signer.signature("what do ya want for nothing?"), "W9zBRr9gdU5qBCQmCJV1x1oAPwidJzmDnexYuWTsOEM=");
+
+    let signer = HmacSigner::new("key", "c2VjcmV0", "passphrase").unwrap();
+    let headers = signer.sign(&request(Method::Post, "/orders", ORDER)).unwrap();
+    assert_eq!(headers, vec![
+        ("CB-ACCESS-KEY".to_owned(), "key".to_owned()),
+        ("CB-ACCESS-SIGN".to_owned(), "Mw1ZYYh7hOZG9UHlsvFfLxbQ5Qks60IO6WfjGMr70OE=".to_owned()),
+        ("CB-ACCESS-PASSPHRASE".to_owned(), "passphrase".to_owned()),
+        ("CB-ACCESS-TIMESTAMP".to_owned(), "1527854400".to_owned()),
+    ]);
+}
+
+#[test]
+fn client_signs_the_body_it_sends() {
+    let transport = MemoryTransport::new();
+    transport.push_json(Method::Post, "/orders", r#"{"id": "00000000-0000-0000-0000-00000000000a"}"#);
+    let mut client = PrivateClient::with_transport("key", "c2VjcmV0", "passphrase", transport.clone()).unwrap();
+    client.post_order(&NewOrder::limit(Side::Buy, "BTC-USD", 1.0, 100.0)).unwrap();
+
+    let sent = &transport.requests()[0];
+    let header = |name: &str| sent.headers.iter().find(|(n, _)| n == name).unwrap().1.clone();
+    let timestamp = header("CB-ACCESS-TIMESTAMP");
+    let signer = HmacSigner::new("key", "c2VjcmV0", "passphrase").unwrap();
+    let expected = signer.signature(&format!("{}POST/orders{}", timestamp, sent.body));
+    assert_eq!(header("CB-ACCESS-SIGN"), expected);
+
+    // The transport doesn't hold on to the passphrase
+    assert_eq!(header("CB-ACCESS-PASSPHRASE"), "<redacted>");
+}
+
+// Stands in for a signer backed by an HSM or a remote service
+#[derive(Debug)]
+struct BearerSigner;
+
+impl RequestSigner for BearerSigner {
+    fn sign(&self, request: &SigningRequest) -> Result<Vec<(String, String)>, Error> {
+        if request.path.starts_with("/forbidden") {
+            return Err(Error::MissingCredentials("signing service refused".to_owned()));
+        }
+        Ok(vec![("Authorization".to_owned(), format!("Bearer {} {}", request.method.as_str(), request.path))])
+    }
+}
+
+#[test]
+fn custom_signers_replace_api_key_headers() {
+    let transport = MemoryTransport::new();
+    transport.push_json(Method::Get, "/accounts", "[]");
+    let mut client = PrivateClient::with_signer(BearerSigner, transport.clone());
+    client.get_accounts().unwrap();
+
+    let headers = &transport.requests()[0].headers;
+    assert!(headers.contains(&("Authorization".to_owned(), "Bearer GET /accounts".to_owned())));
+    assert!(!headers.iter().any(|(name, _)| name.starts_with("CB-ACCESS")));
+
+    // Signing failures are returned without sending anything
+    assert!(client.request::<Vec<u8>>(Method::Get, "/forbidden", "").is_err());
+    assert_eq!(transport.requests().len(), 1);
+}
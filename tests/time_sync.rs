@@ -0,0 +1,113 @@
+extern crate gdax_client;
+
+use gdax_client::{Error, Method, PrivateClient, Response};
+use gdax_client::clock::{local_now, ClockSync};
+use gdax_client::transport::{MemoryTransport, Request};
+use std::time::Duration;
+
+fn server_time(transport: &MemoryTransport, epoch: f64) {
+    transport.push_json(Method::Get, "/time", &format!(r#"{{"iso": "2018-06-01T12:00:00Z", "epoch": {}}}"#, epoch));
+}
+
+fn timestamp(request: &Request) -> i64 {
+    let (_, value) = request.headers.iter().find(|(name, _)| name == "CB-ACCESS-TIMESTAMP").unwrap();
+    value.parse().unwrap()
+}
+
+fn client(transport: &MemoryTransport) -> PrivateClient {
+    PrivateClient::with_transport("key", "c2VjcmV0", "passphrase", transport.clone()).unwrap()
+}
+
+#[test]
+fn offset_is_smoothed_across_samples() {
+    let mut clock = ClockSync::new(Duration::from_secs(60));
+    assert!(clock.is_due());
+
+    clock.record(1000.0, 1100.0, 1000.2);
+    assert!((clock.offset().unwrap() - 99.9).abs() < 1e-9);
+    assert!(!clock.is_due());
+
+    // A single jittery sample only moves the estimate part of the way
+    clock.record(2000.0, 2107.9, 2000.0);
+    assert!((clock.offset().unwrap() - 101.9).abs() < 1e-9);
+
+    // After a rejection the next sample replaces the estimate
+    clock.invalidate();
+    assert!(clock.is_due());
+    clock.record(3000.0, 2950.0, 3000.0);
+    assert_eq!(clock.offset(), Some(-50.0));
+}
+
+#[test]
+fn signs_with_the_server_clock() {
+    let transport = MemoryTransport::new();
+    server_time(&transport, local_now() + 3600.0);
+    transport.push_json(Method::Get, "/accounts", "[]");
+
+    let mut client = client(&transport);
+    client.enable_time_sync(Duration::from_secs(300)).unwrap();
+    assert!((client.time_offset().unwrap() - 3600.0).abs() < 5.0);
+    client.get_accounts().unwrap();
+
+    let requests = transport.requests();
+    assert_eq!(requests[0].path(), "/time");
+    assert!(!requests[0].headers.iter().any(|(name, _)| name == "CB-ACCESS-SIGN"));
+    assert!((timestamp(&requests[1]) - (local_now() as i64 + 3600)).abs() <= 5);
+}
+
+#[test]
+fn refreshes_when_due() {
+    let transport = MemoryTransport::new();
+    server_time(&transport, local_now() + 10.0);
+    server_time(&transport, local_now() + 10.0);
+    transport.push_json(Method::Get, "/accounts", "[]");
+
+    let mut client = client(&transport);
+    client.enable_time_sync(Duration::from_secs(0)).unwrap();
+    client.get_accounts().unwrap();
+
+    let paths: Vec<String> = transport.requests().iter().map(|r| r.path().to_owned()).collect();
+    assert_eq!(paths, vec!["/time", "/time", "/accounts"]);
+}
+
+#[test]
+fn timestamp_rejections_are_identified_and_resync() {
+    let transport = MemoryTransport::new();
+    server_time(&transport, local_now());
+    transport.push_response(Method::Get, "/accounts", Response {
+        value: (),
+        status: 400,
+        headers: vec![],
+        body: br#"{"message": "request timestamp expired"}"#.to_vec()
+    });
+    server_time(&transport, local_now() - 120.0);
+    transport.push_json(Method::Get, "/accounts", "[]");
+
+    let mut client = client(&transport);
+    client.enable_time_sync(Duration::from_secs(300)).unwrap();
+    match client.get_accounts() {
+        Err(Error::TimestampSkew(e)) => assert!(e.message.contains("request timestamp expired")),
+        other => panic!("expected a timestamp rejection, got {:?}", other)
+    }
+
+    // The retry resamples the clock first, taking the new offset outright
+    client.get_accounts().unwrap();
+    assert!((client.time_offset().unwrap() + 120.0).abs() < 5.0);
+    assert_eq!(transport.remaining(), 0);
+}
+
+#[test]
+fn rejections_are_identified_without_time_sync() {
+    let transport = MemoryTransport::new();
+    transport.push_response(Method::Get, "/accounts", Response {
+        value: (),
+        status: 400,
+        headers: vec![],
+        body: br#"{"message": "invalid timestamp"}"#.to_vec()
+    });
+
+    match client(&transport).get_accounts() {
+        Err(Error::TimestampSkew(_)) => {}
+        other => panic!("expected a timestamp rejection, got {:?}", other)
+    }
+}
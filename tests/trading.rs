@@ -0,0 +1,74 @@
+extern crate chrono;
+extern crate gdax_client;
+
+use chrono::{Duration, Utc};
+use gdax_client::{Error, MarketData, NewOrder, Side, Trading};
+use gdax_client::mock::MockExchange;
+use gdax_client::paper::PaperTrader;
+use gdax_client::public::Granularity;
+
+// A strategy that only knows the traits
+fn place_and_cancel<T: Trading>(trader: &mut T) -> Result<(f64, usize), Error> {
+    let first = trader.post_order(&NewOrder::limit(Side::Buy, "BTC-USD", 1.0, 90.0))?;
+    trader.post_order(&NewOrder::limit(Side::Buy, "BTC-USD", 2.0, 80.0))?;
+    let usd = trader.get_accounts()?.into_iter().find(|a| a.currency == "USD").unwrap();
+    assert_eq!(trader.get_account_holds(usd.id)?.len(), 2);
+
+    assert_eq!(trader.cancel_order(first)?, first);
+    let open = trader.get_orders()?.len();
+    assert_eq!(trader.cancel_all_orders(Some("BTC-USD"))?.len(), 1);
+    assert!(trader.get_orders()?.is_empty());
+    assert_eq!(trader.get_order(first)?.done_reason.as_deref(), Some("canceled"));
+    Ok((usd.hold, open))
+}
+
+// The traded volume according to the trade pages and to the candles
+fn volumes<M: MarketData>(market: &mut M) -> Result<(f64, f64), Error> {
+    let page = market.get_trades_page("BTC-USD", None)?;
+    let traded = page.value.iter().map(|t| t.size.parse::<f64>().unwrap()).sum();
+    let now = Utc::now();
+    let range = market.get_historic_rates_range("BTC-USD", now - Duration::minutes(5), now + Duration::minutes(1),
+                                                 Granularity::OneMinute)?;
+    Ok((traded, range.candles.iter().map(|c| c.volume).sum()))
+}
+
+fn spread<M: MarketData>(market: &mut M) -> Result<f64, Error> {
+    let book = market.get_top50_orders("BTC-USD")?;
+    let bid: f64 = book.bids[0].price.parse().unwrap();
+    let ask: f64 = book.asks[0].price.parse().unwrap();
+    Ok(ask - bid)
+}
+
+#[test]
+fn strategies_run_against_any_backend() {
+    let exchange = MockExchange::start().unwrap();
+    exchange.deposit("USD", 1000.0);
+    let mut client = exchange.private_client();
+    assert_eq!(place_and_cancel(&mut client).unwrap(), (250.0, 1));
+
+    let mut paper = PaperTrader::new();
+    paper.deposit("USD", 1000.0);
+    assert_eq!(place_and_cancel(&mut paper).unwrap(), (250.0, 1));
+}
+
+#[test]
+fn market_data_from_either_client() {
+    let exchange = MockExchange::start().unwrap();
+    exchange.place_order(&NewOrder::limit(Side::Buy, "BTC-USD", 1.0, 99.0)).unwrap();
+    exchange.place_order(&NewOrder::limit(Side::Sell, "BTC-USD", 1.0, 101.5)).unwrap();
+
+    assert_eq!(spread(&mut exchange.public_client()).unwrap(), 2.5);
+    let mut private = exchange.private_client();
+    assert_eq!(spread(&mut private).unwrap(), 2.5);
+    assert_eq!(MarketData::get_products(&mut private).unwrap()[0].id, "BTC-USD");
+}
+
+#[test]
+fn paged_and_ranged_market_data_from_either_client() {
+    let exchange = MockExchange::start().unwrap();
+    exchange.place_order(&NewOrder::limit(Side::Sell, "BTC-USD", 1.5, 100.0)).unwrap();
+    exchange.place_order(&NewOrder::limit(Side::Buy, "BTC-USD", 1.5, 100.0)).unwrap();
+
+    assert_eq!(volumes(&mut exchange.public_client()).unwrap(), (1.5, 1.5));
+    assert_eq!(volumes(&mut exchange.private_client()).unwrap(), (1.5, 1.5));
+}
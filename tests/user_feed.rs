@@ -0,0 +1,93 @@
+extern crate futures;
+extern crate gdax_client;
+extern crate serde_json;
+extern crate tungstenite;
+
+use futures::Stream;
+use gdax_client::{Error, PrivateClient};
+use gdax_client::feed::{OrderEvent, UserFeed};
+use gdax_client::signer::{HmacSigner, RequestSigner, SigningRequest};
+use gdax_client::transport::MemoryTransport;
+use serde_json::{json, Value};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+
+// Signs as of a fixed time, so the signature is known in advance
+#[derive(Debug)]
+struct FixedTime(HmacSigner);
+
+impl RequestSigner for FixedTime {
+    fn sign(&self, request: &SigningRequest) -> Result<Vec<(String, String)>, Error> {
+        self.0.sign(&SigningRequest { timestamp: "1527854400", ..*request })
+    }
+}
+
+const OURS: &str = r#"{"type": "open", "time": "2018-06-01T12:00:00.000000Z", "product_id": "BTC-USD",
+    "sequence": 10, "order_id": "d50ec984-77a8-460a-b958-66f114b0de9b", "price": "200.2",
+    "remaining_size": "1.00", "side": "sell", "user_id": "5844eceecf7e803e259d0365",
+    "profile_id": "765d1549-9660-4be2-97d4-fa2d65fa3352"}"#;
+
+const PUBLIC: &str = r#"{"type": "open", "time": "2018-06-01T12:00:00.000000Z", "product_id": "BTC-USD",
+    "sequence": 11, "order_id": "ac928c66-ca53-498f-9c13-a110027a60e8", "price": "200.3",
+    "remaining_size": "2.00", "side": "sell"}"#;
+
+// A local feed that sends a public order and then one of ours, and passes
+// on the subscribe request
+fn serve() -> (String, mpsc::Receiver<Value>, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let (tx, requests) = mpsc::channel();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut socket = tungstenite::accept(stream).unwrap();
+        let request = socket.read().unwrap();
+        tx.send(serde_json::from_str::<Value>(request.to_text().unwrap()).unwrap()).unwrap();
+        socket.send(tungstenite::Message::Text(PUBLIC.to_owned())).unwrap();
+        socket.send(tungstenite::Message::Text(OURS.to_owned())).unwrap();
+        socket.close(None).unwrap();
+        let _ = socket.flush();
+    });
+    (url, requests, server)
+}
+
+fn client() -> PrivateClient {
+    let signer = FixedTime(HmacSigner::new("key", "c2VjcmV0", "passphrase").unwrap());
+    PrivateClient::with_signer(signer, MemoryTransport::new())
+}
+
+#[test]
+fn subscriptions_are_signed_like_a_verify_request() {
+    let (url, requests, server) = serve();
+    let mut feed = UserFeed::connect_to(&url, &client(), &["BTC-USD"]).unwrap();
+
+    // HMAC-SHA256 of "1527854400GET/users/self/verify", keyed with "secret"
+    assert_eq!(requests.recv().unwrap(), json!({
+        "type": "subscribe",
+        "product_ids": ["BTC-USD"],
+        "channels": ["user"],
+        "key": "key",
+        "passphrase": "passphrase",
+        "signature": "9ACRRQayET31J7MyOdvGr1yetRnPpNx0aQ7uqLCRN3s=",
+        "timestamp": "1527854400"
+    }));
+
+    // Only our own order comes through
+    match feed.next_event().unwrap() {
+        Some(OrderEvent::Open(ref open)) => assert_eq!(open.sequence, 10),
+        other => panic!("expected our open order, got {:?}", other)
+    }
+    assert!(feed.next_event().unwrap().is_none());
+    server.join().unwrap();
+}
+
+#[test]
+fn streams_our_orders() {
+    let (url, _requests, server) = serve();
+    let feed = UserFeed::connect_to(&url, &client(), &["BTC-USD"]).unwrap();
+
+    let events: Vec<OrderEvent> = feed.into_stream().wait().collect::<Result<_, _>>().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].order_id().to_string(), "d50ec984-77a8-460a-b958-66f114b0de9b");
+    server.join().unwrap();
+}